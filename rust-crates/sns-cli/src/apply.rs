@@ -0,0 +1,948 @@
+//! Declarative changes of many domains from a manifest.
+//!
+//! The manifest describes the desired owner, records and favourite status of each domain. It is
+//! compared with the chain state, fetched in batches, and only the differences are applied, so
+//! that applying the same manifest twice is a no-op. Records that are not listed in the manifest
+//! are left untouched.
+//!
+//! A TOML manifest has one `[[domain]]` table per domain, all fields except `name` are optional:
+//!
+//! ```toml
+//! [[domain]]
+//! name = "bonfida"
+//! owner = "HKKp49qGWXd639QsuH7JiLijfVW5UtCVY4s1n2HANwEA"
+//! favourite = true
+//! records = { IPFS = "ipfs://...", url = "https://sns.id" }
+//! records_v2 = { TXT = "hello", SOL = "..." }
+//! ```
+//!
+//! A CSV manifest has the columns `domain,owner,favourite,record,version,content` and one line
+//! per record, the other columns can be left empty on all but one line of each domain. The
+//! version of a record is 1 or 2, and defaults to 2.
+//!
+//! The changes of the configured keypair, which must own the domains to change, are sent in
+//! three steps: the V1 records which have to be resized are deleted, then the records and the
+//! favourite domain are written, then the domains are transferred. V2 records written for a
+//! domain which is not transferred are marked as written by its owner (staleness validation).
+//! A favourite domain can only be set when the configured keypair is its desired owner. A V1
+//! record can only be deleted by its own owner: the records created by a previous owner of a
+//! domain are reported instead of being resized.
+use {
+    crate::{
+        config::Config,
+        output::{format_domain, Output, OutputFormat, Row},
+        tx::{TxArgs, TxSender},
+    },
+    anyhow::{anyhow, bail, Context},
+    serde::{Deserialize, Serialize},
+    sns_sdk::{
+        derivation::{get_domain_key, get_hashed_name, get_prefix, trim_tld, Domain},
+        favourite_domain::get_set_favourite_instruction,
+        non_blocking::resolve::{
+            deserialize_name_registry, get_multiple_primary_domains, resolve_name_registry_batch,
+        },
+        record::{
+            get_record_key, record_v1,
+            record_v2::{
+                create_record_v2_instruction, parse_record_v2, serialize_record_v2_content,
+                update_record_v2_instruction, validate_record_v2_content_instruction,
+            },
+            Record, RecordVersion,
+        },
+    },
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey},
+    spl_name_service::state::NameRecordHeader,
+    std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        io::{BufRead, IsTerminal, Write},
+        path::Path,
+        str::FromStr,
+    },
+};
+
+/// The desired state of a domain
+#[derive(Debug, Default)]
+pub struct DomainSpec {
+    pub domain: String,
+    pub owner: Option<Pubkey>,
+    pub favourite: bool,
+    pub records: Vec<(Record, RecordVersion, String)>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlManifest {
+    #[serde(default)]
+    domain: Vec<TomlDomain>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlDomain {
+    name: String,
+    owner: Option<String>,
+    #[serde(default)]
+    favourite: bool,
+    #[serde(default)]
+    records: BTreeMap<String, String>,
+    #[serde(default)]
+    records_v2: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CsvLine {
+    domain: String,
+    owner: Option<String>,
+    favourite: Option<bool>,
+    record: Option<String>,
+    version: Option<u8>,
+    content: Option<String>,
+}
+
+fn parse_owner(domain: &str, owner: &str) -> anyhow::Result<Pubkey> {
+    Pubkey::from_str(owner).map_err(|_| anyhow!("Invalid owner {owner:?} for {domain}"))
+}
+
+fn parse_record(domain: &str, record: &str) -> anyhow::Result<Record> {
+    Record::try_from_str(record).with_context(|| format!("Invalid record for {domain}"))
+}
+
+/// Reads a `.toml` or `.csv` manifest
+pub fn read_manifest(path: &Path) -> anyhow::Result<Vec<DomainSpec>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let specs = match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => parse_toml_manifest(&content),
+        Some("csv") => parse_csv_manifest(&content),
+        _ => Err(anyhow!("The manifest must be a .toml or a .csv file")),
+    }
+    .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let mut seen = HashSet::new();
+    for spec in &specs {
+        if !seen.insert(spec.domain.as_str()) {
+            bail!("{} is listed more than once", format_domain(&spec.domain));
+        }
+        let mut records = HashSet::new();
+        for (record, version, _) in &spec.records {
+            if !records.insert((record.as_str(), *version as u8)) {
+                bail!(
+                    "The {record} record of {} is listed more than once",
+                    format_domain(&spec.domain)
+                );
+            }
+        }
+    }
+    let favourites = specs.iter().filter(|s| s.favourite).count();
+    if favourites > 1 {
+        bail!("Only one domain can be the favourite domain, found {favourites}");
+    }
+    Ok(specs)
+}
+
+fn parse_toml_manifest(content: &str) -> anyhow::Result<Vec<DomainSpec>> {
+    let manifest: TomlManifest = toml::from_str(content)?;
+    manifest
+        .domain
+        .into_iter()
+        .map(|d| {
+            let domain = trim_tld(&d.name).to_owned();
+            let records = d
+                .records
+                .iter()
+                .map(|r| (r, RecordVersion::V1))
+                .chain(d.records_v2.iter().map(|r| (r, RecordVersion::V2)))
+                .map(|((record, content), version)| {
+                    Ok((parse_record(&domain, record)?, version, content.clone()))
+                })
+                .collect::<anyhow::Result<_>>()?;
+            Ok(DomainSpec {
+                owner: d.owner.map(|o| parse_owner(&domain, &o)).transpose()?,
+                favourite: d.favourite,
+                records,
+                domain,
+            })
+        })
+        .collect()
+}
+
+fn parse_csv_manifest(content: &str) -> anyhow::Result<Vec<DomainSpec>> {
+    let mut specs: Vec<DomainSpec> = vec![];
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    for line in reader.deserialize() {
+        let line: CsvLine = line?;
+        let domain = trim_tld(&line.domain).to_owned();
+        let position = match specs.iter().position(|s| s.domain == domain) {
+            Some(position) => position,
+            None => {
+                specs.push(DomainSpec {
+                    domain: domain.clone(),
+                    ..Default::default()
+                });
+                specs.len() - 1
+            }
+        };
+        let spec = &mut specs[position];
+
+        if let Some(owner) = line.owner {
+            let owner = parse_owner(&domain, &owner)?;
+            if spec.owner.is_some_and(|o| o != owner) {
+                bail!("{} has more than one owner", format_domain(&domain));
+            }
+            spec.owner = Some(owner);
+        }
+        spec.favourite |= line.favourite.unwrap_or_default();
+        match (line.record, line.content) {
+            (Some(record), Some(content)) => {
+                let version = match line.version {
+                    Some(1) => RecordVersion::V1,
+                    Some(2) | None => RecordVersion::V2,
+                    Some(v) => bail!("Invalid record version {v} for {domain}"),
+                };
+                spec.records
+                    .push((parse_record(&domain, &record)?, version, content));
+            }
+            (None, None) => {}
+            _ => bail!(
+                "Both the record and its content are required for {}",
+                format_domain(&domain)
+            ),
+        }
+    }
+    Ok(specs)
+}
+
+/// The step in which a change is sent, the changes of a step are sent once the previous step
+/// has landed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Step {
+    Cleanup,
+    Write,
+    Transfer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    CreateRecord,
+    UpdateRecord,
+    SetFavourite,
+    Transfer,
+}
+
+impl Action {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::CreateRecord => "Create record",
+            Self::UpdateRecord => "Update record",
+            Self::SetFavourite => "Set favourite",
+            Self::Transfer => "Transfer",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PlanRow {
+    pub domain: String,
+    pub action: Action,
+    pub record: Option<String>,
+    pub version: Option<u8>,
+    pub current: Option<String>,
+    pub desired: String,
+}
+
+impl PlanRow {
+    /// The label of the transactions of the change, e.g. `IPFS.bonfida.sol` for a record
+    fn label(&self) -> String {
+        match &self.record {
+            Some(record) => format!("{record}.{}", self.domain),
+            None => self.domain.clone(),
+        }
+    }
+}
+
+impl Row for PlanRow {
+    const HEADERS: &'static [&'static str] = &["Domain", "Action", "Record", "Current", "Desired"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.domain.clone(),
+            self.action.as_str().to_owned(),
+            match (&self.record, self.version) {
+                (Some(record), Some(version)) => format!("{record} (V{version})"),
+                _ => String::new(),
+            },
+            self.current.clone().unwrap_or_default(),
+            self.desired.clone(),
+        ]
+    }
+}
+
+struct Change {
+    row: PlanRow,
+    /// The instructions of each step
+    instructions: Vec<(Step, Vec<Instruction>)>,
+}
+
+/// Builds the changes needed to reach the state of `specs`, in the order of the manifest
+async fn plan(
+    rpc_client: &RpcClient,
+    specs: &[DomainSpec],
+    authority: Pubkey,
+    payer: Pubkey,
+) -> anyhow::Result<Vec<Change>> {
+    let domain_keys = specs
+        .iter()
+        .map(|s| get_domain_key(&s.domain))
+        .collect::<Result<Vec<_>, _>>()?;
+    let registries = resolve_name_registry_batch(rpc_client, &domain_keys).await?;
+
+    let record_keys = specs
+        .iter()
+        .flat_map(|s| {
+            s.records
+                .iter()
+                .map(|(record, version, _)| get_record_key(&s.domain, record.clone(), *version))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut record_accounts = vec![];
+    for keys in record_keys.chunks(100) {
+        record_accounts.extend(rpc_client.get_multiple_accounts(keys).await?);
+    }
+    let mut record_accounts = record_keys.iter().zip(record_accounts);
+
+    let favourite = match specs.iter().any(|s| s.favourite) {
+        true => get_multiple_primary_domains(rpc_client, &[authority])
+            .await?
+            .pop()
+            .flatten()
+            .filter(|f| !f.stale)
+            .map(|f| f.domain),
+        false => None,
+    };
+
+    let mut rent = HashMap::new();
+    let mut changes = vec![];
+    let mut not_owned = vec![];
+    let mut previous_owners = vec![];
+    for ((spec, domain_key), registry) in specs.iter().zip(&domain_keys).zip(registries) {
+        let domain = format_domain(&spec.domain);
+        let (header, _) = registry.ok_or_else(|| anyhow!("{domain} does not exist"))?;
+        let owner = header.owner;
+        let transfer = spec.owner.filter(|o| *o != owner);
+        let first_change = changes.len();
+
+        for (record, version, content) in &spec.records {
+            let (key, account) = record_accounts.next().unwrap();
+            let row = |action, current| PlanRow {
+                domain: domain.clone(),
+                action,
+                record: Some(record.as_str().to_owned()),
+                version: Some(*version as u8),
+                current,
+                desired: content.clone(),
+            };
+            match version {
+                RecordVersion::V1 => {
+                    let data = record_v1::serialize_record(content, record.clone())
+                        .with_context(|| format!("Invalid {record} record for {domain}"))?;
+                    let registry = account
+                        .map(|a| deserialize_name_registry(&a.data))
+                        .transpose()
+                        .with_context(|| format!("Invalid {record} record of {domain}"))?;
+                    let record_owner = registry.as_ref().map(|(header, _)| header.owner);
+                    let current = registry.map(|(_, data)| data);
+                    let current_content = current.as_ref().map(|c| {
+                        record_v1::deserialize_record(c, record.clone(), key, &owner).ok()
+                    });
+                    if current.as_ref() == Some(&data)
+                        || current_content
+                            .as_ref()
+                            .is_some_and(|c| c.as_ref() == Some(content))
+                    {
+                        continue;
+                    }
+
+                    let mut instructions = vec![];
+                    let create = match &current {
+                        Some(current) if current.len() == data.len() => false,
+                        Some(_) if record_owner != Some(owner) => {
+                            // The domain owner can update the record as the owner of its parent,
+                            // but only the record owner can delete it
+                            previous_owners.push(format!(
+                                "{record}.{domain} ({})",
+                                record_owner.unwrap_or_default()
+                            ));
+                            continue;
+                        }
+                        Some(_) => {
+                            // Resizing requires the record to be deleted first
+                            let ix = spl_name_service::instruction::delete(
+                                spl_name_service::ID,
+                                *key,
+                                owner,
+                                payer,
+                            )?;
+                            instructions.push((Step::Cleanup, vec![ix]));
+                            true
+                        }
+                        None => true,
+                    };
+                    let mut ixs = vec![];
+                    if create {
+                        let space = data.len() + NameRecordHeader::LEN;
+                        let lamports = match rent.get(&space) {
+                            Some(lamports) => *lamports,
+                            None => {
+                                let lamports = rpc_client
+                                    .get_minimum_balance_for_rent_exemption(space)
+                                    .await?;
+                                *rent.entry(space).or_insert(lamports)
+                            }
+                        };
+                        let hashed_name = get_hashed_name(&format!(
+                            "{}{}",
+                            get_prefix(Domain::Record(RecordVersion::V1)),
+                            record.as_str()
+                        ));
+                        ixs.push(spl_name_service::instruction::create(
+                            spl_name_service::ID,
+                            spl_name_service::instruction::NameRegistryInstruction::Create {
+                                hashed_name,
+                                lamports,
+                                space: data.len() as u32,
+                            },
+                            *key,
+                            payer,
+                            owner,
+                            None,
+                            Some(*domain_key),
+                            Some(owner),
+                        )?);
+                    }
+                    ixs.push(spl_name_service::instruction::update(
+                        spl_name_service::ID,
+                        0,
+                        data,
+                        *key,
+                        owner,
+                        Some(*domain_key),
+                    )?);
+                    instructions.push((Step::Write, ixs));
+                    changes.push(Change {
+                        row: row(
+                            if create && current.is_none() {
+                                Action::CreateRecord
+                            } else {
+                                Action::UpdateRecord
+                            },
+                            current_content.map(|c| c.unwrap_or_else(|| "<invalid>".to_owned())),
+                        ),
+                        instructions,
+                    });
+                }
+                RecordVersion::V2 => {
+                    serialize_record_v2_content(content, record.clone())
+                        .with_context(|| format!("Invalid {record} record for {domain}"))?;
+                    let current = match &account {
+                        Some(account) => Some(
+                            parse_record_v2(record.clone(), &account.data)
+                                .await
+                                .map(|r| r.content)
+                                .ok(),
+                        ),
+                        None => None,
+                    };
+                    if current
+                        .as_ref()
+                        .is_some_and(|c| c.as_ref() == Some(content))
+                    {
+                        continue;
+                    }
+                    let (action, ix) = match &current {
+                        Some(_) => (
+                            Action::UpdateRecord,
+                            update_record_v2_instruction(
+                                &spec.domain,
+                                record.clone(),
+                                content,
+                                owner,
+                                payer,
+                            )?,
+                        ),
+                        None => (
+                            Action::CreateRecord,
+                            create_record_v2_instruction(
+                                &spec.domain,
+                                record.clone(),
+                                content,
+                                owner,
+                                payer,
+                            )?,
+                        ),
+                    };
+                    let mut ixs = vec![ix];
+                    // The new owner would have to sign the staleness of the records again
+                    if transfer.is_none() {
+                        ixs.push(validate_record_v2_content_instruction(
+                            true,
+                            &spec.domain,
+                            record.clone(),
+                            owner,
+                            payer,
+                            owner,
+                        )?);
+                    }
+                    changes.push(Change {
+                        row: row(
+                            action,
+                            current.map(|c| c.unwrap_or_else(|| "<invalid>".to_owned())),
+                        ),
+                        instructions: vec![(Step::Write, ixs)],
+                    });
+                }
+            }
+        }
+
+        if spec.favourite && favourite != Some(*domain_key) {
+            let desired_owner = spec.owner.unwrap_or(owner);
+            if desired_owner != authority {
+                bail!("{domain} can only be set as favourite by its desired owner {desired_owner}");
+            }
+            changes.push(Change {
+                row: PlanRow {
+                    domain: domain.clone(),
+                    action: Action::SetFavourite,
+                    record: None,
+                    version: None,
+                    current: favourite.map(|f| f.to_string()),
+                    desired: domain_key.to_string(),
+                },
+                instructions: vec![(
+                    Step::Write,
+                    vec![get_set_favourite_instruction(&authority, &spec.domain)?],
+                )],
+            });
+        }
+
+        if let Some(new_owner) = transfer {
+            let ix = spl_name_service::instruction::transfer(
+                spl_name_service::ID,
+                new_owner,
+                *domain_key,
+                owner,
+                None,
+            )?;
+            changes.push(Change {
+                row: PlanRow {
+                    domain: domain.clone(),
+                    action: Action::Transfer,
+                    record: None,
+                    version: None,
+                    current: Some(owner.to_string()),
+                    desired: new_owner.to_string(),
+                },
+                instructions: vec![(Step::Transfer, vec![ix])],
+            });
+        }
+
+        if changes.len() > first_change && owner != authority {
+            not_owned.push(format!("{domain} ({owner})"));
+        }
+    }
+
+    if !not_owned.is_empty() {
+        bail!(
+            "The following domains have to be changed but are not owned by {authority}: {}",
+            not_owned.join(", ")
+        );
+    }
+    if !previous_owners.is_empty() {
+        bail!(
+            "The following records have to be resized but are owned by a previous owner of their \
+             domain, who has to delete them first: {}",
+            previous_owners.join(", ")
+        );
+    }
+    Ok(changes)
+}
+
+/// Asks for a confirmation on stderr
+fn confirm(changes: usize) -> anyhow::Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        bail!("Use --yes to apply the changes without a confirmation");
+    }
+    eprint!("Apply {changes} changes? [y/N] ");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn process_apply(
+    rpc_client: &RpcClient,
+    out: &Output,
+    config: &Config,
+    tx_args: &TxArgs,
+    path: &Path,
+    plan_only: bool,
+    yes: bool,
+) -> anyhow::Result<()> {
+    let specs = read_manifest(path)?;
+    let mut sender = TxSender::new(rpc_client, config, tx_args, None)?;
+
+    out.status(&format!(
+        "Comparing {} domains with the chain...",
+        specs.len()
+    ));
+    let changes = plan(rpc_client, &specs, sender.authority(), sender.fee_payer()).await?;
+
+    let (rows, changes): (Vec<_>, Vec<_>) =
+        changes.into_iter().map(|c| (c.row, c.instructions)).unzip();
+
+    let exported = tx_args.sign_only || tx_args.dump_instructions || tx_args.dry_run;
+    let prompt = !plan_only && !exported && !yes && !changes.is_empty();
+    // Scripts applying a manifest only get the results, which keeps the output parsable
+    if plan_only || config.output == OutputFormat::Table {
+        out.print(&rows)?;
+    } else if prompt {
+        // The plan is shown on stderr before asking for a confirmation
+        out.print_table_to_stderr(&rows)?;
+    }
+    if changes.is_empty() {
+        out.status("The domains already match the manifest");
+        return Ok(());
+    }
+    if plan_only {
+        return Ok(());
+    }
+    if prompt && !confirm(changes.len())? {
+        bail!("Aborted");
+    }
+
+    for step in [Step::Cleanup, Step::Write, Step::Transfer] {
+        for (row, instructions) in rows.iter().zip(&changes) {
+            for (_, ixs) in instructions.iter().filter(|(s, _)| *s == step) {
+                sender.execute(&row.label(), ixs.clone()).await?;
+            }
+        }
+        sender.flush().await?;
+        // The next steps depend on this one, applying the manifest again resumes from here
+        if sender.has_failures() {
+            break;
+        }
+    }
+    sender.print(out).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sns_sdk::testing::{self, rpc::LocalRpc};
+    use solana_sdk::{signature::Keypair, signer::Signer};
+
+    fn write_manifest(name: &str, content: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("sns-apply-{}-{name}", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_parse_toml_manifest() {
+        let owner = Keypair::new().pubkey();
+        let specs = parse_toml_manifest(&format!(
+            r#"
+            [[domain]]
+            name = "bonfida.sol"
+            owner = "{owner}"
+            favourite = true
+            records = {{ url = "https://sns.id" }}
+            records_v2 = {{ TXT = "hello" }}
+
+            [[domain]]
+            name = "dex"
+            "#
+        ))
+        .unwrap();
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[0].domain, "bonfida");
+        assert_eq!(specs[0].owner, Some(owner));
+        assert!(specs[0].favourite);
+        assert_eq!(
+            specs[0].records,
+            vec![
+                (Record::Url, RecordVersion::V1, "https://sns.id".to_owned()),
+                (Record::TXT, RecordVersion::V2, "hello".to_owned())
+            ]
+        );
+        assert_eq!(specs[1].domain, "dex");
+        assert_eq!(specs[1].owner, None);
+        assert!(!specs[1].favourite && specs[1].records.is_empty());
+
+        for invalid in [
+            "[[domain]]\nname = \"bonfida\"\nowner = \"not a key\"",
+            "[[domain]]\nname = \"bonfida\"\nrecords = { \"not a record\" = \"hello\" }",
+            "[[domain]]\nname = \"bonfida\"\nunknown = true",
+            "[[domain]]\nowner = \"bonfida\"",
+        ] {
+            assert!(parse_toml_manifest(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_parse_csv_manifest() {
+        let owner = Keypair::new().pubkey();
+        let specs = parse_csv_manifest(&format!(
+            "domain,owner,favourite,record,version,content\n\
+             bonfida.sol,{owner},true,url,1,https://sns.id\n\
+             bonfida,,,TXT,,hello\n\
+             dex,,,,,\n"
+        ))
+        .unwrap();
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[0].domain, "bonfida");
+        assert_eq!(specs[0].owner, Some(owner));
+        assert!(specs[0].favourite);
+        // The version defaults to 2
+        assert_eq!(
+            specs[0].records,
+            vec![
+                (Record::Url, RecordVersion::V1, "https://sns.id".to_owned()),
+                (Record::TXT, RecordVersion::V2, "hello".to_owned())
+            ]
+        );
+        assert_eq!(specs[1].domain, "dex");
+        assert!(specs[1].owner.is_none() && specs[1].records.is_empty());
+
+        let other = Keypair::new().pubkey();
+        for invalid in [
+            // More than one owner
+            format!("domain,owner\nbonfida,{owner}\nbonfida,{other}\n"),
+            "domain,record,version,content\nbonfida,TXT,3,hello\n".to_owned(),
+            // A record without content
+            "domain,record\nbonfida,TXT\n".to_owned(),
+            "domain,owner\nbonfida,not a key\n".to_owned(),
+        ] {
+            assert!(parse_csv_manifest(&invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_read_manifest() {
+        let path = write_manifest("valid.csv", "domain,favourite\nbonfida,true\ndex,\n");
+        assert_eq!(read_manifest(&path).unwrap().len(), 2);
+
+        for (name, content, error) in [
+            (
+                "domains.toml",
+                "[[domain]]\nname = \"bonfida\"\n[[domain]]\nname = \"bonfida.sol\"",
+                "bonfida.sol is listed more than once",
+            ),
+            (
+                "records.csv",
+                "domain,record,content\nbonfida,TXT,hello\nbonfida,TXT,world\n",
+                "The TXT record of bonfida.sol is listed more than once",
+            ),
+            (
+                "favourites.csv",
+                "domain,favourite\nbonfida,true\ndex,true\n",
+                "Only one domain can be the favourite domain, found 2",
+            ),
+            (
+                "manifest.json",
+                "[]",
+                "The manifest must be a .toml or a .csv file",
+            ),
+        ] {
+            let path = write_manifest(name, content);
+            let err = read_manifest(&path).unwrap_err();
+            assert!(format!("{err:#}").contains(error), "{name}: {err:#}");
+        }
+        let owners = write_manifest(
+            "owners.csv",
+            &format!(
+                "domain,owner\nbonfida,{}\nbonfida,{}\n",
+                Keypair::new().pubkey(),
+                Keypair::new().pubkey()
+            ),
+        );
+        let err = read_manifest(&owners).unwrap_err();
+        assert!(format!("{err:#}").contains("bonfida.sol has more than one owner"));
+    }
+
+    fn actions(changes: &[Change]) -> Vec<(String, Action, Option<String>, Vec<Step>)> {
+        changes
+            .iter()
+            .map(|c| {
+                (
+                    c.row.domain.clone(),
+                    c.row.action,
+                    c.row.record.clone(),
+                    c.instructions.iter().map(|(step, _)| *step).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_plan() {
+        let authority = Keypair::new().pubkey();
+        let new_owner = Keypair::new().pubkey();
+        let bonfida = get_domain_key("bonfida").unwrap();
+        let specs = vec![
+            DomainSpec {
+                domain: "bonfida".to_owned(),
+                owner: None,
+                favourite: true,
+                records: vec![
+                    (Record::Url, RecordVersion::V1, "https://sns.id".to_owned()),
+                    (Record::TXT, RecordVersion::V2, "hello".to_owned()),
+                ],
+            },
+            DomainSpec {
+                domain: "dex".to_owned(),
+                owner: Some(new_owner),
+                ..Default::default()
+            },
+        ];
+
+        let state = [
+            testing::domain("bonfida", &authority, &[]).unwrap(),
+            testing::reverse("bonfida").unwrap(),
+            testing::record_v1("bonfida", Record::Url, "https://old.sns.id", &authority).unwrap(),
+        ];
+        let client = LocalRpc::new(
+            state
+                .iter()
+                .cloned()
+                .chain([testing::domain("dex", &authority, &[]).unwrap()]),
+        )
+        .into_client();
+        let changes = plan(&client, &specs, authority, authority).await.unwrap();
+        let domain = |d: &str| format!("{d}.sol");
+        assert_eq!(
+            actions(&changes),
+            vec![
+                (
+                    domain("bonfida"),
+                    Action::UpdateRecord,
+                    Some("url".to_owned()),
+                    vec![Step::Cleanup, Step::Write]
+                ),
+                (
+                    domain("bonfida"),
+                    Action::CreateRecord,
+                    Some("TXT".to_owned()),
+                    vec![Step::Write]
+                ),
+                (
+                    domain("bonfida"),
+                    Action::SetFavourite,
+                    None,
+                    vec![Step::Write]
+                ),
+                (domain("dex"), Action::Transfer, None, vec![Step::Transfer]),
+            ]
+        );
+        assert_eq!(
+            changes[0].row.current.as_deref(),
+            Some("https://old.sns.id")
+        );
+
+        // The state once the changes landed
+        let client = LocalRpc::new(state.into_iter().take(2).chain([
+            testing::record_v1("bonfida", Record::Url, "https://sns.id", &authority).unwrap(),
+            testing::verified_record_v2("bonfida", Record::TXT, "hello", &authority).unwrap(),
+            testing::favourite_domain(&authority, &bonfida),
+            testing::domain("dex", &new_owner, &[]).unwrap(),
+        ]))
+        .into_client();
+        assert!(plan(&client, &specs, authority, authority)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_plan_previous_owner() {
+        let authority = Keypair::new().pubkey();
+        let previous_owner = Keypair::new().pubkey();
+        let client = LocalRpc::new([
+            testing::domain("bonfida", &authority, &[]).unwrap(),
+            testing::record_v1(
+                "bonfida",
+                Record::Url,
+                "https://old.sns.id",
+                &previous_owner,
+            )
+            .unwrap(),
+            testing::record_v1("bonfida", Record::TXT, "hello", &previous_owner).unwrap(),
+        ])
+        .into_client();
+        let spec = |records: Vec<(Record, &str)>| DomainSpec {
+            domain: "bonfida".to_owned(),
+            records: records
+                .into_iter()
+                .map(|(record, content)| (record, RecordVersion::V1, content.to_owned()))
+                .collect(),
+            ..Default::default()
+        };
+
+        // The domain owner updates the records of the previous owner in place
+        let changes = plan(
+            &client,
+            &[spec(vec![(Record::TXT, "world")])],
+            authority,
+            authority,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            actions(&changes),
+            vec![(
+                "bonfida.sol".to_owned(),
+                Action::UpdateRecord,
+                Some("TXT".to_owned()),
+                vec![Step::Write]
+            )]
+        );
+
+        // But cannot delete them to resize them
+        let err = plan(
+            &client,
+            &[spec(vec![
+                (Record::TXT, "world"),
+                (Record::Url, "https://sns.id"),
+            ])],
+            authority,
+            authority,
+        )
+        .await
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "The following records have to be resized but are owned by a previous owner of \
+                 their domain, who has to delete them first: url.bonfida.sol ({previous_owner})"
+            )
+        );
+
+        // Nor change a domain they do not own
+        let err = plan(
+            &client,
+            &[spec(vec![(Record::TXT, "world")])],
+            previous_owner,
+            previous_owner,
+        )
+        .await
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("are not owned by"));
+    }
+}
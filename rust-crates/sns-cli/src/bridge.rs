@@ -0,0 +1,127 @@
+//! The `bridge` command group, for domains bridged to other chains through the SNS warp program.
+//!
+//! Only the state of bridged domains is read: the instruction locking a domain in the warp program
+//! and emitting its VAA is not published, so domains cannot be bridged from here.
+use {
+    crate::output::{cell, format_domain, Output, Row},
+    anyhow::anyhow,
+    clap::{Args, Subcommand},
+    serde::Serialize,
+    sns_sdk::{
+        bridge::{chain_name, derive_x_domain_key, deserialize_x_domain, format_chain_address},
+        derivation::get_domain_key,
+        non_blocking::resolve::resolve_name_registry_batch,
+    },
+    solana_client::nonblocking::rpc_client::RpcClient,
+};
+
+#[derive(Debug, Args)]
+#[command(about = "Inspect domains bridged to other chains through the SNS warp program")]
+pub struct BridgeCommand {
+    #[command(subcommand)]
+    pub cmd: BridgeSubCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BridgeSubCommand {
+    #[command(
+        arg_required_else_help = true,
+        about = "Shows whether domains are bridged and the chain and address of their owner"
+    )]
+    Status {
+        #[arg(
+            required = true,
+            help = "The list of domains with or without .sol suffix"
+        )]
+        domains: Vec<String>,
+    },
+}
+
+pub async fn process_bridge(
+    rpc_client: &RpcClient,
+    out: &Output,
+    command: BridgeCommand,
+) -> anyhow::Result<()> {
+    match command.cmd {
+        BridgeSubCommand::Status { domains } => {
+            process_bridge_status(rpc_client, out, domains).await
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct BridgeStatusRow {
+    domain: String,
+    x_domain: String,
+    /// Whether the domain is owned by its `XDomain` account
+    bridged: bool,
+    owner_chain: Option<String>,
+    owner_address: Option<String>,
+}
+
+impl Row for BridgeStatusRow {
+    const HEADERS: &'static [&'static str] = &[
+        "Domain",
+        "XDomain account",
+        "Bridged",
+        "Owner chain",
+        "Owner address",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.domain.clone(),
+            self.x_domain.clone(),
+            if self.bridged { "yes" } else { "no" }.to_owned(),
+            cell(&self.owner_chain, ""),
+            cell(&self.owner_address, ""),
+        ]
+    }
+}
+
+async fn process_bridge_status(
+    rpc_client: &RpcClient,
+    out: &Output,
+    domains: Vec<String>,
+) -> anyhow::Result<()> {
+    let domain_keys = domains
+        .iter()
+        .map(|d| get_domain_key(d))
+        .collect::<Result<Vec<_>, _>>()?;
+    let x_domain_keys = domain_keys
+        .iter()
+        .map(derive_x_domain_key)
+        .collect::<Vec<_>>();
+    let registries = resolve_name_registry_batch(rpc_client, &domain_keys).await?;
+    let mut x_domains = vec![];
+    for keys in x_domain_keys.chunks(100) {
+        x_domains.extend(rpc_client.get_multiple_accounts(keys).await?);
+    }
+
+    let mut rows = vec![];
+    for (((domain, x_domain_key), registry), x_domain) in domains
+        .iter()
+        .zip(&x_domain_keys)
+        .zip(registries)
+        .zip(x_domains)
+    {
+        let (header, _) =
+            registry.ok_or_else(|| anyhow!("{} does not exist", format_domain(domain)))?;
+        let x_domain = x_domain
+            .map(|a| deserialize_x_domain(&a.data))
+            .transpose()?;
+        rows.push(BridgeStatusRow {
+            domain: format_domain(domain),
+            x_domain: x_domain_key.to_string(),
+            bridged: x_domain.is_some() && header.owner == *x_domain_key,
+            owner_chain: x_domain.as_ref().map(|x| {
+                chain_name(x.owner_chain).map_or_else(|| x.owner_chain.to_string(), str::to_owned)
+            }),
+            owner_address: x_domain
+                .as_ref()
+                .map(|x| format_chain_address(x.owner_chain, &x.owner_address)),
+        });
+    }
+    out.print(&rows)?;
+    Ok(())
+}
@@ -0,0 +1,225 @@
+//! Configuration of the CLI.
+//!
+//! Each setting is resolved from, in order of precedence, the global command line flags, the
+//! `sns.toml` file and the Solana CLI config file (`~/.config/solana/cli/config.yml`), so that
+//! `sns` uses the same cluster and wallet as `solana` and `spl-token` by default.
+//!
+//! The `sns.toml` file is read from the `--config` flag, or else from `./sns.toml` or
+//! `~/.config/sns/sns.toml` when they exist. All its fields are optional:
+//!
+//! ```toml
+//! url = "devnet"          # An RPC URL or a moniker: mainnet-beta, testnet, devnet, localhost
+//! keypair = "~/.config/solana/devnet.json"
+//! commitment = "confirmed"
+//! priority_fee = 10000    # In micro-lamports per compute unit
+//! output = "json"         # table, json, jsonl or csv
+//! ```
+use {
+    crate::{output::OutputFormat, tx::SignerSource},
+    anyhow::{anyhow, Context},
+    clap::Args,
+    serde::Deserialize,
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_program::pubkey::Pubkey,
+    solana_sdk::{
+        commitment_config::{CommitmentConfig, CommitmentLevel},
+        signer::keypair::read_keypair_file,
+    },
+    std::{
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+const DEFAULT_URL: &str = "https://api.mainnet-beta.solana.com";
+
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    #[arg(
+        long,
+        global = true,
+        help = "Path of the sns.toml config file [default: ./sns.toml or ~/.config/sns/sns.toml]"
+    )]
+    pub config: Option<PathBuf>,
+    #[arg(
+        long,
+        short,
+        global = true,
+        value_name = "URL_OR_MONIKER",
+        help = "The RPC URL or moniker of the cluster: [mainnet-beta, testnet, devnet, localhost]"
+    )]
+    pub url: Option<String>,
+    #[arg(
+        long,
+        short,
+        global = true,
+        help = "The path to the wallet private key used to sign transactions, or its address with --sign-only"
+    )]
+    pub keypair: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        value_parser = ["processed", "confirmed", "finalized"],
+        help = "The commitment used to query the cluster"
+    )]
+    pub commitment: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        help = "The priority fee of transactions in micro-lamports per compute unit"
+    )]
+    pub priority_fee: Option<u64>,
+}
+
+/// The subset of the Solana CLI config file used by `sns`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SolanaCliConfig {
+    json_rpc_url: Option<String>,
+    keypair_path: Option<String>,
+    commitment: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SnsConfig {
+    #[serde(alias = "cluster")]
+    url: Option<String>,
+    keypair: Option<String>,
+    commitment: Option<String>,
+    priority_fee: Option<u64>,
+    output: Option<OutputFormat>,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub url: String,
+    pub keypair_path: PathBuf,
+    pub commitment: CommitmentConfig,
+    pub priority_fee: u64,
+    pub output: OutputFormat,
+}
+
+impl Config {
+    pub fn load(args: &ConfigArgs, output: Option<OutputFormat>) -> anyhow::Result<Self> {
+        let home = dirs_next::home_dir();
+
+        let solana = match &home {
+            Some(home) => {
+                let path = home.join(".config/solana/cli/config.yml");
+                read_config(&path, |s| serde_yaml::from_str::<SolanaCliConfig>(s))?
+            }
+            None => None,
+        }
+        .unwrap_or_default();
+
+        let sns = match &args.config {
+            Some(path) => Some(
+                read_config(path, toml::from_str::<SnsConfig>)?
+                    .ok_or_else(|| anyhow!("Config file {} does not exist", path.display()))?,
+            ),
+            None => {
+                let mut paths = vec![PathBuf::from("sns.toml")];
+                paths.extend(home.as_ref().map(|h| h.join(".config/sns/sns.toml")));
+                paths
+                    .iter()
+                    .find(|p| p.is_file())
+                    .map(|p| read_config(p, toml::from_str::<SnsConfig>))
+                    .transpose()?
+                    .flatten()
+            }
+        }
+        .unwrap_or_default();
+
+        let url = args
+            .url
+            .clone()
+            .or(sns.url)
+            .or(solana.json_rpc_url)
+            .map_or_else(|| DEFAULT_URL.to_owned(), |url| normalize_url(&url));
+
+        let keypair_path = match args.keypair.clone().or(sns.keypair).or(solana.keypair_path) {
+            Some(path) => expand_home(&path, home.as_deref()),
+            None => home
+                .ok_or_else(|| anyhow!("No keypair configured, use --keypair"))?
+                .join(".config/solana/id.json"),
+        };
+
+        let commitment = match args
+            .commitment
+            .clone()
+            .or(sns.commitment)
+            .or(solana.commitment)
+        {
+            Some(commitment) => CommitmentConfig {
+                commitment: CommitmentLevel::from_str(&commitment)
+                    .map_err(|_| anyhow!("Invalid commitment {commitment:?}"))?,
+            },
+            None => CommitmentConfig::confirmed(),
+        };
+
+        Ok(Self {
+            url,
+            keypair_path,
+            commitment,
+            priority_fee: args.priority_fee.or(sns.priority_fee).unwrap_or_default(),
+            output: output.or(sns.output).unwrap_or_default(),
+        })
+    }
+
+    pub fn rpc_client(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.url.clone(), self.commitment)
+    }
+
+    /// The configured keypair, which can also be an address when it signs elsewhere
+    pub fn signer(&self) -> anyhow::Result<SignerSource> {
+        if let Some(address) = self
+            .keypair_path
+            .to_str()
+            .and_then(|path| Pubkey::from_str(path).ok())
+        {
+            return Ok(SignerSource::Address(address));
+        }
+        read_keypair_file(&self.keypair_path)
+            .map(SignerSource::Keypair)
+            .map_err(|e| {
+                anyhow!(
+                    "Failed to read the keypair {}: {e}",
+                    self.keypair_path.display()
+                )
+            })
+    }
+}
+
+/// Reads and parses a config file, returns `None` if it does not exist
+fn read_config<T, E>(path: &Path, parse: impl Fn(&str) -> Result<T, E>) -> anyhow::Result<Option<T>>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let config = parse(&content).with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok(Some(config))
+}
+
+/// Resolves the cluster monikers accepted by the Solana CLI
+fn normalize_url(url: &str) -> String {
+    match url {
+        "m" | "mainnet-beta" => DEFAULT_URL,
+        "t" | "testnet" => "https://api.testnet.solana.com",
+        "d" | "devnet" => "https://api.devnet.solana.com",
+        "l" | "localhost" => "http://localhost:8899",
+        url => url,
+    }
+    .to_owned()
+}
+
+fn expand_home(path: &str, home: Option<&Path>) -> PathBuf {
+    match (path.strip_prefix("~/"), home) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
@@ -1,41 +1,30 @@
+#![allow(clippy::result_large_err)]
 use serde::Serialize;
-use sns_sdk::{
-    favourite_domain::register_favourite::Accounts,
-    record::{self, get_record_v2_key},
-    NAME_OFFERS_PROGRAM_ID,
-};
-use solana_account_decoder::UiAccountEncoding;
-use solana_client::{
-    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
-    rpc_filter::{Memcmp, RpcFilterType},
-};
-use solana_sdk::{bs58, signature::Keypair, system_program};
-use std::collections::HashMap;
+use sns_sdk::favourite_domain::get_set_favourite_instruction;
+use sns_sdk::snapshot::SkipReason;
+
+mod apply;
+mod bridge;
+mod config;
+mod output;
+mod record;
+mod snapshot;
+mod tx;
 
 use {
     anyhow::anyhow,
     base64::Engine,
     clap::Args,
     clap::{Parser, Subcommand},
-    console::Term,
-    indicatif::{ProgressBar, ProgressState, ProgressStyle},
-    prettytable::{row, Table},
+    config::{Config, ConfigArgs},
+    output::{cell, format_domain, Output, OutputFormat, Row},
     serde::Deserialize,
     sns_sdk::non_blocking::resolve,
-    sns_sdk::{
-        derivation::{get_domain_key, get_hashed_name},
-        record::Record,
-    },
     solana_client::nonblocking::rpc_client::RpcClient,
     solana_program::instruction::{AccountMeta, Instruction},
-    solana_program::program_pack::Pack,
     solana_program::pubkey::Pubkey,
-    solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel},
-    solana_sdk::signer::keypair::read_keypair_file,
-    solana_sdk::{signer::Signer, transaction::Transaction},
-    spl_name_service::state::NameRecordHeader,
-    std::fmt::Write,
-    std::str::FromStr,
+    std::{path::PathBuf, str::FromStr},
+    tx::{process_submit, TxArgs, TxCommand, TxSender, TxSubCommand},
 };
 
 #[derive(Debug, Parser)]
@@ -44,6 +33,16 @@ use {
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    #[arg(
+        long,
+        short,
+        global = true,
+        value_enum,
+        help = "The output format [default: table]"
+    )]
+    output: Option<OutputFormat>,
+    #[command(flatten)]
+    config: ConfigArgs,
 }
 
 #[derive(Debug, Subcommand)]
@@ -58,19 +57,12 @@ enum Commands {
             help = "The list of domains to resolve with or without .sol suffix"
         )]
         domain: Vec<String>,
-        #[arg(long, short, help = "Optional custom RPC URL")]
-        url: Option<String>,
     },
     #[command(
         arg_required_else_help = true,
         about = "Register the specified domain names"
     )]
     Register {
-        #[arg(
-            required = true,
-            help = "The path to the wallet private key used to register the domains"
-        )]
-        keypair_path: String,
         #[arg(
             required = true,
             help = "The space to allocate for each domain (1kB to 10kB"
@@ -81,10 +73,14 @@ enum Commands {
             help = "The list of domains to register with or without .sol suffix"
         )]
         domains: Vec<String>,
-        #[arg(long, short, help = "Optional custom RPC URL")]
-        url: Option<String>,
+        #[command(flatten)]
+        tx: TxArgs,
     },
-    #[command(arg_required_else_help = true, about = "Register a favourite domain")]
+    #[command(
+        arg_required_else_help = true,
+        hide = true,
+        about = "Register a favourite domain, prefer `sns favourite set`"
+    )]
     RegisterFavourite {
         #[arg(
             required = true,
@@ -93,19 +89,14 @@ enum Commands {
         owner: String,
         #[arg(required = true, help = "The domain to set as favorite")]
         domain: String,
-        #[arg(long, short, help = "Optional custom RPC URL")]
-        url: Option<String>,
+        #[command(flatten)]
+        tx: TxArgs,
     },
     #[command(
         arg_required_else_help = true,
         about = "Transfer a list of domains to a new owner"
     )]
     Transfer {
-        #[arg(
-            required = true,
-            help = "The path to the wallet private key which currently owns the domains to transfer"
-        )]
-        owner_keypair: String,
         #[arg(required = true, help = "The new owner of the domains")]
         new_owner: String,
         #[arg(
@@ -113,26 +104,21 @@ enum Commands {
             help = "The list of domains to transfer with or without .sol suffix"
         )]
         domain: Vec<String>,
-        #[arg(long, short, help = "Optional custom RPC URL")]
-        url: Option<String>,
+        #[command(flatten)]
+        tx: TxArgs,
     },
     #[command(
         arg_required_else_help = true,
         about = "⛔️ Burn a list of domain names"
     )]
     Burn {
-        #[arg(
-            required = true,
-            help = "The path to the wallet private key which currently owns the domains to burn"
-        )]
-        keypair_path: String,
         #[arg(
             required = true,
             help = "The list of domains to burn with or without .sol suffix"
         )]
         domain: Vec<String>,
-        #[arg(long, short, help = "Optional custom RPC URL")]
-        url: Option<String>,
+        #[command(flatten)]
+        tx: TxArgs,
     },
     #[command(
         arg_required_else_help = true,
@@ -144,116 +130,118 @@ enum Commands {
             help = "The list of domains to fetch with or without .sol suffix"
         )]
         domain: Vec<String>,
-        #[arg(long, short, help = "Optional custom RPC URL")]
-        url: Option<String>,
     },
     #[command(arg_required_else_help = true, about = "Perform a reverse lookup")]
     ReverseLookup {
         #[arg(required = true, help = "The public key (base58 encoded) to lookup")]
         key: String,
-        #[arg(long, short, help = "Optional custom RPC URL")]
-        url: Option<String>,
-    },
-    #[command(arg_required_else_help = true)]
-    Bridge {
-        #[arg(required = true)]
-        target_chain: String,
-        #[arg(required = true)]
-        domain: String,
-        #[arg(required = true)]
-        keypair_path: String,
     },
+    Bridge(bridge::BridgeCommand),
     #[command(
         arg_required_else_help = true,
         about = "Fetch all the domain names owned for the specified wallets"
     )]
     Domains {
-        #[arg(long, short, help = "Optional custom RPC URL")]
-        url: Option<String>,
         #[arg(required = true, help = "The list of wallets")]
         owners: Vec<String>,
     },
-    Record(RecordCommand),
+    #[command(
+        arg_required_else_help = true,
+        about = "Apply the owners, records and favourite domain described in a manifest"
+    )]
+    Apply {
+        #[arg(
+            required = true,
+            help = "The .toml or .csv manifest describing the desired state of the domains"
+        )]
+        manifest: PathBuf,
+        #[arg(long, help = "Print the changes without applying them")]
+        plan: bool,
+        #[arg(
+            long,
+            short,
+            help = "Apply the changes without asking for a confirmation"
+        )]
+        yes: bool,
+        #[command(flatten)]
+        tx: TxArgs,
+    },
+    Record(record::RecordCommand),
+    Favourite(FavouriteCommand),
+    Tx(TxCommand),
+    #[command(
+        arg_required_else_help = true,
+        about = "Write a snapshot of the whole name service to a SQLite database"
+    )]
+    Snapshot {
+        #[arg(
+            required = true,
+            help = "The path of the SQLite database, an existing database is updated in place from a full re-download"
+        )]
+        path: String,
+    },
 }
 
 #[derive(Debug, Args)]
-pub struct RecordCommand {
+#[command(about = "Manage the favourite (primary) domain of a wallet")]
+pub struct FavouriteCommand {
     #[command(subcommand)]
-    pub cmd: RecordSubCommand,
-    #[clap(long, help = "Use records V2", default_value_t)]
-    v2: bool,
-    #[arg(long, short, help = "Optional custom RPC URL")]
-    url: Option<String>,
+    pub cmd: FavouriteSubCommand,
 }
 
 #[derive(Debug, Subcommand)]
-pub enum RecordSubCommand {
-    #[command(about = "Gets a record content")]
+pub enum FavouriteSubCommand {
+    #[command(about = "Gets the favourite domain of the specified wallets")]
     Get {
-        #[clap(long, help = "The domain of the record to fetch")]
-        domain: String,
-        #[clap(long, help = "The record to fetch")]
-        record: String,
+        #[arg(required = true, help = "The list of wallets")]
+        owners: Vec<String>,
     },
-    #[command(about = "Sets a record content")]
+    #[command(about = "Sets or replaces the favourite domain of a wallet")]
     Set {
-        #[clap(long, help = "The domain of the record to set")]
+        #[arg(
+            required = true,
+            help = "The domain or subdomain to set as favourite with or without .sol suffix"
+        )]
         domain: String,
-        #[clap(long, help = "The record to set")]
-        record: String,
-        #[clap(long, help = "The content of the record")]
-        content: String,
-        #[clap(long, help = "The path of keypair ownning the domain")]
-        keypair: String,
+        #[arg(
+            long,
+            help = "The path to the wallet private key, or the address of the owner with --sign-only, to use instead of the configured keypair"
+        )]
+        owner: Option<String>,
+        #[command(flatten)]
+        tx: TxArgs,
     },
-    #[command(about = "Dump records system info")]
-    SystemDump,
 }
 
-const RPC_URL: &str = "https://api.mainnet-beta.solana.com";
+type CliResult = Result<(), Box<dyn std::error::Error>>;
 
-fn get_rpc_client(url: Option<String>) -> RpcClient {
-    match url {
-        Some(url) => RpcClient::new(url),
-        _ => RpcClient::new(RPC_URL.to_string()),
-    }
+#[derive(Serialize)]
+struct DomainRow {
+    domain: String,
+    owner: String,
 }
 
-fn format_domain(domain: &str) -> String {
-    if domain.ends_with(".sol") {
-        return domain.to_owned();
+impl Row for DomainRow {
+    const HEADERS: &'static [&'static str] = &["Domain", "Owner", "Link"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.domain.clone(),
+            self.owner.clone(),
+            format!(
+                "https://naming.bonfida.org/domain/{}",
+                self.domain.trim_end_matches(".sol")
+            ),
+        ]
     }
-    format!("{domain}.sol")
-}
-
-fn make_tx_url(sig: &str) -> String {
-    format!("https://explorer.solana.com/tx/{sig}")
-}
-
-pub fn progress_bar(len: usize) -> ProgressBar {
-    let pb = ProgressBar::new(len as u64);
-    pb.set_style(
-        ProgressStyle::with_template(
-            "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] ({eta})",
-        )
-        .unwrap()
-        .with_key("eta", |state: &ProgressState, w: &mut dyn Write| {
-            write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap()
-        })
-        .progress_chars("#>-"),
-    );
-    pb
 }
 
-type CliResult = Result<(), Box<dyn std::error::Error>>;
-
-async fn process_domains(rpc_client: &RpcClient, owners: Vec<String>) -> CliResult {
-    println!("Resolving domains...\n");
-    let mut table = Table::new();
-    table.add_row(row!["Domain", "Owner", "Link"]);
-    let pb = progress_bar(owners.len());
+async fn process_domains(rpc_client: &RpcClient, out: &Output, owners: Vec<String>) -> CliResult {
+    out.status("Resolving domains...");
+    let mut rows = vec![];
+    let pb = out.progress_bar(owners.len());
 
-    for (idx, owner) in owners.into_iter().enumerate() {
+    for owner in owners {
         let owner_key = Pubkey::from_str(&owner)?;
         let domains = resolve::get_domains_owner(rpc_client, owner_key).await?;
         resolve::resolve_reverse_batch(rpc_client, &domains)
@@ -261,163 +249,193 @@ async fn process_domains(rpc_client: &RpcClient, owners: Vec<String>) -> CliResu
             .into_iter()
             .flatten()
             .for_each(|x| {
-                table.add_row(row![
-                    format_domain(&x),
-                    owner,
-                    format!("https://naming.bonfida.org/domain/{x}")
-                ]);
+                rows.push(DomainRow {
+                    domain: format_domain(&x),
+                    owner: owner.clone(),
+                })
             });
-        pb.set_position(idx as u64);
+        pb.inc(1);
     }
-    pb.finish();
-    Term::stdout().clear_to_end_of_screen()?;
-    table.printstd();
+    pb.finish_and_clear();
+    out.print(&rows)?;
     Ok(())
 }
 
-async fn process_resolve(rpc_client: &RpcClient, domains: Vec<String>) -> CliResult {
-    println!("Resolving domains...\n");
-    let mut table = Table::new();
-    table.add_row(row!["Domain", "Owner", "Explorer"]);
-
-    let pb = progress_bar(domains.len());
-    for (idx, domain) in domains.into_iter().enumerate() {
-        let row = match resolve::resolve_owner(rpc_client, &domain).await? {
-            Some(owner) => row![
-                format_domain(&domain),
-                owner,
-                format!("https://explorer.solana.com/address/{owner}")
+#[derive(Serialize)]
+struct ResolveRow {
+    domain: String,
+    owner: Option<String>,
+    /// The chain of the owner, which is not Solana when the domain is bridged
+    chain: Option<String>,
+}
+
+impl Row for ResolveRow {
+    const HEADERS: &'static [&'static str] = &["Domain", "Owner", "Chain", "Explorer"];
+
+    fn cells(&self) -> Vec<String> {
+        match (&self.owner, &self.chain) {
+            (Some(owner), Some(chain)) => vec![
+                self.domain.clone(),
+                owner.clone(),
+                chain.clone(),
+                if chain == "solana" {
+                    format!("https://explorer.solana.com/address/{owner}")
+                } else {
+                    String::new()
+                },
             ],
-            _ => row![format_domain(&domain), "Domain not found"],
-        };
-        table.add_row(row);
-        pb.set_position(idx as u64);
+            _ => vec![self.domain.clone(), "Domain not found".to_owned()],
+        }
+    }
+}
+
+async fn process_resolve(rpc_client: &RpcClient, out: &Output, domains: Vec<String>) -> CliResult {
+    out.status("Resolving domains...");
+    let mut rows = vec![];
+
+    let pb = out.progress_bar(domains.len());
+    for domain in domains {
+        let owner = resolve::resolve_domain_owner(rpc_client, &domain).await?;
+        rows.push(ResolveRow {
+            domain: format_domain(&domain),
+            owner: owner.as_ref().map(|o| o.to_string()),
+            chain: owner.as_ref().map(|o| o.chain()),
+        });
+        pb.inc(1);
     }
-    pb.finish();
-    Term::stdout().clear_to_end_of_screen()?;
-    table.printstd();
+    pb.finish_and_clear();
+    out.print(&rows)?;
     Ok(())
 }
 
 async fn process_burn(
     rpc_client: &RpcClient,
-    keypair_path: &str,
+    out: &Output,
+    config: &Config,
+    tx_args: &TxArgs,
     domains: Vec<String>,
 ) -> CliResult {
-    println!("Burning domain...");
-    let mut table = Table::new();
-    table.add_row(row!["Domain", "Transaction", "Explorer"]);
-    let pb = progress_bar(domains.len());
-    for (idx, domain) in domains.into_iter().enumerate() {
+    out.status("Burning domain...");
+    let mut sender = TxSender::new(rpc_client, config, tx_args, None)?;
+    let owner = sender.authority();
+    let pb = out.progress_bar(domains.len());
+    for domain in domains {
         let domain_key = sns_sdk::derivation::get_domain_key(&domain)?;
-        let keypair = read_keypair_file(keypair_path)?;
-        let ix = spl_name_service::instruction::delete(
-            spl_name_service::ID,
-            domain_key,
-            keypair.pubkey(),
-            keypair.pubkey(),
-        )?;
-        let mut tx = Transaction::new_with_payer(&[ix], Some(&keypair.pubkey()));
-        let blockhash = rpc_client.get_latest_blockhash().await?;
-        tx.partial_sign(&[&keypair], blockhash);
-        let sig = rpc_client.send_and_confirm_transaction(&tx).await?;
-
-        table.add_row(row![
-            format_domain(&domain),
-            sig,
-            make_tx_url(&sig.to_string())
-        ]);
-        pb.set_position(idx as u64);
+        let ix =
+            spl_name_service::instruction::delete(spl_name_service::ID, domain_key, owner, owner)?;
+        sender.execute(&domain, vec![ix]).await?;
+        pb.inc(1);
     }
-    pb.finish();
-    Term::stdout().clear_to_end_of_screen()?;
-    table.printstd();
+    pb.finish_and_clear();
+    sender.print(out).await?;
     Ok(())
 }
 
 async fn process_transfer(
     rpc_client: &RpcClient,
+    out: &Output,
+    config: &Config,
+    tx_args: &TxArgs,
     domains: Vec<String>,
-    owner_keypair: &str,
     new_owner: &str,
 ) -> CliResult {
-    println!("Transfering domains...");
-    let mut table = Table::new();
-    table.add_row(row!["Domain", "Transaction", "Explorer"]);
-    let pb = progress_bar(domains.len());
-    for (idx, domain) in domains.into_iter().enumerate() {
+    out.status("Transfering domains...");
+    let mut sender = TxSender::new(rpc_client, config, tx_args, None)?;
+    let owner = sender.authority();
+    let new_owner = Pubkey::from_str(new_owner)?;
+    let pb = out.progress_bar(domains.len());
+    for domain in domains {
         let domain_key = sns_sdk::derivation::get_domain_key(&domain)?;
-        let keypair = read_keypair_file(owner_keypair)?;
         let ix = spl_name_service::instruction::transfer(
             spl_name_service::ID,
-            Pubkey::from_str(new_owner)?,
+            new_owner,
             domain_key,
-            keypair.pubkey(),
+            owner,
             None,
         )?;
-        let mut tx = Transaction::new_with_payer(&[ix], Some(&keypair.pubkey()));
-        let blockhash = rpc_client.get_latest_blockhash().await?;
-        tx.partial_sign(&[&keypair], blockhash);
-        let sig = rpc_client.send_and_confirm_transaction(&tx).await?;
-        table.add_row(row![
-            format_domain(&domain),
-            sig,
-            make_tx_url(&sig.to_string())
-        ]);
-        pb.set_position(idx as u64);
+        sender.execute(&domain, vec![ix]).await?;
+        pb.inc(1);
     }
-    pb.finish();
-    Term::stdout().clear_to_end_of_screen()?;
-    table.printstd();
+    pb.finish_and_clear();
+    sender.print(out).await?;
     Ok(())
 }
 
-async fn process_lookup(rpc_client: &RpcClient, domains: Vec<String>) -> CliResult {
-    println!("Fetching information...\n");
-    let mut table = Table::new();
-    table.add_row(row!["Domain", "Domain key", "Parent", "Owner", "Data"]);
-    let pb = progress_bar(domains.len());
-    for (idx, domain) in domains.into_iter().enumerate() {
+#[derive(Serialize)]
+struct LookupRow {
+    domain: String,
+    domain_key: String,
+    parent: String,
+    owner: Option<String>,
+    data: Option<String>,
+}
+
+impl Row for LookupRow {
+    const HEADERS: &'static [&'static str] = &["Domain", "Domain key", "Parent", "Owner", "Data"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.domain.clone(),
+            self.domain_key.clone(),
+            self.parent.clone(),
+            cell(&self.owner, "N/A"),
+            cell(&self.data, "N/A"),
+        ]
+    }
+}
+
+async fn process_lookup(rpc_client: &RpcClient, out: &Output, domains: Vec<String>) -> CliResult {
+    out.status("Fetching information...");
+    let mut rows = vec![];
+    let pb = out.progress_bar(domains.len());
+    for domain in domains {
         let sns_sdk::derivation::DomainKeyWithParent {
             key: domain_key,
             parent,
         } = sns_sdk::derivation::get_domain_key_with_parent(&domain)?;
-        let row = match resolve::resolve_name_registry(rpc_client, &domain_key).await? {
-            Some((header, data)) => {
-                let data = String::from_utf8(data)?;
-                row![
-                    format_domain(&domain),
-                    domain_key,
-                    header.parent_name,
-                    header.owner,
-                    data
-                ]
-            }
-            _ => row![format_domain(&domain), domain_key, parent, "N/A", "N/A"],
-        };
-        table.add_row(row);
-        pb.set_position(idx as u64);
+        let registry = resolve::resolve_name_registry(rpc_client, &domain_key).await?;
+        rows.push(LookupRow {
+            domain: format_domain(&domain),
+            domain_key: domain_key.to_string(),
+            parent: registry
+                .as_ref()
+                .map_or(parent, |(header, _)| header.parent_name)
+                .to_string(),
+            owner: registry
+                .as_ref()
+                .map(|(header, _)| header.owner.to_string()),
+            data: registry
+                .map(|(_, data)| String::from_utf8(data))
+                .transpose()?,
+        });
+        pb.inc(1);
     }
-    pb.finish();
-    Term::stdout().clear_to_end_of_screen()?;
-    table.printstd();
+    pb.finish_and_clear();
+    out.print(&rows)?;
     Ok(())
 }
 
-async fn process_reverse_lookup(rpc_client: &RpcClient, key: &str) -> CliResult {
-    println!("Fetching information about {key}\n");
-
-    if let Some(reverse) = resolve::resolve_reverse(rpc_client, &Pubkey::from_str(key)?).await? {
-        let mut table = Table::new();
-        table.add_row(row!["Public key", "Reverse"]);
-        table.add_row(row![key, format_domain(&reverse)]);
-        Term::stdout().clear_line()?;
-        table.printstd();
-    } else {
-        Term::stdout().clear_line()?;
-        println!("Domain not found - Are you sure it exists?")
+#[derive(Serialize)]
+struct ReverseLookupRow {
+    key: String,
+    domain: Option<String>,
+}
+
+impl Row for ReverseLookupRow {
+    const HEADERS: &'static [&'static str] = &["Public key", "Reverse"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.key.clone(), cell(&self.domain, "Domain not found")]
     }
+}
 
+async fn process_reverse_lookup(rpc_client: &RpcClient, out: &Output, key: &str) -> CliResult {
+    out.status(&format!("Fetching information about {key}"));
+    let reverse = resolve::resolve_reverse(rpc_client, &Pubkey::from_str(key)?).await?;
+    out.print(&[ReverseLookupRow {
+        key: key.to_owned(),
+        domain: reverse.as_deref().map(format_domain),
+    }])?;
     Ok(())
 }
 
@@ -446,27 +464,27 @@ struct Key {
 
 async fn process_register(
     rpc_client: &RpcClient,
-    keypair_path: &str,
+    out: &Output,
+    config: &Config,
+    tx_args: &TxArgs,
     domains: Vec<String>,
     space: u64,
 ) -> CliResult {
-    println!("Registering domains...");
-    let mut table = Table::new();
-    table.add_row(row!["Domain", "Transaction", "Explorer"]);
-    let pb = progress_bar(domains.len());
+    out.status("Registering domains...");
+    let mut sender = TxSender::new(rpc_client, config, tx_args, None)?;
+    let pb = out.progress_bar(domains.len());
     let client = reqwest::Client::new();
-    let keypair = read_keypair_file(keypair_path)?;
 
     let re = regex::Regex::new(r"^[a-z\d\-_]+$").unwrap();
 
-    for (idx, domain) in domains.into_iter().enumerate() {
+    for domain in domains {
         if !re.is_match(&domain) {
             return Err(anyhow!("Invalid domain").into());
         }
         let response = client
             .get(format!(
                 "https://sns-sdk-proxy.bonfida.workers.dev/register?buyer={}&domain={}&space={}",
-                keypair.pubkey(),
+                sender.authority(),
                 domain,
                 space
             ))
@@ -489,370 +507,183 @@ async fn process_register(
             let data = base64::engine::general_purpose::URL_SAFE.decode(r.data)?;
             ixs.push(Instruction::new_with_bytes(program_id, &data, accounts))
         }
-
-        let mut tx = Transaction::new_with_payer(&ixs, Some(&keypair.pubkey()));
-        let blockhash = rpc_client.get_latest_blockhash().await?;
-        tx.partial_sign(&[&keypair], blockhash);
-        let sig = rpc_client.send_and_confirm_transaction(&tx).await?;
-        table.add_row(row![
-            format_domain(&domain),
-            sig,
-            make_tx_url(&sig.to_string())
-        ]);
-        pb.set_position(idx as u64);
+        sender.execute(&domain, ixs).await?;
+        pb.inc(1);
     }
-    pb.finish();
-    Term::stdout().clear_to_end_of_screen()?;
-    table.printstd();
+    pb.finish_and_clear();
+    sender.print(out).await?;
     Ok(())
 }
 
-enum OwnerKind {
-    Keypair(Keypair),
-    Pubkey(Pubkey),
-}
-
-impl OwnerKind {
-    fn owner(&self) -> Pubkey {
-        match self {
-            OwnerKind::Keypair(keypair) => keypair.pubkey(),
-            OwnerKind::Pubkey(pk) => *pk,
-        }
-    }
-}
-
 async fn process_register_favourite(
     rpc_client: &RpcClient,
-    owner_keypair_path_or_address: &str,
+    out: &Output,
+    config: &Config,
+    tx_args: &TxArgs,
+    owner_keypair_path_or_address: Option<&str>,
     domain: &str,
 ) -> CliResult {
-    println!("Registering favourite domain...");
-    let owner_kind = {
-        match read_keypair_file(owner_keypair_path_or_address) {
-            Ok(kp) => OwnerKind::Keypair(kp),
-            Err(e) => match Pubkey::from_str(owner_keypair_path_or_address) {
-                Ok(owner) => OwnerKind::Pubkey(owner),
-                Err(parse_pk_error) => {
-                    return Err(anyhow!(
-                    "Owner was not a valid keypair nor a valid address: {e:?}, {parse_pk_error:?}"
-                )
-                    .into())
-                }
-            },
-        }
-    };
-    let owner = owner_kind.owner();
-    let domain_key = get_domain_key(domain)?;
-    let ix = sns_sdk::favourite_domain::get_register_favourite_instruction(
-        NAME_OFFERS_PROGRAM_ID,
-        Accounts {
-            owner: &owner,
-            name: &domain_key,
-            favourite_domain: &sns_sdk::favourite_domain::derive_favourite_domain_key(&owner),
-            system_program: &system_program::ID,
-        },
-        sns_sdk::favourite_domain::register_favourite::Params {},
-    );
-    let blockhash = rpc_client.get_latest_blockhash().await?;
-
-    match owner_kind {
-        OwnerKind::Keypair(keypair) => {
-            let tx = Transaction::new_signed_with_payer(
-                &[ix],
-                Some(&keypair.pubkey()),
-                &[&keypair],
-                blockhash,
-            );
-            let sig = rpc_client.send_and_confirm_transaction(&tx).await?;
-            println!("Favourite set, txid: {sig}");
-        }
-        OwnerKind::Pubkey(_) => {
-            let mut tx = Transaction::new_with_payer(&[ix.clone()], Some(&owner));
-            tx.message.recent_blockhash = blockhash;
-
-            println!(
-                "base58 register favourite tx: {}",
-                bs58::encode(bincode::serialize(&tx).unwrap()).into_string()
-            );
-        }
-    }
-
+    out.status("Registering favourite domain...");
+    let mut sender = TxSender::new(rpc_client, config, tx_args, owner_keypair_path_or_address)?;
+    let ix = get_set_favourite_instruction(&sender.authority(), domain)?;
+    sender.execute(domain, vec![ix]).await?;
+    sender.print(out).await?;
     Ok(())
 }
 
-async fn process_record_set(
-    rpc_client: &RpcClient,
-    domain: &str,
-    record_str: &str,
-    content: &str,
-    keypair_path: &str,
-) -> CliResult {
-    let mut ixs = vec![];
-    let mut table = Table::new();
-    table.add_row(row!["Transaction", "Signature"]);
-
-    let record = Record::try_from_str(record_str)?;
-    let keypair = read_keypair_file(keypair_path)?;
-    let data = sns_sdk::record::record_v1::serialize_record(content, record)?;
-    let key = record::get_record_key(
-        domain,
-        Record::try_from_str(record_str)?,
-        record::RecordVersion::V1,
-    )?;
-    let hashed_name = get_hashed_name(&format!("\x01{record_str}"));
-    let parent = get_domain_key(domain)?;
-
-    let lamports = rpc_client
-        .get_minimum_balance_for_rent_exemption(data.len() + NameRecordHeader::LEN)
-        .await?;
-
-    let acc = rpc_client
-        .get_account_with_commitment(&key, CommitmentConfig::default())
-        .await?;
-
-    if let Some(value) = acc.value {
-        if value.data.len() - NameRecordHeader::LEN != data.len() {
-            // Delete existing record
-            // This is the only way to handle the account resizing
-            let ix = spl_name_service::instruction::delete(
-                spl_name_service::ID,
-                key,
-                keypair.pubkey(),
-                keypair.pubkey(),
-            )?;
-
-            // Clean up transaction
-            let mut tx = Transaction::new_with_payer(&[ix], Some(&keypair.pubkey()));
-            let blockhash = rpc_client.get_latest_blockhash().await?;
-            tx.sign(&[&keypair], blockhash);
-
-            let sig = rpc_client
-                .send_and_confirm_transaction_with_spinner(&tx)
-                .await?;
-            table.add_row(row!["Clean up", make_tx_url(&sig.to_string())]);
-
-            // Create the record
-            let ix = spl_name_service::instruction::create(
-                spl_name_service::ID,
-                spl_name_service::instruction::NameRegistryInstruction::Create {
-                    hashed_name,
-                    lamports,
-                    space: data.len() as u32,
-                },
-                key,
-                keypair.pubkey(),
-                keypair.pubkey(),
-                None,
-                Some(parent),
-                Some(keypair.pubkey()),
-            )?;
-            ixs.push(ix);
-        }
-    } else {
-        let ix: Instruction = spl_name_service::instruction::create(
-            spl_name_service::ID,
-            spl_name_service::instruction::NameRegistryInstruction::Create {
-                hashed_name,
-                lamports,
-                space: data.len() as u32,
-            },
-            key,
-            keypair.pubkey(),
-            keypair.pubkey(),
-            None,
-            Some(parent),
-            Some(keypair.pubkey()),
-        )?;
-        ixs.push(ix);
-    }
+#[derive(Serialize)]
+struct FavouriteRow {
+    owner: String,
+    domain: Option<String>,
+    domain_key: Option<String>,
+    stale: Option<bool>,
+}
 
-    // Update
-    let ix = spl_name_service::instruction::update(
-        spl_name_service::ID,
-        0,
-        data,
-        key,
-        keypair.pubkey(),
-        Some(parent),
-    )?;
-    ixs.push(ix);
-
-    let mut tx = Transaction::new_with_payer(&ixs, Some(&keypair.pubkey()));
-    let blockhash = rpc_client.get_latest_blockhash().await?;
-    tx.sign(&[&keypair], blockhash);
-
-    let sig = rpc_client
-        .send_and_confirm_transaction_with_spinner_and_commitment(
-            &tx,
-            CommitmentConfig {
-                commitment: CommitmentLevel::Processed,
+impl Row for FavouriteRow {
+    const HEADERS: &'static [&'static str] = &["Wallet", "Favourite domain", "Domain key", "Stale"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.owner.clone(),
+            cell(&self.domain, ""),
+            cell(&self.domain_key, ""),
+            match self.stale {
+                Some(true) => "yes".to_owned(),
+                Some(false) => "no".to_owned(),
+                None => String::new(),
             },
-        )
-        .await?;
-    table.add_row(row!["Update record", make_tx_url(&sig.to_string())]);
-
-    Term::stdout().clear_to_end_of_screen()?;
-    table.printstd();
-
-    Ok(())
+        ]
+    }
 }
 
-async fn process_record_get(
+async fn process_favourite_get(
     rpc_client: &RpcClient,
-    domain: &str,
-    record_str: &str,
-    v2: bool,
+    out: &Output,
+    owners: Vec<String>,
 ) -> CliResult {
-    let record = Record::try_from_str(record_str)?;
-
-    let key = record::get_record_key(
-        domain,
-        Record::try_from_str(record_str)?,
-        if v2 {
-            record::RecordVersion::V2
-        } else {
-            record::RecordVersion::V1
-        },
-    )?;
-    let mut table = Table::new();
-    if let Some((_, data)) = resolve::resolve_name_registry(rpc_client, &key).await? {
-        let des = record::record_v1::deserialize_record(&data, record, &key)?;
-
-        table.add_row(row!["Domain", "Record", "Content"]);
-        table.add_row(row![format_domain(domain), record_str, des]);
-    }
-    Term::stdout().clear_to_end_of_screen()?;
-    table.printstd();
+    let owners = owners
+        .iter()
+        .map(|o| Pubkey::from_str(o))
+        .collect::<Result<Vec<_>, _>>()?;
+    let favourites = resolve::get_multiple_primary_domains(rpc_client, &owners).await?;
+
+    let rows = owners
+        .iter()
+        .zip(favourites)
+        .map(|(owner, favourite)| FavouriteRow {
+            owner: owner.to_string(),
+            domain: favourite.as_ref().map(|f| format_domain(&f.reverse)),
+            domain_key: favourite.as_ref().map(|f| f.domain.to_string()),
+            stale: favourite.map(|f| f.stale),
+        })
+        .collect::<Vec<_>>();
+    out.print(&rows)?;
     Ok(())
 }
 
-#[derive(Serialize)]
-pub struct SystemDumpRecord {
-    domain: String,
-    record_type: Option<String>,
-    record_key: String,
-}
-
-pub async fn process_system_dump(rpc_client: &RpcClient) -> CliResult {
-    let record_v2_accounts = rpc_client
-        .get_program_accounts_with_config(
-            &spl_name_service::ID,
-            RpcProgramAccountsConfig {
-                filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
-                    64,
-                    sns_records::central_state::KEY.as_ref().to_vec(),
-                ))]),
-                account_config: RpcAccountInfoConfig {
-                    encoding: Some(UiAccountEncoding::Base64),
-                    data_slice: None,
-                    commitment: None,
-                    min_context_slot: None,
-                },
-                with_context: None,
-            },
-        )
-        .await?;
-    eprintln!("Found {} v2 records", record_v2_accounts.len());
-    let mut by_parent: HashMap<Pubkey, Vec<Pubkey>> = HashMap::new();
-    for (k, a) in record_v2_accounts {
-        let spl_header = NameRecordHeader::unpack_unchecked(&a.data[..NameRecordHeader::LEN])?;
-        let entry = by_parent.entry(spl_header.parent_name);
-        match entry {
-            std::collections::hash_map::Entry::Occupied(mut o) => o.get_mut().push(k),
-            std::collections::hash_map::Entry::Vacant(v) => {
-                v.insert(vec![k]);
-            }
-        }
+async fn process_snapshot(rpc_client: &RpcClient, out: &Output, path: &str) -> CliResult {
+    out.status("Fetching name service accounts...");
+    let snapshot = sns_sdk::non_blocking::snapshot::get_snapshot(rpc_client).await?;
+    if snapshot.end_slot != snapshot.slot {
+        eprintln!(
+            "Warning: the accounts were read between slots {} and {}, the snapshot may mix their states",
+            snapshot.slot, snapshot.end_slot
+        );
     }
-    let parent_domains = by_parent.keys().cloned().collect::<Vec<_>>();
-    eprintln!("From a total of {} domains", by_parent.keys().len());
-    let reverse_lookup_keys =
-        sns_sdk::non_blocking::resolve::resolve_reverse_batch(rpc_client, &parent_domains).await?;
-    for (domain, name) in parent_domains
-        .into_iter()
-        .zip(reverse_lookup_keys.into_iter())
-    {
-        if name.is_none() {
-            continue;
-        }
-        let name = name.unwrap();
-        use Record::*;
-        let possible_records = [
-            Ipfs, Arwv, Sol, Eth, Btc, Ltc, Doge, Email, Url, Discord, Github, Reddit, Twitter,
-            Telegram, Pic, Shdw, Point, Bsc, Injective, Backpack, A, AAAA, CNAME, TXT, BASE,
-        ]
-        .into_iter()
-        .map(|r| get_record_v2_key(&name, r).map(|res| (res, r)))
-        .collect::<Result<HashMap<_, _>, _>>()?;
-        for record in by_parent.get(&domain).unwrap() {
-            let record_type = possible_records.get(record);
-            println!(
-                "{}",
-                serde_json::to_string(&SystemDumpRecord {
-                    domain: name.clone(),
-                    record_type: record_type.map(|r| r.as_str().to_owned()),
-                    record_key: record.to_string()
-                })?
-            )
+    for (reason, description) in [
+        (SkipReason::Malformed, "malformed accounts"),
+        (
+            SkipReason::UnknownChild,
+            "children of domains that are neither subdomains with a reverse nor known records",
+        ),
+        (
+            SkipReason::UnknownParent,
+            "name accounts whose parent is not a domain or subdomain",
+        ),
+    ] {
+        let count = snapshot
+            .skipped
+            .iter()
+            .filter(|s| s.reason == reason)
+            .count();
+        if count > 0 {
+            eprintln!("Skipped {count} {description}");
         }
     }
+    out.status(&format!("Writing snapshot at slot {}...", snapshot.slot));
+    let mut conn = rusqlite::Connection::open(path)?;
+    let stats = snapshot::write_snapshot(&mut conn, &snapshot)?;
+    out.print(&stats)?;
     Ok(())
 }
 
 #[tokio::main]
 async fn main() {
     let args = Cli::parse();
+    let config = match Config::load(&args.config, args.output) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            std::process::exit(1);
+        }
+    };
+    let config = &config;
+    let out = &Output::new(config.output);
+    let rpc_client = &config.rpc_client();
 
     let res = match args.command {
-        Commands::Resolve { domain, url } => process_resolve(&get_rpc_client(url), domain).await,
-        Commands::Domains { owners, url } => process_domains(&get_rpc_client(url), owners).await,
-        Commands::Burn {
-            domain,
-            keypair_path,
-            url,
-        } => process_burn(&get_rpc_client(url), &keypair_path, domain).await,
+        Commands::Resolve { domain } => process_resolve(rpc_client, out, domain).await,
+        Commands::Domains { owners } => process_domains(rpc_client, out, owners).await,
+        Commands::Burn { domain, tx } => process_burn(rpc_client, out, config, &tx, domain).await,
         Commands::Transfer {
             domain,
-            owner_keypair,
             new_owner,
-            url,
-        } => process_transfer(&get_rpc_client(url), domain, &owner_keypair, &new_owner).await,
-        Commands::Lookup { domain, url } => process_lookup(&get_rpc_client(url), domain).await,
-        Commands::ReverseLookup { key, url } => {
-            process_reverse_lookup(&get_rpc_client(url), &key).await
+            tx,
+        } => process_transfer(rpc_client, out, config, &tx, domain, &new_owner).await,
+        Commands::Lookup { domain } => process_lookup(rpc_client, out, domain).await,
+        Commands::ReverseLookup { key } => process_reverse_lookup(rpc_client, out, &key).await,
+        Commands::Bridge(command) => bridge::process_bridge(rpc_client, out, command)
+            .await
+            .map_err(Into::into),
+        Commands::Register { domains, space, tx } => {
+            process_register(rpc_client, out, config, &tx, domains, space).await
         }
-        Commands::Bridge { .. } => unimplemented!(),
-        Commands::Register {
-            domains,
-            keypair_path,
-            space,
-            url,
-        } => process_register(&get_rpc_client(url), &keypair_path, domains, space).await,
-        Commands::RegisterFavourite { owner, domain, url } => {
-            process_register_favourite(&get_rpc_client(url), &owner, &domain).await
+        Commands::RegisterFavourite { owner, domain, tx } => {
+            process_register_favourite(rpc_client, out, config, &tx, Some(&owner), &domain).await
         }
-        Commands::Record(RecordCommand { cmd, v2, url }) => match cmd {
-            RecordSubCommand::Get { domain, record } => {
-                process_record_get(&get_rpc_client(url), &domain, &record, v2).await
+        Commands::Favourite(FavouriteCommand { cmd }) => match cmd {
+            FavouriteSubCommand::Get { owners } => {
+                process_favourite_get(rpc_client, out, owners).await
             }
-            RecordSubCommand::Set {
-                domain,
-                record,
-                content,
-                keypair,
-            } => {
-                if v2 {
-                    unimplemented!()
-                } else {
-                    process_record_set(&get_rpc_client(url), &domain, &record, &content, &keypair)
-                        .await
-                }
+            FavouriteSubCommand::Set { domain, owner, tx } => {
+                process_register_favourite(rpc_client, out, config, &tx, owner.as_deref(), &domain)
+                    .await
             }
-            RecordSubCommand::SystemDump {} => process_system_dump(&get_rpc_client(url)).await,
         },
+        Commands::Record(command) => record::process_record(rpc_client, out, config, command)
+            .await
+            .map_err(Into::into),
+        Commands::Tx(TxCommand { cmd }) => match cmd {
+            TxSubCommand::Submit {
+                transactions,
+                encoding,
+            } => process_submit(rpc_client, out, transactions, encoding)
+                .await
+                .map_err(Into::into),
+        },
+        Commands::Apply {
+            manifest,
+            plan,
+            yes,
+            tx,
+        } => apply::process_apply(rpc_client, out, config, &tx, &manifest, plan, yes)
+            .await
+            .map_err(Into::into),
+        Commands::Snapshot { path } => process_snapshot(rpc_client, out, &path).await,
     };
 
     if let Err(err) = res {
-        println!("Error: {err:?}")
+        eprintln!("Error: {err}");
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,176 @@
+//! Output formatting for the CLI commands.
+//!
+//! Commands produce rows that are either printed as a table for humans or serialized with
+//! stable field names in JSON, JSON lines or CSV for scripts. Progress bars and status
+//! messages are written to stderr, and only when stdout is a terminal.
+use {
+    clap::ValueEnum,
+    console::Term,
+    indicatif::{ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle},
+    prettytable::{Row as TableRow, Table},
+    serde::{Deserialize, Serialize},
+    solana_sdk::signature::Signature,
+    std::io::Write,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Human readable table
+    #[default]
+    Table,
+    /// A JSON array of objects
+    Json,
+    /// One JSON object per line
+    Jsonl,
+    /// CSV with a header line
+    Csv,
+}
+
+/// A row of command output
+pub trait Row: Serialize {
+    /// The column names of the table output
+    const HEADERS: &'static [&'static str];
+
+    /// The cells of the table output
+    fn cells(&self) -> Vec<String>;
+}
+
+pub struct Output {
+    format: OutputFormat,
+    interactive: bool,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            interactive: Term::stdout().is_term(),
+        }
+    }
+
+    /// Prints a status message on stderr
+    pub fn status(&self, msg: &str) {
+        if self.interactive {
+            eprintln!("{msg}");
+        }
+    }
+
+    pub fn progress_bar(&self, len: usize) -> ProgressBar {
+        if !self.interactive {
+            return ProgressBar::hidden();
+        }
+        let pb = ProgressBar::with_draw_target(Some(len as u64), ProgressDrawTarget::stderr());
+        pb.set_style(
+            ProgressStyle::with_template(
+                "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] ({eta})",
+            )
+            .unwrap()
+            .with_key(
+                "eta",
+                |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+                    write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap()
+                },
+            )
+            .progress_chars("#>-"),
+        );
+        pb
+    }
+
+    pub fn print<R: Row>(&self, rows: &[R]) -> anyhow::Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        match self.format {
+            OutputFormat::Table => {
+                table(rows).print(&mut stdout)?;
+            }
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut stdout, rows)?;
+                writeln!(stdout)?;
+            }
+            OutputFormat::Jsonl => {
+                for r in rows {
+                    serde_json::to_writer(&mut stdout, r)?;
+                    writeln!(stdout)?;
+                }
+            }
+            OutputFormat::Csv => {
+                // The header line is derived from the serialized field names
+                let mut writer = csv::Writer::from_writer(stdout);
+                for r in rows {
+                    writer.serialize(r)?;
+                }
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints rows as a table on stderr whatever the output format, for the user to review
+    /// them while stdout is kept parsable
+    pub fn print_table_to_stderr<R: Row>(&self, rows: &[R]) -> anyhow::Result<()> {
+        table(rows).print(&mut std::io::stderr().lock())?;
+        Ok(())
+    }
+}
+
+fn table<R: Row>(rows: &[R]) -> Table {
+    let mut table = Table::new();
+    table.add_row(TableRow::from(R::HEADERS));
+    for r in rows {
+        table.add_row(TableRow::from(r.cells()));
+    }
+    table
+}
+
+pub fn format_domain(domain: &str) -> String {
+    if domain.ends_with(".sol") {
+        return domain.to_owned();
+    }
+    format!("{domain}.sol")
+}
+
+pub fn make_tx_url(sig: &str) -> String {
+    format!("https://explorer.solana.com/tx/{sig}")
+}
+
+/// The result of the transaction sent for a domain
+#[derive(Serialize)]
+pub struct TransactionRow {
+    pub domain: String,
+    pub signature: Option<String>,
+    pub explorer: Option<String>,
+    pub error: Option<String>,
+}
+
+impl TransactionRow {
+    pub fn new(domain: &str, result: &Result<Signature, String>) -> Self {
+        Self {
+            domain: format_domain(domain),
+            signature: result.as_ref().ok().map(|s| s.to_string()),
+            explorer: result.as_ref().ok().map(|s| make_tx_url(&s.to_string())),
+            error: result.as_ref().err().cloned(),
+        }
+    }
+}
+
+impl Row for TransactionRow {
+    const HEADERS: &'static [&'static str] = &["Domain", "Transaction", "Explorer"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.domain.clone(),
+            cell(&self.signature, "Failed"),
+            self.explorer
+                .clone()
+                .or_else(|| self.error.clone())
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+/// Renders an optional value as a table cell
+pub fn cell<T: ToString>(value: &Option<T>, default: &str) -> String {
+    value
+        .as_ref()
+        .map_or_else(|| default.to_owned(), |v| v.to_string())
+}
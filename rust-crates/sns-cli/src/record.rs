@@ -0,0 +1,680 @@
+//! The `record` command group.
+//!
+//! V1 records are plain name registries whose content is written by the domain owner. V2 records
+//! are written through the records program, which also stores whether the record was signed by
+//! the current domain owner (staleness) and by the owner of its content (right of association,
+//! RoA), e.g. the address of a SOL record.
+use {
+    crate::{
+        config::Config,
+        output::{cell, format_domain, make_tx_url, Output, Row},
+        tx::{check_failures, SignerSource, TxArgs, TxSender},
+    },
+    anyhow::{anyhow, bail},
+    clap::{Args, Subcommand},
+    serde::Serialize,
+    sns_records::state::validation::Validation,
+    sns_sdk::{
+        bridge::Owner,
+        derivation::{get_domain_key, get_hashed_name, get_prefix, Domain},
+        non_blocking::resolve,
+        record::{
+            get_record_key, get_record_v2_key, record_v1,
+            record_v2::{
+                create_record_v2_instruction, delete_record_v2_instruction, parse_record_v2,
+                update_record_v2_instruction, validate_record_v2_content_instruction,
+            },
+            Record, RecordVersion,
+        },
+    },
+    solana_account_decoder::UiAccountEncoding,
+    solana_client::{
+        nonblocking::rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
+    solana_program::{program_pack::Pack, pubkey::Pubkey},
+    solana_sdk::signature::Signature,
+    spl_name_service::state::NameRecordHeader,
+    std::{collections::HashMap, str::FromStr},
+};
+
+#[derive(Debug, Args)]
+pub struct RecordCommand {
+    #[command(subcommand)]
+    pub cmd: RecordSubCommand,
+    #[clap(long, help = "Use records V2", default_value_t)]
+    pub v2: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RecordSubCommand {
+    #[command(about = "Gets a record content")]
+    Get {
+        #[clap(long, help = "The domain of the record to fetch")]
+        domain: String,
+        #[clap(long, help = "The record to fetch")]
+        record: String,
+    },
+    #[command(about = "Lists the V1 and V2 records of a domain with their validation status")]
+    List {
+        #[arg(required = true, help = "The domain with or without .sol suffix")]
+        domain: String,
+    },
+    #[command(
+        about = "Sets a record content, V2 records are also signed by the domain owner and, for SOL records, by their address"
+    )]
+    Set {
+        #[clap(long, help = "The domain of the record to set")]
+        domain: String,
+        #[clap(long, help = "The record to set")]
+        record: String,
+        #[clap(long, help = "The content of the record")]
+        content: String,
+        #[clap(
+            long,
+            help = "The keypair path or address signing the right of association of a V2 SOL record [default: the domain owner, when it is the record address]"
+        )]
+        roa_signer: Option<String>,
+        #[command(flatten)]
+        tx: TxArgs,
+    },
+    #[command(about = "Deletes a record and refunds its rent")]
+    Delete {
+        #[clap(long, help = "The domain of the record to delete")]
+        domain: String,
+        #[clap(long, help = "The record to delete")]
+        record: String,
+        #[command(flatten)]
+        tx: TxArgs,
+    },
+    #[command(
+        about = "Shows whether a V2 record is stale and whether its right of association is verified"
+    )]
+    Verify {
+        #[clap(long, help = "The domain of the record to verify")]
+        domain: String,
+        #[clap(long, help = "The record to verify")]
+        record: String,
+    },
+    #[command(about = "Dump records system info")]
+    SystemDump,
+}
+
+pub async fn process_record(
+    rpc_client: &RpcClient,
+    out: &Output,
+    config: &Config,
+    command: RecordCommand,
+) -> anyhow::Result<()> {
+    let RecordCommand { cmd, v2 } = command;
+    let version = if v2 {
+        RecordVersion::V2
+    } else {
+        RecordVersion::V1
+    };
+    match cmd {
+        RecordSubCommand::Get { domain, record } => {
+            process_record_get(rpc_client, out, &domain, &record, version).await
+        }
+        RecordSubCommand::List { domain } => process_record_list(rpc_client, out, &domain).await,
+        RecordSubCommand::Set {
+            domain,
+            record,
+            content,
+            roa_signer,
+            tx,
+        } => match version {
+            RecordVersion::V1 if roa_signer.is_some() => {
+                bail!("--roa-signer is only supported by V2 records")
+            }
+            RecordVersion::V1 => {
+                process_record_set(rpc_client, out, config, &tx, &domain, &record, &content).await
+            }
+            RecordVersion::V2 => {
+                process_record_set_v2(
+                    rpc_client,
+                    out,
+                    config,
+                    &tx,
+                    &domain,
+                    &record,
+                    &content,
+                    roa_signer.as_deref(),
+                )
+                .await
+            }
+        },
+        RecordSubCommand::Delete { domain, record, tx } => {
+            process_record_delete(rpc_client, out, config, &tx, &domain, &record, version).await
+        }
+        RecordSubCommand::Verify { domain, record } => {
+            process_record_verify(rpc_client, out, &domain, &record).await
+        }
+        RecordSubCommand::SystemDump => process_system_dump(rpc_client).await,
+    }
+}
+
+#[derive(Serialize)]
+struct RecordSetRow {
+    step: String,
+    signature: Option<String>,
+    explorer: Option<String>,
+    error: Option<String>,
+}
+
+impl RecordSetRow {
+    fn new(step: &str, result: &Result<Signature, String>) -> Self {
+        Self {
+            step: step.to_owned(),
+            signature: result.as_ref().ok().map(|s| s.to_string()),
+            explorer: result.as_ref().ok().map(|s| make_tx_url(&s.to_string())),
+            error: result.as_ref().err().cloned(),
+        }
+    }
+}
+
+impl Row for RecordSetRow {
+    const HEADERS: &'static [&'static str] = &["Transaction", "Signature"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.step.clone(),
+            self.explorer
+                .clone()
+                .or_else(|| self.error.clone())
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+async fn process_record_set(
+    rpc_client: &RpcClient,
+    out: &Output,
+    config: &Config,
+    tx_args: &TxArgs,
+    domain: &str,
+    record_str: &str,
+    content: &str,
+) -> anyhow::Result<()> {
+    let mut ixs = vec![];
+    let mut sender = TxSender::new(rpc_client, config, tx_args, None)?;
+    let owner = sender.authority();
+    let payer = sender.fee_payer();
+
+    let record = Record::try_from_str(record_str)?;
+    let data = record_v1::serialize_record(content, record.clone())?;
+    let key = get_record_key(domain, record, RecordVersion::V1)?;
+    let hashed_name = get_hashed_name(&format!(
+        "{}{record_str}",
+        get_prefix(Domain::Record(RecordVersion::V1))
+    ));
+    let parent = get_domain_key(domain)?;
+
+    let lamports = rpc_client
+        .get_minimum_balance_for_rent_exemption(data.len() + NameRecordHeader::LEN)
+        .await?;
+
+    let acc = rpc_client
+        .get_account_with_commitment(&key, config.commitment)
+        .await?;
+
+    let create = spl_name_service::instruction::create(
+        spl_name_service::ID,
+        spl_name_service::instruction::NameRegistryInstruction::Create {
+            hashed_name,
+            lamports,
+            space: data.len() as u32,
+        },
+        key,
+        payer,
+        owner,
+        None,
+        Some(parent),
+        Some(owner),
+    )?;
+
+    if let Some(value) = acc.value {
+        let (_, current) = resolve::deserialize_name_registry(&value.data)
+            .map_err(|e| anyhow!("Invalid record account {key}: {e}"))?;
+        if current.len() != data.len() {
+            // Delete existing record
+            // This is the only way to handle the account resizing
+            let ix =
+                spl_name_service::instruction::delete(spl_name_service::ID, key, owner, owner)?;
+
+            // Clean up transaction, which has to land before the record is created again
+            sender.execute("Clean up", vec![ix]).await?;
+            sender.flush().await?;
+
+            // Create the record
+            ixs.push(create);
+        }
+    } else {
+        ixs.push(create);
+    }
+
+    // Update
+    let ix = spl_name_service::instruction::update(
+        spl_name_service::ID,
+        0,
+        data,
+        key,
+        owner,
+        Some(parent),
+    )?;
+    ixs.push(ix);
+    if !sender.has_failures() {
+        sender.execute("Update record", ixs).await?;
+    }
+
+    if let Some(sent) = sender.finish(out).await? {
+        let rows = sent
+            .iter()
+            .map(|(step, result)| RecordSetRow::new(step, result))
+            .collect::<Vec<_>>();
+        out.print(&rows)?;
+        check_failures(&sent)?;
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn process_record_set_v2(
+    rpc_client: &RpcClient,
+    out: &Output,
+    config: &Config,
+    tx_args: &TxArgs,
+    domain: &str,
+    record_str: &str,
+    content: &str,
+    roa_signer: Option<&str>,
+) -> anyhow::Result<()> {
+    let mut sender = TxSender::new(rpc_client, config, tx_args, None)?;
+    let owner = sender.authority();
+    let payer = sender.fee_payer();
+
+    let record = Record::try_from_str(record_str)?;
+    let key = get_record_v2_key(domain, record.clone())?;
+    let exists = rpc_client
+        .get_account_with_commitment(&key, config.commitment)
+        .await?
+        .value
+        .is_some();
+
+    let mut ixs = vec![if exists {
+        update_record_v2_instruction(domain, record.clone(), content, owner, payer)?
+    } else {
+        create_record_v2_instruction(domain, record.clone(), content, owner, payer)?
+    }];
+    ixs.push(validate_record_v2_content_instruction(
+        true,
+        domain,
+        record.clone(),
+        owner,
+        payer,
+        owner,
+    )?);
+
+    match record.roa_validation() {
+        Validation::Solana if record == Record::Sol => {
+            let verifier = Pubkey::from_str(content)?;
+            let signer = roa_signer.map(SignerSource::parse).transpose()?;
+            match &signer {
+                Some(signer) if signer.pubkey() != verifier => {
+                    bail!("The right of association of the record must be signed by {verifier}")
+                }
+                None if verifier != owner => out.status(&format!(
+                    "The right of association of the record is not verified, use --roa-signer to sign it with {verifier}"
+                )),
+                _ => ixs.push(validate_record_v2_content_instruction(
+                    false, domain, record, owner, payer, verifier,
+                )?),
+            }
+            if let Some(signer) = signer {
+                sender.add_signer(signer);
+            }
+        }
+        Validation::Solana => out.status(&format!(
+            "The right of association of {record} records is verified by the SNS guardian"
+        )),
+        Validation::Ethereum => out.status(&format!(
+            "The right of association of {record} records has to be signed with an Ethereum wallet"
+        )),
+        _ => {}
+    }
+
+    sender.execute(domain, ixs).await?;
+    sender.print(out).await?;
+    Ok(())
+}
+
+async fn process_record_delete(
+    rpc_client: &RpcClient,
+    out: &Output,
+    config: &Config,
+    tx_args: &TxArgs,
+    domain: &str,
+    record_str: &str,
+    version: RecordVersion,
+) -> anyhow::Result<()> {
+    let mut sender = TxSender::new(rpc_client, config, tx_args, None)?;
+    let owner = sender.authority();
+
+    let record = Record::try_from_str(record_str)?;
+    let key = get_record_key(domain, record.clone(), version)?;
+    if resolve::resolve_name_registry(rpc_client, &key)
+        .await?
+        .is_none()
+    {
+        bail!(
+            "The {record} record of {} does not exist",
+            format_domain(domain)
+        );
+    }
+    let ix = match version {
+        RecordVersion::V1 => {
+            spl_name_service::instruction::delete(spl_name_service::ID, key, owner, owner)?
+        }
+        RecordVersion::V2 => {
+            delete_record_v2_instruction(domain, record, owner, sender.fee_payer())?
+        }
+    };
+    sender.execute(domain, vec![ix]).await?;
+    sender.print(out).await?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct RecordRow {
+    domain: String,
+    record: String,
+    content: Option<String>,
+}
+
+impl Row for RecordRow {
+    const HEADERS: &'static [&'static str] = &["Domain", "Record", "Content"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.domain.clone(),
+            self.record.clone(),
+            cell(&self.content, "Record not found"),
+        ]
+    }
+}
+
+async fn process_record_get(
+    rpc_client: &RpcClient,
+    out: &Output,
+    domain: &str,
+    record_str: &str,
+    version: RecordVersion,
+) -> anyhow::Result<()> {
+    let record = Record::try_from_str(record_str)?;
+    let key = get_record_key(domain, record.clone(), version)?;
+    let content = match version {
+        RecordVersion::V1 => match resolve::resolve_name_registry(rpc_client, &key).await? {
+            Some((_, data)) => {
+                // SOL records must be signed by the registry owner of the domain
+                let owner = resolve::resolve_name_registry(rpc_client, &get_domain_key(domain)?)
+                    .await?
+                    .map(|(header, _)| header.owner)
+                    .unwrap_or_default();
+                Some(record_v1::deserialize_record(
+                    &data,
+                    record.clone(),
+                    &key,
+                    &owner,
+                )?)
+            }
+            None => None,
+        },
+        RecordVersion::V2 => {
+            let account = rpc_client
+                .get_account_with_commitment(&key, rpc_client.commitment())
+                .await?
+                .value;
+            match account {
+                Some(account) => Some(
+                    parse_record_v2(record.clone(), &account.data)
+                        .await?
+                        .content,
+                ),
+                None => None,
+            }
+        }
+    };
+    out.print(&[RecordRow {
+        domain: format_domain(domain),
+        record: record.as_str().to_owned(),
+        content,
+    }])?;
+    Ok(())
+}
+
+/// A record with its validation status, which is only known for V2 records
+#[derive(Serialize)]
+struct RecordStatusRow {
+    domain: String,
+    record: String,
+    version: u8,
+    key: String,
+    /// `None` when the record data cannot be decoded
+    content: Option<String>,
+    stale: Option<bool>,
+    /// Whether the right of association is verified, `None` when the record does not require one
+    roa_verified: Option<bool>,
+}
+
+impl RecordStatusRow {
+    async fn new(
+        domain: &str,
+        record: Record,
+        version: RecordVersion,
+        key: &Pubkey,
+        data: &[u8],
+        domain_owner: &Owner,
+    ) -> Self {
+        let mut row = Self {
+            domain: format_domain(domain),
+            record: record.as_str().to_owned(),
+            version: version as u8,
+            key: key.to_string(),
+            content: None,
+            stale: None,
+            roa_verified: None,
+        };
+        match version {
+            RecordVersion::V1 => {
+                // Bridged domains cannot sign SOL records
+                let owner = match domain_owner {
+                    Owner::Solana(owner) => *owner,
+                    Owner::Foreign { .. } => Pubkey::default(),
+                };
+                row.content = data
+                    .get(NameRecordHeader::LEN..)
+                    .and_then(|data| record_v1::deserialize_record(data, record, key, &owner).ok());
+            }
+            RecordVersion::V2 => {
+                if let Ok(parsed) = parse_record_v2(record.clone(), data).await {
+                    row.stale = Some(parsed.verify_staleness(domain_owner).is_err());
+                    row.roa_verified = (!matches!(record.roa_validation(), Validation::None))
+                        .then(|| parsed.verify_roa().is_ok());
+                    row.content = Some(parsed.content);
+                }
+            }
+        }
+        row
+    }
+}
+
+impl Row for RecordStatusRow {
+    const HEADERS: &'static [&'static str] = &[
+        "Record",
+        "Version",
+        "Content",
+        "Stale",
+        "Right of association",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.record.clone(),
+            format!("V{}", self.version),
+            cell(&self.content, "Invalid content"),
+            match self.stale {
+                Some(true) => "yes".to_owned(),
+                Some(false) => "no".to_owned(),
+                None => String::new(),
+            },
+            match (self.version, self.roa_verified) {
+                (_, Some(true)) => "verified".to_owned(),
+                (_, Some(false)) => "unverified".to_owned(),
+                (2, None) if self.content.is_some() => "not required".to_owned(),
+                _ => String::new(),
+            },
+        ]
+    }
+}
+
+/// The owner signing the records of a domain, on the foreign chain when the domain is bridged
+async fn get_domain_owner(rpc_client: &RpcClient, domain: &str) -> anyhow::Result<Owner> {
+    resolve::get_registry_owner(rpc_client, &get_domain_key(domain)?)
+        .await?
+        .ok_or_else(|| anyhow!("{} does not exist", format_domain(domain)))
+}
+
+async fn process_record_list(
+    rpc_client: &RpcClient,
+    out: &Output,
+    domain: &str,
+) -> anyhow::Result<()> {
+    let owner = get_domain_owner(rpc_client, domain).await?;
+    let records = [RecordVersion::V1, RecordVersion::V2]
+        .into_iter()
+        .flat_map(|version| Record::ALL.into_iter().map(move |record| (record, version)))
+        .map(|(record, version)| {
+            Ok((
+                record.clone(),
+                version,
+                get_record_key(domain, record, version)?,
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let keys = records.iter().map(|(_, _, key)| *key).collect::<Vec<_>>();
+    let mut accounts = vec![];
+    for keys in keys.chunks(100) {
+        accounts.extend(rpc_client.get_multiple_accounts(keys).await?);
+    }
+
+    let mut rows = vec![];
+    for ((record, version, key), account) in records.into_iter().zip(accounts) {
+        if let Some(account) = account {
+            rows.push(
+                RecordStatusRow::new(domain, record, version, &key, &account.data, &owner).await,
+            );
+        }
+    }
+    out.print(&rows)?;
+    Ok(())
+}
+
+async fn process_record_verify(
+    rpc_client: &RpcClient,
+    out: &Output,
+    domain: &str,
+    record_str: &str,
+) -> anyhow::Result<()> {
+    let record = Record::try_from_str(record_str)?;
+    let owner = get_domain_owner(rpc_client, domain).await?;
+    let key = get_record_v2_key(domain, record.clone())?;
+    let account = rpc_client
+        .get_account_with_commitment(&key, rpc_client.commitment())
+        .await?
+        .value
+        .ok_or_else(|| {
+            anyhow!(
+                "The {record} V2 record of {} does not exist",
+                format_domain(domain)
+            )
+        })?;
+    let row = RecordStatusRow::new(
+        domain,
+        record,
+        RecordVersion::V2,
+        &key,
+        &account.data,
+        &owner,
+    )
+    .await;
+    out.print(&[row])?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub struct SystemDumpRecord {
+    domain: String,
+    record_type: Option<String>,
+    record_key: String,
+}
+
+pub async fn process_system_dump(rpc_client: &RpcClient) -> anyhow::Result<()> {
+    let record_v2_accounts = rpc_client
+        .get_program_accounts_with_config(
+            &spl_name_service::ID,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    64,
+                    sns_records::central_state::KEY.as_ref().to_vec(),
+                ))]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    data_slice: None,
+                    commitment: None,
+                    min_context_slot: None,
+                },
+                with_context: None,
+            },
+        )
+        .await?;
+    eprintln!("Found {} v2 records", record_v2_accounts.len());
+    let mut by_parent: HashMap<Pubkey, Vec<Pubkey>> = HashMap::new();
+    for (k, a) in record_v2_accounts {
+        let (spl_header, _) = resolve::deserialize_name_registry(&a.data)
+            .map_err(|e| anyhow!("Invalid record account {k}: {e}"))?;
+        let entry = by_parent.entry(spl_header.parent_name);
+        match entry {
+            std::collections::hash_map::Entry::Occupied(mut o) => o.get_mut().push(k),
+            std::collections::hash_map::Entry::Vacant(v) => {
+                v.insert(vec![k]);
+            }
+        }
+    }
+    let parent_domains = by_parent.keys().cloned().collect::<Vec<_>>();
+    eprintln!("From a total of {} domains", by_parent.keys().len());
+    let reverse_lookup_keys = resolve::resolve_reverse_batch(rpc_client, &parent_domains).await?;
+    for (domain, name) in parent_domains.into_iter().zip(reverse_lookup_keys) {
+        if name.is_none() {
+            continue;
+        }
+        let name = name.unwrap();
+        let possible_records = Record::ALL
+            .into_iter()
+            .map(|r| get_record_v2_key(&name, r.clone()).map(|res| (res, r)))
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        for record in by_parent.get(&domain).unwrap() {
+            let record_type = possible_records.get(record);
+            println!(
+                "{}",
+                serde_json::to_string(&SystemDumpRecord {
+                    domain: name.clone(),
+                    record_type: record_type.map(|r| r.as_str().to_owned()),
+                    record_key: record.to_string()
+                })?
+            )
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,308 @@
+//! SQLite storage for name service snapshots.
+//!
+//! Public keys are stored as base58 strings. Every snapshot is a full download of the name
+//! service, which is then diffed against the database. The accounts are read by several scans:
+//! the snapshot slot is the slot of the first one, and the later scans may be served at more
+//! recent slots, stored in `meta` under the `end_slot` key. Every table has a `slot` column
+//! holding the slot of the snapshot in which the row was last inserted or modified, not the slot
+//! at which the account changed on chain, so that consumers can fetch the rows changed by later
+//! snapshots with `WHERE slot > ?`. Rows missing from a snapshot are removed and logged in the
+//! `deletions` table with the slot of the snapshot that removed them: the account was closed, or
+//! can no longer be classified, e.g. a subdomain whose reverse was deleted. Accounts that cannot
+//! be classified are not stored, their count is printed. The slot of the latest snapshot is
+//! stored in `meta` under the `slot` key.
+use {
+    crate::output::Row,
+    anyhow::anyhow,
+    rusqlite::{params, types::Value, Connection, OptionalExtension},
+    serde::Serialize,
+    sns_sdk::snapshot::Snapshot,
+};
+
+pub const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS domains (
+    key TEXT PRIMARY KEY,
+    name TEXT,
+    owner TEXT NOT NULL,
+    class TEXT NOT NULL,
+    data_len INTEGER NOT NULL,
+    slot INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS subdomains (
+    key TEXT PRIMARY KEY,
+    parent TEXT NOT NULL,
+    name TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    slot INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS reverses (
+    key TEXT PRIMARY KEY,
+    parent TEXT NOT NULL,
+    name TEXT NOT NULL,
+    slot INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS records (
+    key TEXT PRIMARY KEY,
+    domain TEXT NOT NULL,
+    record TEXT,
+    version INTEGER NOT NULL,
+    owner TEXT NOT NULL,
+    data BLOB NOT NULL,
+    slot INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS nft_records (
+    key TEXT PRIMARY KEY,
+    name_account TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    nft_mint TEXT NOT NULL,
+    active INTEGER NOT NULL,
+    slot INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS favourites (
+    key TEXT PRIMARY KEY,
+    owner TEXT,
+    name_account TEXT NOT NULL,
+    slot INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS deletions (
+    key TEXT NOT NULL,
+    table_name TEXT NOT NULL,
+    slot INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS domains_owner ON domains (owner);
+CREATE INDEX IF NOT EXISTS domains_name ON domains (name);
+CREATE INDEX IF NOT EXISTS subdomains_parent ON subdomains (parent);
+CREATE INDEX IF NOT EXISTS records_domain ON records (domain);
+CREATE INDEX IF NOT EXISTS deletions_slot ON deletions (slot);
+";
+
+#[derive(Serialize)]
+pub struct TableStats {
+    pub table: &'static str,
+    pub rows: usize,
+    pub changed: usize,
+    pub deleted: usize,
+}
+
+impl Row for TableStats {
+    const HEADERS: &'static [&'static str] = &["Table", "Rows", "Changed", "Deleted"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.table.to_owned(),
+            self.rows.to_string(),
+            self.changed.to_string(),
+            self.deleted.to_string(),
+        ]
+    }
+}
+
+fn sync_table(
+    tx: &rusqlite::Transaction,
+    table: &'static str,
+    columns: &[&str],
+    rows: Vec<Vec<Value>>,
+    slot: u64,
+) -> anyhow::Result<TableStats> {
+    let placeholders = vec!["?"; columns.len() + 1].join(", ");
+    let updates = columns[1..]
+        .iter()
+        .map(|c| format!("{c} = excluded.{c}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let changed_condition = columns[1..]
+        .iter()
+        .map(|c| format!("{table}.{c} IS NOT excluded.{c}"))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    let sql = format!(
+        "INSERT INTO {table} ({}, slot) VALUES ({placeholders}) \
+         ON CONFLICT(key) DO UPDATE SET {updates}, slot = excluded.slot \
+         WHERE {changed_condition}",
+        columns.join(", ")
+    );
+
+    tx.execute("DELETE FROM seen", [])?;
+    let mut stats = TableStats {
+        table,
+        rows: rows.len(),
+        changed: 0,
+        deleted: 0,
+    };
+    {
+        let mut upsert = tx.prepare_cached(&sql)?;
+        let mut seen = tx.prepare_cached("INSERT OR IGNORE INTO seen (key) VALUES (?)")?;
+        for mut row in rows {
+            seen.execute([&row[0]])?;
+            row.push(Value::Integer(slot as i64));
+            stats.changed += upsert.execute(rusqlite::params_from_iter(row))?;
+        }
+    }
+
+    tx.execute(
+        &format!(
+            "INSERT INTO deletions (key, table_name, slot) \
+             SELECT key, ?1, ?2 FROM {table} WHERE key NOT IN (SELECT key FROM seen)"
+        ),
+        params![table, slot as i64],
+    )?;
+    stats.deleted = tx.execute(
+        &format!("DELETE FROM {table} WHERE key NOT IN (SELECT key FROM seen)"),
+        [],
+    )?;
+    Ok(stats)
+}
+
+fn key(k: &impl ToString) -> Value {
+    Value::Text(k.to_string())
+}
+
+/// Writes `snapshot` to the database, only touching the rows that changed since the previous snapshot
+pub fn write_snapshot(
+    conn: &mut Connection,
+    snapshot: &Snapshot,
+) -> anyhow::Result<Vec<TableStats>> {
+    conn.execute_batch(SCHEMA)?;
+    let tx = conn.transaction()?;
+
+    let previous_slot = tx
+        .query_row("SELECT value FROM meta WHERE key = 'slot'", [], |r| {
+            r.get::<_, String>(0)
+        })
+        .optional()?
+        .map(|s| s.parse::<u64>())
+        .transpose()?;
+    if let Some(previous_slot) = previous_slot {
+        if previous_slot > snapshot.slot {
+            return Err(anyhow!(
+                "The database is at slot {previous_slot}, which is more recent than the snapshot slot {}",
+                snapshot.slot
+            ));
+        }
+    }
+    tx.execute_batch("CREATE TEMP TABLE IF NOT EXISTS seen (key TEXT PRIMARY KEY)")?;
+
+    let slot = snapshot.slot;
+    let mut stats = vec![];
+
+    stats.push(sync_table(
+        &tx,
+        "domains",
+        &["key", "name", "owner", "class", "data_len"],
+        snapshot
+            .domains
+            .iter()
+            .map(|d| {
+                vec![
+                    key(&d.key),
+                    d.name.clone().map_or(Value::Null, Value::Text),
+                    key(&d.owner),
+                    key(&d.class),
+                    Value::Integer(d.data_len as i64),
+                ]
+            })
+            .collect(),
+        slot,
+    )?);
+    stats.push(sync_table(
+        &tx,
+        "subdomains",
+        &["key", "parent", "name", "owner"],
+        snapshot
+            .subdomains
+            .iter()
+            .map(|s| {
+                vec![
+                    key(&s.key),
+                    key(&s.parent),
+                    Value::Text(s.name.clone()),
+                    key(&s.owner),
+                ]
+            })
+            .collect(),
+        slot,
+    )?);
+    stats.push(sync_table(
+        &tx,
+        "reverses",
+        &["key", "parent", "name"],
+        snapshot
+            .reverses
+            .iter()
+            .map(|r| vec![key(&r.key), key(&r.parent), Value::Text(r.name.clone())])
+            .collect(),
+        slot,
+    )?);
+    stats.push(sync_table(
+        &tx,
+        "records",
+        &["key", "domain", "record", "version", "owner", "data"],
+        snapshot
+            .records
+            .iter()
+            .map(|r| {
+                vec![
+                    key(&r.key),
+                    key(&r.domain),
+                    r.record
+                        .as_ref()
+                        .map_or(Value::Null, |r| Value::Text(r.as_str().to_owned())),
+                    Value::Integer(r.version as i64),
+                    key(&r.owner),
+                    Value::Blob(r.data.clone()),
+                ]
+            })
+            .collect(),
+        slot,
+    )?);
+    stats.push(sync_table(
+        &tx,
+        "nft_records",
+        &["key", "name_account", "owner", "nft_mint", "active"],
+        snapshot
+            .nft_records
+            .iter()
+            .map(|n| {
+                vec![
+                    key(&n.key),
+                    key(&n.name_account),
+                    key(&n.owner),
+                    key(&n.nft_mint),
+                    Value::Integer(n.active as i64),
+                ]
+            })
+            .collect(),
+        slot,
+    )?);
+    stats.push(sync_table(
+        &tx,
+        "favourites",
+        &["key", "owner", "name_account"],
+        snapshot
+            .favourites
+            .iter()
+            .map(|f| {
+                vec![
+                    key(&f.key),
+                    f.owner.map_or(Value::Null, |o| key(&o)),
+                    key(&f.name_account),
+                ]
+            })
+            .collect(),
+        slot,
+    )?);
+
+    for (name, value) in [("slot", slot), ("end_slot", snapshot.end_slot)] {
+        tx.execute(
+            "INSERT INTO meta (key, value) VALUES (?1, ?2) \
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            [name.to_owned(), value.to_string()],
+        )?;
+    }
+    tx.commit()?;
+    Ok(stats)
+}
@@ -0,0 +1,873 @@
+//! Signing, export and submission of the transactions built by the mutating commands.
+//!
+//! By default transactions are signed with the configured keypair and sent. When the domains are
+//! held by a cold wallet or a multisig, `--sign-only` prints the transactions with the signatures
+//! that could be made locally, `--signer PUBKEY=SIGNATURE` adds signatures collected offline and
+//! `--dump-instructions` prints the raw instructions to import them in a multisig. Signed
+//! transactions are broadcast with `sns tx submit`. `--dry-run` simulates the transactions instead.
+use {
+    crate::{
+        config::Config,
+        output::{cell, make_tx_url, Output, Row, TransactionRow},
+    },
+    anyhow::{anyhow, bail, Context},
+    base64::Engine,
+    clap::{Args, Subcommand, ValueEnum},
+    serde::Serialize,
+    sns_sdk::{
+        non_blocking::transaction::send_instruction_groups,
+        transaction::{compute_budget_instructions, InstructionGroup, SendConfig},
+    },
+    solana_account_decoder::UiAccountEncoding,
+    solana_client::{
+        nonblocking::{
+            blockhash_query::{BlockhashQuery, Source},
+            rpc_client::RpcClient,
+        },
+        rpc_config::{RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig},
+    },
+    solana_program::{hash::Hash, instruction::Instruction, pubkey::Pubkey},
+    solana_sdk::{
+        bs58,
+        signature::{Keypair, Signature},
+        signer::{keypair::read_keypair_file, Signer},
+        system_instruction,
+        transaction::{Transaction, VersionedTransaction},
+    },
+    std::{io::BufRead, str::FromStr},
+};
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum Encoding {
+    #[default]
+    Base58,
+    Base64,
+}
+
+impl Encoding {
+    pub fn encode(self, data: &[u8]) -> String {
+        match self {
+            Self::Base58 => bs58::encode(data).into_string(),
+            Self::Base64 => base64::engine::general_purpose::STANDARD.encode(data),
+        }
+    }
+
+    pub fn decode(self, data: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(match self {
+            Self::Base58 => bs58::decode(data).into_vec()?,
+            Self::Base64 => base64::engine::general_purpose::STANDARD.decode(data)?,
+        })
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct TxArgs {
+    #[arg(
+        long,
+        help = "Sign the transactions with the available signers and print them instead of sending them"
+    )]
+    pub sign_only: bool,
+    #[arg(
+        long,
+        conflicts_with = "sign_only",
+        help = "Print the instructions instead of the transactions, to import them in a multisig"
+    )]
+    pub dump_instructions: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["sign_only", "dump_instructions"],
+        help = "Simulate the transactions and report their logs, compute units and balance changes without sending them"
+    )]
+    pub dry_run: bool,
+    #[arg(
+        long,
+        help = "The blockhash to use instead of the latest one, or the durable nonce with --nonce"
+    )]
+    pub blockhash: Option<Hash>,
+    #[arg(
+        long,
+        help = "The nonce account whose durable nonce is used instead of a recent blockhash"
+    )]
+    pub nonce: Option<Pubkey>,
+    #[arg(
+        long,
+        requires = "nonce",
+        help = "The keypair path or address of the nonce authority [default: the fee payer]"
+    )]
+    pub nonce_authority: Option<String>,
+    #[arg(
+        long,
+        help = "The keypair path or address of the fee payer [default: the configured keypair]"
+    )]
+    pub fee_payer: Option<String>,
+    #[arg(
+        long = "signer",
+        value_name = "PUBKEY=SIGNATURE",
+        help = "A signature collected with --sign-only, can be repeated"
+    )]
+    pub signers: Vec<String>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help = "The encoding of the printed transactions and instructions"
+    )]
+    pub encoding: Encoding,
+    #[arg(
+        long,
+        help = "The compute unit limit requested for each domain [default: the runtime default]"
+    )]
+    pub compute_unit_limit: Option<u32>,
+    #[arg(
+        long,
+        default_value_t = 8,
+        help = "The maximum number of transactions sent concurrently"
+    )]
+    pub max_concurrency: usize,
+}
+
+/// A signer given as a keypair file or, when it signs elsewhere, as an address
+pub enum SignerSource {
+    Keypair(Keypair),
+    Address(Pubkey),
+}
+
+impl SignerSource {
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        if let Ok(address) = Pubkey::from_str(source) {
+            return Ok(Self::Address(address));
+        }
+        read_keypair_file(source)
+            .map(Self::Keypair)
+            .map_err(|e| anyhow!("{source:?} is neither an address nor a valid keypair: {e}"))
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        match self {
+            Self::Keypair(keypair) => keypair.pubkey(),
+            Self::Address(address) => *address,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SignedRow {
+    /// The domain or the step the transaction is for
+    pub label: String,
+    pub blockhash: String,
+    pub transaction: String,
+    /// The signatures of the transaction as space separated `PUBKEY=SIGNATURE` pairs
+    pub signers: String,
+    /// The space separated addresses whose signature is missing
+    pub absent_signers: String,
+}
+
+impl Row for SignedRow {
+    const HEADERS: &'static [&'static str] = &[
+        "Label",
+        "Blockhash",
+        "Transaction",
+        "Signers",
+        "Absent signers",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.label.clone(),
+            self.blockhash.clone(),
+            self.transaction.clone(),
+            self.signers.replace(' ', "\n"),
+            self.absent_signers.replace(' ', "\n"),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+pub struct InstructionRow {
+    /// The domain or the step the instruction is for
+    pub label: String,
+    pub program_id: String,
+    /// The space separated accounts as `PUBKEY:FLAGS`, with `s` for signers and `w` for writable accounts
+    pub accounts: String,
+    pub data: String,
+}
+
+impl Row for InstructionRow {
+    const HEADERS: &'static [&'static str] = &["Label", "Program", "Accounts", "Data"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.label.clone(),
+            self.program_id.clone(),
+            self.accounts.replace(' ', "\n"),
+            self.data.clone(),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+pub struct SimulationRow {
+    /// The domain or the step the transaction is for
+    pub label: String,
+    pub success: bool,
+    pub error: Option<String>,
+    pub units_consumed: Option<u64>,
+    /// The lamport balance changes of the transaction accounts, including the fees and rent, as
+    /// space separated `PUBKEY:DELTA` pairs
+    pub lamport_changes: String,
+    /// The program logs, separated by new lines
+    pub logs: String,
+}
+
+impl Row for SimulationRow {
+    const HEADERS: &'static [&'static str] = &[
+        "Label",
+        "Result",
+        "Compute units",
+        "Lamport changes",
+        "Logs",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.label.clone(),
+            match &self.error {
+                Some(error) => format!("Failed: {error}"),
+                None => "Success".to_owned(),
+            },
+            cell(&self.units_consumed, ""),
+            self.lamport_changes.replace(' ', "\n"),
+            self.logs.clone(),
+        ]
+    }
+}
+
+/// Signs and sends, or exports, the transactions of a command
+pub struct TxSender<'a> {
+    rpc_client: &'a RpcClient,
+    config: &'a Config,
+    args: &'a TxArgs,
+    authority: SignerSource,
+    fee_payer: Option<SignerSource>,
+    nonce_authority: Option<SignerSource>,
+    /// Signers required by some instructions only, e.g. the verifier of a record
+    additional_signers: Vec<SignerSource>,
+    presigned: Vec<(Pubkey, Signature)>,
+    send_config: SendConfig,
+    queued: Vec<InstructionGroup>,
+    sent: Vec<(String, Result<Signature, String>)>,
+    signed: Vec<SignedRow>,
+    instructions: Vec<InstructionRow>,
+    simulations: Vec<SimulationRow>,
+}
+
+impl<'a> TxSender<'a> {
+    /// The authority signing the instructions is `authority` when given, or the configured keypair
+    pub fn new(
+        rpc_client: &'a RpcClient,
+        config: &'a Config,
+        args: &'a TxArgs,
+        authority: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let authority = match authority {
+            Some(authority) => SignerSource::parse(authority)?,
+            None => config.signer()?,
+        };
+        let presigned = args
+            .signers
+            .iter()
+            .map(|s| {
+                let (pubkey, signature) = s
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("Invalid signer {s:?}, expected PUBKEY=SIGNATURE"))?;
+                Ok((Pubkey::from_str(pubkey)?, Signature::from_str(signature)?))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            rpc_client,
+            config,
+            args,
+            authority,
+            fee_payer: args
+                .fee_payer
+                .as_deref()
+                .map(SignerSource::parse)
+                .transpose()?,
+            nonce_authority: args
+                .nonce_authority
+                .as_deref()
+                .map(SignerSource::parse)
+                .transpose()?,
+            additional_signers: vec![],
+            presigned,
+            send_config: SendConfig {
+                compute_unit_price: config.priority_fee,
+                compute_unit_limit: args.compute_unit_limit,
+                max_concurrency: args.max_concurrency,
+                ..Default::default()
+            },
+            queued: vec![],
+            sent: vec![],
+            signed: vec![],
+            instructions: vec![],
+            simulations: vec![],
+        })
+    }
+
+    pub fn authority(&self) -> Pubkey {
+        self.authority.pubkey()
+    }
+
+    pub fn fee_payer(&self) -> Pubkey {
+        self.fee_payer.as_ref().unwrap_or(&self.authority).pubkey()
+    }
+
+    /// Adds a signer which is only required by some of the instructions
+    pub fn add_signer(&mut self, signer: SignerSource) {
+        self.additional_signers.push(signer);
+    }
+
+    /// Whether the transactions are packed and sent concurrently by the SDK, which is only possible
+    /// when they do not depend on a given blockhash or on signatures collected offline
+    fn batched(&self) -> bool {
+        !(self.args.sign_only || self.args.dump_instructions || self.args.dry_run)
+            && self.args.blockhash.is_none()
+            && self.args.nonce.is_none()
+            && self.presigned.is_empty()
+    }
+
+    /// Builds a transaction for `ixs`, then sends it or records it to be printed by [`Self::finish`].
+    /// When nothing has to be exported, the instructions are queued to be sent with the others by
+    /// [`Self::flush`]
+    pub async fn execute(&mut self, label: &str, ixs: Vec<Instruction>) -> anyhow::Result<()> {
+        if self.batched() {
+            self.queued.push(InstructionGroup::new(label, ixs));
+            return Ok(());
+        }
+
+        if self.args.dump_instructions {
+            self.instructions.extend(ixs.iter().map(|ix| {
+                InstructionRow {
+                    label: label.to_owned(),
+                    program_id: ix.program_id.to_string(),
+                    accounts: ix
+                        .accounts
+                        .iter()
+                        .map(|a| {
+                            let flags = match (a.is_signer, a.is_writable) {
+                                (true, true) => "sw",
+                                (true, false) => "s",
+                                (false, true) => "w",
+                                (false, false) => "",
+                            };
+                            format!("{}:{flags}", a.pubkey)
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    data: self.args.encoding.encode(&ix.data),
+                }
+            }));
+            return Ok(());
+        }
+
+        let fee_payer = self.fee_payer();
+        let mut instructions = vec![];
+        if let Some(nonce) = self.args.nonce {
+            if !self.sent.is_empty() || !self.signed.is_empty() {
+                bail!("A nonce account can only be used for a single transaction");
+            }
+            let authority = self
+                .nonce_authority
+                .as_ref()
+                .map_or(fee_payer, |a| a.pubkey());
+            instructions.push(system_instruction::advance_nonce_account(
+                &nonce, &authority,
+            ));
+        }
+        instructions.extend(compute_budget_instructions(&self.send_config, 1));
+        instructions.extend(ixs);
+
+        let source = self
+            .args
+            .nonce
+            .map_or(Source::Cluster, Source::NonceAccount);
+        let query = match self.args.blockhash {
+            Some(blockhash) if self.args.sign_only => BlockhashQuery::Static(blockhash),
+            Some(blockhash) => BlockhashQuery::Validated(source, blockhash),
+            None => BlockhashQuery::Rpc(source),
+        };
+        let blockhash = query
+            .get_blockhash(self.rpc_client, self.config.commitment)
+            .await
+            .map_err(|e| anyhow!("Failed to get the blockhash: {e}"))?;
+
+        let mut tx = Transaction::new_with_payer(&instructions, Some(&fee_payer));
+        tx.message.recent_blockhash = blockhash;
+        let signer_keys = tx
+            .message
+            .signer_keys()
+            .into_iter()
+            .copied()
+            .collect::<Vec<_>>();
+
+        let mut keypairs: Vec<&Keypair> = vec![];
+        for source in [
+            Some(&self.authority),
+            self.fee_payer.as_ref(),
+            self.nonce_authority.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(&self.additional_signers)
+        {
+            if let SignerSource::Keypair(keypair) = source {
+                if signer_keys.contains(&keypair.pubkey())
+                    && !keypairs.iter().any(|k| k.pubkey() == keypair.pubkey())
+                {
+                    keypairs.push(keypair);
+                }
+            }
+        }
+        tx.try_partial_sign(&keypairs, blockhash)?;
+
+        let message_data = tx.message_data();
+        for (pubkey, signature) in &self.presigned {
+            let Some(position) = signer_keys.iter().position(|k| k == pubkey) else {
+                continue;
+            };
+            if !signature.verify(pubkey.as_ref(), &message_data) {
+                bail!("The signature of {pubkey} does not match the {label} transaction");
+            }
+            tx.signatures[position] = *signature;
+        }
+
+        if self.args.dry_run {
+            let row = self.simulate(label, &tx).await?;
+            self.simulations.push(row);
+            return Ok(());
+        }
+
+        let (signers, absent): (Vec<_>, Vec<_>) = signer_keys
+            .iter()
+            .zip(&tx.signatures)
+            .partition(|(_, signature)| **signature != Signature::default());
+
+        if self.args.sign_only {
+            self.signed.push(SignedRow {
+                label: label.to_owned(),
+                blockhash: blockhash.to_string(),
+                transaction: self.args.encoding.encode(&bincode::serialize(&tx)?),
+                signers: signers
+                    .iter()
+                    .map(|(pubkey, signature)| format!("{pubkey}={signature}"))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                absent_signers: absent
+                    .iter()
+                    .map(|(pubkey, _)| pubkey.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            });
+            return Ok(());
+        }
+
+        if !absent.is_empty() {
+            bail!(
+                "Missing signatures for {}, use --sign-only to export the transaction",
+                absent
+                    .iter()
+                    .map(|(pubkey, _)| pubkey.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        let signature = self
+            .rpc_client
+            .send_and_confirm_transaction(&tx)
+            .await
+            .with_context(|| format!("Failed to send the {label} transaction"))?;
+        self.sent.push((label.to_owned(), Ok(signature)));
+        Ok(())
+    }
+
+    /// Sends the queued instructions, packed in as few transactions as possible
+    pub async fn flush(&mut self) -> anyhow::Result<()> {
+        if self.queued.is_empty() {
+            return Ok(());
+        }
+        let mut keypairs: Vec<&Keypair> = vec![];
+        for source in [self.fee_payer.as_ref(), Some(&self.authority)]
+            .into_iter()
+            .flatten()
+            .chain(&self.additional_signers)
+        {
+            match source {
+                SignerSource::Keypair(keypair) => {
+                    if !keypairs.iter().any(|k| k.pubkey() == keypair.pubkey()) {
+                        keypairs.push(keypair)
+                    }
+                }
+                SignerSource::Address(address) => bail!(
+                    "Missing signature for {address}, use --sign-only to export the transactions"
+                ),
+            }
+        }
+        let groups = std::mem::take(&mut self.queued);
+        let results = send_instruction_groups(
+            self.rpc_client,
+            keypairs.as_slice(),
+            &groups,
+            &self.send_config,
+        )
+        .await?;
+        for r in results {
+            let result = r.result.map_err(|e| e.to_string());
+            self.sent
+                .extend(r.domains.into_iter().map(|d| (d, result.clone())));
+        }
+        Ok(())
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.sent.iter().any(|(_, r)| r.is_err())
+    }
+
+    /// Simulates a transaction without verifying its signatures, so that missing signers do not
+    /// prevent the simulation
+    async fn simulate(&self, label: &str, tx: &Transaction) -> anyhow::Result<SimulationRow> {
+        let keys = &tx.message.account_keys;
+        let before = self
+            .rpc_client
+            .get_multiple_accounts_with_commitment(keys, self.config.commitment)
+            .await?
+            .value;
+        let result = self
+            .rpc_client
+            .simulate_transaction_with_config(
+                tx,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(self.config.commitment),
+                    accounts: Some(RpcSimulateTransactionAccountsConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        addresses: keys.iter().map(|k| k.to_string()).collect(),
+                    }),
+                    ..Default::default()
+                },
+            )
+            .await?
+            .value;
+
+        let lamport_changes = match &result.accounts {
+            Some(after) => keys
+                .iter()
+                .zip(before.iter().zip(after))
+                .filter_map(|(key, (before, after))| {
+                    let before = before.as_ref().map_or(0, |a| a.lamports) as i128;
+                    let after = after.as_ref().map_or(0, |a| a.lamports) as i128;
+                    (before != after).then(|| format!("{key}:{:+}", after - before))
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+            None => String::new(),
+        };
+
+        Ok(SimulationRow {
+            label: label.to_owned(),
+            success: result.err.is_none(),
+            error: result.err.map(|e| e.to_string()),
+            units_consumed: result.units_consumed,
+            lamport_changes,
+            logs: result.logs.unwrap_or_default().join("\n"),
+        })
+    }
+
+    /// Prints the exported transactions or instructions, or returns the signatures of the sent
+    /// transactions so that the command prints its own output
+    pub async fn finish(
+        mut self,
+        out: &Output,
+    ) -> anyhow::Result<Option<Vec<(String, Result<Signature, String>)>>> {
+        self.flush().await?;
+        if self.args.dump_instructions {
+            out.print(&self.instructions)?;
+            Ok(None)
+        } else if self.args.sign_only {
+            out.print(&self.signed)?;
+            Ok(None)
+        } else if self.args.dry_run {
+            out.print(&self.simulations)?;
+            Ok(None)
+        } else {
+            Ok(Some(self.sent))
+        }
+    }
+
+    /// Like [`Self::finish`], printing the sent transactions with their domain
+    pub async fn print(self, out: &Output) -> anyhow::Result<()> {
+        if let Some(sent) = self.finish(out).await? {
+            let rows = sent
+                .iter()
+                .map(|(domain, result)| TransactionRow::new(domain, result))
+                .collect::<Vec<_>>();
+            out.print(&rows)?;
+            check_failures(&sent)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns an error when some transactions failed, once the results have been printed
+pub fn check_failures(sent: &[(String, Result<Signature, String>)]) -> anyhow::Result<()> {
+    let failed = sent.iter().filter(|(_, r)| r.is_err()).count();
+    if failed > 0 {
+        bail!("{failed} of {} transactions failed", sent.len());
+    }
+    Ok(())
+}
+
+#[derive(Debug, Args)]
+#[command(about = "Broadcast transactions exported with --sign-only")]
+pub struct TxCommand {
+    #[command(subcommand)]
+    pub cmd: TxSubCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TxSubCommand {
+    #[command(about = "Submits fully signed transactions")]
+    Submit {
+        #[arg(help = "The encoded transactions, read one per line from stdin when omitted")]
+        transactions: Vec<String>,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            help = "The encoding of the transactions"
+        )]
+        encoding: Encoding,
+    },
+}
+
+#[derive(Serialize)]
+struct SubmitRow {
+    signature: String,
+    explorer: String,
+}
+
+impl Row for SubmitRow {
+    const HEADERS: &'static [&'static str] = &["Transaction", "Explorer"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.signature.clone(), self.explorer.clone()]
+    }
+}
+
+pub async fn process_submit(
+    rpc_client: &RpcClient,
+    out: &Output,
+    mut transactions: Vec<String>,
+    encoding: Encoding,
+) -> anyhow::Result<()> {
+    if transactions.is_empty() {
+        for line in std::io::stdin().lock().lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                transactions.push(line.trim().to_owned());
+            }
+        }
+    }
+
+    let mut rows = vec![];
+    let pb = out.progress_bar(transactions.len());
+    for (i, encoded) in transactions.iter().enumerate() {
+        let tx: VersionedTransaction = bincode::deserialize(&encoding.decode(encoded)?)
+            .with_context(|| format!("Transaction {i} is not a valid transaction"))?;
+        tx.verify_with_results()
+            .iter()
+            .all(|valid| *valid)
+            .then_some(())
+            .ok_or_else(|| anyhow!("Transaction {i} is not fully signed"))?;
+        let signature = rpc_client.send_and_confirm_transaction(&tx).await?;
+        rows.push(SubmitRow {
+            explorer: make_tx_url(&signature.to_string()),
+            signature: signature.to_string(),
+        });
+        pb.inc(1);
+    }
+    pb.finish_and_clear();
+    out.print(&rows)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::output::OutputFormat,
+        sns_sdk::testing::rpc::LocalRpc,
+        solana_program::instruction::AccountMeta,
+        solana_sdk::{commitment_config::CommitmentConfig, signer::keypair::write_keypair_file},
+    };
+
+    fn keypair_file(name: &str, keypair: &Keypair) -> String {
+        let path = std::env::temp_dir().join(format!("sns-tx-{}-{name}.json", std::process::id()));
+        write_keypair_file(keypair, &path).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    fn config() -> Config {
+        Config {
+            url: "local".to_owned(),
+            keypair_path: "unused".into(),
+            commitment: CommitmentConfig::confirmed(),
+            priority_fee: 0,
+            output: OutputFormat::Table,
+        }
+    }
+
+    /// The arguments of `--sign-only` with a fixed blockhash, which does not query the RPC node
+    fn sign_only_args(signers: Vec<String>) -> TxArgs {
+        TxArgs {
+            sign_only: true,
+            dump_instructions: false,
+            dry_run: false,
+            blockhash: Some(Hash::new_from_array([7; 32])),
+            nonce: None,
+            nonce_authority: None,
+            fee_payer: None,
+            signers,
+            encoding: Encoding::Base64,
+            compute_unit_limit: None,
+            max_concurrency: 1,
+        }
+    }
+
+    /// An instruction signed by both `authority` and `cold_wallet`
+    fn instruction(authority: Pubkey, cold_wallet: Pubkey) -> Instruction {
+        Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[1, 2, 3],
+            vec![
+                AccountMeta::new(authority, true),
+                AccountMeta::new_readonly(cold_wallet, true),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_signer_source_parse() {
+        let address = Keypair::new().pubkey();
+        assert!(matches!(
+            SignerSource::parse(&address.to_string()).unwrap(),
+            SignerSource::Address(a) if a == address
+        ));
+
+        let keypair = Keypair::new();
+        let source = SignerSource::parse(&keypair_file("parse", &keypair)).unwrap();
+        assert!(matches!(&source, SignerSource::Keypair(k) if k.pubkey() == keypair.pubkey()));
+        assert_eq!(source.pubkey(), keypair.pubkey());
+
+        let err = SignerSource::parse("missing.json").err().unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("\"missing.json\" is neither an address nor a valid keypair"));
+    }
+
+    #[tokio::test]
+    async fn test_signer_arguments() {
+        let rpc_client = LocalRpc::default().into_client();
+        let config = config();
+        let authority = keypair_file("signer-arguments", &Keypair::new());
+        let key = Keypair::new().pubkey();
+
+        for invalid in [
+            format!("{key}"),
+            format!("{key}={key}"),
+            format!("not a key={}", Signature::default()),
+        ] {
+            let args = sign_only_args(vec![invalid.clone()]);
+            assert!(
+                TxSender::new(&rpc_client, &config, &args, Some(&authority)).is_err(),
+                "{invalid}"
+            );
+        }
+
+        // A signature which does not sign the message of the transaction
+        let cold_wallet = Keypair::new();
+        let args = sign_only_args(vec![format!(
+            "{}={}",
+            cold_wallet.pubkey(),
+            cold_wallet.sign_message(b"another message")
+        )]);
+        let mut sender = TxSender::new(&rpc_client, &config, &args, Some(&authority)).unwrap();
+        let ix = instruction(sender.authority(), cold_wallet.pubkey());
+        let err = sender.execute("bonfida.sol", vec![ix]).await.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "The signature of {} does not match the bonfida.sol transaction",
+                cold_wallet.pubkey()
+            )
+        );
+
+        // The signatures of keys which do not sign the transaction are ignored
+        let args = sign_only_args(vec![format!("{key}={}", Signature::default())]);
+        let mut sender = TxSender::new(&rpc_client, &config, &args, Some(&authority)).unwrap();
+        let ix = instruction(sender.authority(), sender.authority());
+        sender.execute("bonfida.sol", vec![ix]).await.unwrap();
+        assert_eq!(sender.signed[0].absent_signers, "");
+    }
+
+    #[tokio::test]
+    async fn test_sign_only_round_trip() {
+        let rpc_client = LocalRpc::default().into_client();
+        let config = config();
+        let authority = Keypair::new();
+        let authority_path = keypair_file("round-trip", &authority);
+        let cold_wallet = Keypair::new();
+        let ix = instruction(authority.pubkey(), cold_wallet.pubkey());
+
+        // The authority signs, the cold wallet is only known by its address
+        let args = sign_only_args(vec![]);
+        let mut sender = TxSender::new(&rpc_client, &config, &args, Some(&authority_path)).unwrap();
+        sender.add_signer(SignerSource::Address(cold_wallet.pubkey()));
+        sender
+            .execute("bonfida.sol", vec![ix.clone()])
+            .await
+            .unwrap();
+        let row = &sender.signed[0];
+        assert_eq!(row.blockhash, Hash::new_from_array([7; 32]).to_string());
+        assert_eq!(row.absent_signers, cold_wallet.pubkey().to_string());
+        let tx: Transaction =
+            bincode::deserialize(&Encoding::Base64.decode(&row.transaction).unwrap()).unwrap();
+        assert_eq!(
+            row.signers,
+            format!("{}={}", authority.pubkey(), tx.signatures[0])
+        );
+        assert!(tx.verify().is_err());
+
+        // The cold wallet signs the exported transaction offline, its signature is imported
+        let signature = cold_wallet.sign_message(&tx.message_data());
+        let args = sign_only_args(vec![format!("{}={signature}", cold_wallet.pubkey())]);
+        let mut sender = TxSender::new(&rpc_client, &config, &args, Some(&authority_path)).unwrap();
+        sender.add_signer(SignerSource::Address(cold_wallet.pubkey()));
+        sender.execute("bonfida.sol", vec![ix]).await.unwrap();
+        let row = &sender.signed[0];
+        assert_eq!(row.absent_signers, "");
+        let signed: Transaction =
+            bincode::deserialize(&Encoding::Base64.decode(&row.transaction).unwrap()).unwrap();
+        assert_eq!(signed.message, tx.message);
+        signed.verify().unwrap();
+        assert_eq!(
+            row.signers,
+            format!(
+                "{}={} {}={signature}",
+                authority.pubkey(),
+                signed.signatures[0],
+                cold_wallet.pubkey()
+            )
+        );
+    }
+}
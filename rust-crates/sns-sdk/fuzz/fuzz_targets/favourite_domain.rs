@@ -0,0 +1,7 @@
+#![no_main]
+
+use {libfuzzer_sys::fuzz_target, sns_sdk::favourite_domain::FavouriteDomain};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = FavouriteDomain::parse(data);
+});
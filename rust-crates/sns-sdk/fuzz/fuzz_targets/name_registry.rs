@@ -0,0 +1,9 @@
+#![no_main]
+
+use {libfuzzer_sys::fuzz_target, sns_sdk::non_blocking::resolve::deserialize_name_registry};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((_, content)) = deserialize_name_registry(data) {
+        assert_eq!(content.len() + 96, data.len());
+    }
+});
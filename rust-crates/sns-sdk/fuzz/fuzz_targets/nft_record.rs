@@ -0,0 +1,7 @@
+#![no_main]
+
+use {borsh::BorshDeserialize, libfuzzer_sys::fuzz_target, name_tokenizer::state::NftRecord};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = NftRecord::deserialize(&mut &data[..]);
+});
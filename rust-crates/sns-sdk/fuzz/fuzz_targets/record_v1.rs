@@ -0,0 +1,28 @@
+#![no_main]
+
+use {
+    libfuzzer_sys::fuzz_target,
+    sns_sdk::record::{record_v1::deserialize_record, Record},
+    solana_program::pubkey::Pubkey,
+};
+
+// The first byte selects the record type, the following 32 bytes the record key and the next 32
+// bytes the domain owner
+fuzz_target!(|data: &[u8]| {
+    let Some((selector, data)) = data.split_first() else {
+        return;
+    };
+    let Some((key, data)) = data.split_first_chunk::<32>() else {
+        return;
+    };
+    let Some((owner, data)) = data.split_first_chunk::<32>() else {
+        return;
+    };
+    let record = Record::ALL[*selector as usize % Record::ALL.len()].clone();
+    let _ = deserialize_record(
+        data,
+        record,
+        &Pubkey::new_from_array(*key),
+        &Pubkey::new_from_array(*owner),
+    );
+});
@@ -0,0 +1,21 @@
+#![no_main]
+
+use {
+    libfuzzer_sys::fuzz_target,
+    sns_sdk::record::{
+        record_v2::{deserialize_record_v2_content, parse_record_v2},
+        Record,
+    },
+};
+
+// The first byte selects the record type, the rest is the account data
+fuzz_target!(|data: &[u8]| {
+    let Some((selector, data)) = data.split_first() else {
+        return;
+    };
+    let record = Record::ALL[*selector as usize % Record::ALL.len()].clone();
+    let _ = deserialize_record_v2_content(data, record.clone());
+    if let Ok(parsed) = futures::executor::block_on(parse_record_v2(record, data)) {
+        let _ = parsed.verify_roa();
+    }
+});
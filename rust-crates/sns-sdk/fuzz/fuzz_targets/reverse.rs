@@ -0,0 +1,7 @@
+#![no_main]
+
+use {libfuzzer_sys::fuzz_target, sns_sdk::non_blocking::resolve::deserialize_reverse};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = deserialize_reverse(data);
+});
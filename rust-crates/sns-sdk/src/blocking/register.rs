@@ -67,7 +67,7 @@ pub fn register_domain_name(
                 None
             }
         })
-        .ok_or(SnsError::UnsupportedMint)?;
+        .ok_or(SnsError::UnsupportedMint { mint: *mint })?;
     let vault = get_associated_token_address(&VAULT_OWNER, mint);
     let instruction = get_register_instruction(
         REGISTER_PROGRAM_ID,
@@ -105,10 +105,12 @@ pub fn register_domain_name(
 mod test {
     use super::*;
     use crate::register::FIDA_MINT;
+    use crate::testing::{self, rpc::LocalRpc};
     use crate::utils::test::generate_random_string;
     use dotenv::dotenv;
 
     #[test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     fn test_registration() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -129,6 +131,7 @@ mod test {
     }
 
     #[test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     fn test_registration_ref() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -147,4 +150,34 @@ mod test {
         let res = client.simulate_transaction(&tx).unwrap();
         assert!(res.value.err.is_none())
     }
+
+    #[test]
+    fn test_local_registration() {
+        let buyer = Pubkey::new_unique();
+        let buyer_token_account = get_associated_token_address(&buyer, &FIDA_MINT);
+        let register = |client: &RpcClient| {
+            register_domain_name(
+                client,
+                "bonfida",
+                1_000,
+                &buyer,
+                &buyer_token_account,
+                Some(&FIDA_MINT),
+                Some(&REFERRERS[2]),
+            )
+            .unwrap()
+        };
+
+        // The token account of the referrer is created when it does not exist
+        let tx = register(&LocalRpc::default().into_blocking_client());
+        assert_eq!(tx.message.instructions.len(), 2);
+        let client = LocalRpc::new([testing::token_account(&FIDA_MINT, &REFERRERS[2], 0)])
+            .into_blocking_client();
+        let tx = register(&client);
+        assert_eq!(tx.message.instructions.len(), 1);
+        assert!(tx
+            .message
+            .account_keys
+            .contains(&get_associated_token_address(&REFERRERS[2], &FIDA_MINT)));
+    }
 }
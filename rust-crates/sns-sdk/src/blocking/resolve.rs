@@ -1,26 +1,42 @@
 use {
-    solana_account_decoder::UiAccountEncoding,
+    solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig},
     solana_client::{
-        client_error::{ClientError, ClientErrorKind},
         rpc_client::RpcClient,
         rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
         rpc_filter::{Memcmp, RpcFilterType},
-        rpc_request::RpcError::RpcRequestError,
     },
     solana_program::{program_pack::Pack, pubkey::Pubkey},
     spl_name_service::state::{get_seeds_and_key, NameRecordHeader},
     spl_token::state::Mint,
+    std::collections::HashMap,
 };
 
 use crate::{
+    bridge::{derive_x_domain_key, deserialize_x_domain, is_bridged, Owner, XDomain},
+    content::{ContentTarget, CONTENT_RECORDS},
     derivation::{
-        get_domain_key, get_domain_mint, get_hashed_name, REVERSE_LOOKUP_CLASS, ROOT_DOMAIN_ACCOUNT,
+        derive_reverse, get_domain_key, get_domain_mint, get_hashed_name, REVERSE_LOOKUP_CLASS,
+        ROOT_DOMAIN_ACCOUNT,
+    },
+    error::{ErrorKind, SnsError},
+    favourite_domain::{
+        build_primary_domain, derive_favourite_domain_key, FavouriteDomain, PrimaryDomain,
+        PrimaryDomainAccounts,
+    },
+    record::{
+        get_record_key,
+        record_v1::{deserialize_record, verify_sol_record},
+        record_v2::parse_record_v2,
+        Record, RecordVersion,
     },
-    error::SnsError,
-    favourite_domain::{derive_favorite_domain_key, FavouriteDomain},
-    record::{get_record_key, record_v1::check_sol_record, Record},
 };
 
+/// Resolves the Solana owner of a domain: the holder of its NFT when it is tokenized, else the
+/// address of its SOL record V1 when it is signed by the registry owner, else the registry owner.
+///
+/// For a domain bridged to another chain, this returns the registry owner, i.e. the warp emitter
+/// or the `XDomain` account, and not its owner. Use [`resolve_domain_owner`] to get the owner on
+/// the foreign chain
 pub fn resolve_owner(rpc_client: &RpcClient, domain: &str) -> Result<Option<Pubkey>, SnsError> {
     let key = get_domain_key(domain)?;
 
@@ -29,41 +45,35 @@ pub fn resolve_owner(rpc_client: &RpcClient, domain: &str) -> Result<Option<Pubk
         _ => return Ok(None),
     };
 
-    let nft_owner = resolve_nft_owner(rpc_client, &key)?;
+    resolve_owner_from_registry(rpc_client, domain, &key, &header.owner).map(Some)
+}
+
+/// [`resolve_owner`] for a domain whose name registry is already fetched
+fn resolve_owner_from_registry(
+    rpc_client: &RpcClient,
+    domain: &str,
+    key: &Pubkey,
+    registry_owner: &Pubkey,
+) -> Result<Pubkey, SnsError> {
+    let nft_owner = resolve_nft_owner(rpc_client, key)?;
 
     if let Some(nft_owner) = nft_owner {
-        return Ok(Some(nft_owner));
+        return Ok(nft_owner);
     }
 
     let sol_record_key = get_record_key(domain, Record::Sol, crate::record::RecordVersion::V1)?;
     match resolve_name_registry(rpc_client, &sol_record_key) {
         Ok(Some((_, data))) => {
-            let data = &data[..96];
-            let record = [&data[..32], &sol_record_key.to_bytes()].concat();
-            let sig = &data[32..];
-            let encoded = hex::encode(record);
-            if check_sol_record(encoded.as_bytes(), sig, header.owner)? {
-                let owner = Pubkey::new_from_array(
-                    data[0..32]
-                        .try_into()
-                        .map_err(|_| SnsError::InvalidPubkey)?,
-                );
-                return Ok(Some(owner));
+            // Malformed and unverified SOL records are ignored
+            if let Ok(dst) = verify_sol_record(&data, &sol_record_key, registry_owner) {
+                return Ok(dst);
             }
         }
-        Err(SnsError::SolanaClient(ClientError {
-            request: None,
-            kind: ClientErrorKind::RpcError(RpcRequestError(err)),
-        })) => {
-            return Err(SnsError::SolanaClient(ClientError {
-                request: None,
-                kind: ClientErrorKind::RpcError(RpcRequestError(err)),
-            }))
-        }
+        Err(e) if e.kind() == ErrorKind::Transport => return Err(e),
         _ => {}
     }
 
-    Ok(Some(header.owner))
+    Ok(*registry_owner)
 }
 
 pub fn resolve_record(
@@ -80,6 +90,28 @@ pub fn resolve_record(
     }
 }
 
+pub fn deserialize_name_registry(data: &[u8]) -> Result<(NameRecordHeader, Vec<u8>), SnsError> {
+    if data.len() < NameRecordHeader::LEN {
+        return Err(SnsError::AccountTooSmall {
+            expected: NameRecordHeader::LEN,
+            len: data.len(),
+        });
+    }
+    let (header, data) = data.split_at(NameRecordHeader::LEN);
+    let header = NameRecordHeader::unpack_unchecked(header)?;
+    Ok((header, data.to_vec()))
+}
+
+pub fn deserialize_reverse(data: &[u8]) -> Result<String, SnsError> {
+    let (len, data) = data
+        .split_first_chunk::<4>()
+        .ok_or(SnsError::InvalidReverse)?;
+    let reverse = data
+        .get(..u32::from_le_bytes(*len) as usize)
+        .ok_or(SnsError::InvalidReverse)?;
+    String::from_utf8(reverse.to_vec()).or(Err(SnsError::InvalidReverse))
+}
+
 pub fn resolve_name_registry(
     rpc_client: &RpcClient,
     key: &Pubkey,
@@ -88,14 +120,32 @@ pub fn resolve_name_registry(
         .get_account_with_commitment(key, rpc_client.commitment())?
         .value;
     if let Some(acc) = acc {
-        let header = NameRecordHeader::unpack_unchecked(&acc.data[0..NameRecordHeader::LEN])?;
-        let data = acc.data[NameRecordHeader::LEN..].to_vec();
-        Ok(Some((header, data)))
+        Ok(Some(deserialize_name_registry(&acc.data)?))
     } else {
         Ok(None)
     }
 }
 
+#[allow(clippy::type_complexity)]
+pub fn resolve_name_registry_batch(
+    rpc_client: &RpcClient,
+    keys: &[Pubkey],
+) -> Result<Vec<Option<(NameRecordHeader, Vec<u8>)>>, SnsError> {
+    let mut res = vec![];
+    for k in keys.chunks(100) {
+        let accs = rpc_client.get_multiple_accounts(k)?;
+        for acc in accs {
+            if let Some(acc) = acc {
+                let des = deserialize_name_registry(&acc.data)?;
+                res.push(Some(des))
+            } else {
+                res.push(None)
+            }
+        }
+    }
+    Ok(res)
+}
+
 pub fn resolve_reverse(rpc_client: &RpcClient, key: &Pubkey) -> Result<Option<String>, SnsError> {
     let hashed = get_hashed_name(&key.to_string());
     let (key, _) = get_seeds_and_key(
@@ -105,15 +155,26 @@ pub fn resolve_reverse(rpc_client: &RpcClient, key: &Pubkey) -> Result<Option<St
         None,
     );
     if let Some((_, data)) = resolve_name_registry(rpc_client, &key)? {
-        let len = u32::from_le_bytes(data[0..4].try_into().unwrap());
-        let reverse = String::from_utf8(data[4..4 + len as usize].to_vec())
-            .or(Err(SnsError::InvalidReverse))?;
-        Ok(Some(reverse))
+        Ok(Some(deserialize_reverse(&data)?))
     } else {
         Ok(None)
     }
 }
 
+pub fn resolve_reverse_batch(
+    rpc_client: &RpcClient,
+    keys: &[Pubkey],
+) -> Result<Vec<Option<String>>, SnsError> {
+    let reverse_keys = keys
+        .iter()
+        .map(|k| derive_reverse(k, None))
+        .collect::<Vec<_>>();
+    resolve_name_registry_batch(rpc_client, &reverse_keys)?
+        .into_iter()
+        .map(|r| r.map(|(_, data)| deserialize_reverse(&data)).transpose())
+        .collect()
+}
+
 pub fn get_domains_owner(rpc_client: &RpcClient, owner: Pubkey) -> Result<Vec<Pubkey>, SnsError> {
     let config = RpcProgramAccountsConfig {
         filters: Some(vec![
@@ -134,6 +195,72 @@ pub fn get_domains_owner(rpc_client: &RpcClient, owner: Pubkey) -> Result<Vec<Pu
     Ok(keys)
 }
 
+/// Maximum length of the reverse lookup data fetched by `get_all_registered_domains`
+const MAX_REVERSE_LEN: usize = 256;
+
+pub fn get_all_registered_domains(
+    rpc_client: &RpcClient,
+) -> Result<Vec<(Pubkey, Option<String>)>, SnsError> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            0,
+            ROOT_DOMAIN_ACCOUNT.to_bytes().to_vec(),
+        ))]),
+        with_context: None,
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: Some(UiDataSliceConfig {
+                offset: 0,
+                length: 0,
+            }),
+            ..Default::default()
+        },
+    };
+    let domains = rpc_client.get_program_accounts_with_config(&spl_name_service::ID, config)?;
+
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                0,
+                Pubkey::default().to_bytes().to_vec(),
+            )),
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                64,
+                REVERSE_LOOKUP_CLASS.to_bytes().to_vec(),
+            )),
+        ]),
+        with_context: None,
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: Some(UiDataSliceConfig {
+                offset: NameRecordHeader::LEN,
+                length: MAX_REVERSE_LEN,
+            }),
+            ..Default::default()
+        },
+    };
+    let reverses = rpc_client
+        .get_program_accounts_with_config(&spl_name_service::ID, config)?
+        .into_iter()
+        .filter_map(|(k, acc)| Some((k, deserialize_reverse(&acc.data).ok()?)))
+        .collect::<HashMap<_, _>>();
+
+    let res = domains
+        .into_iter()
+        .map(|(k, _)| (k, reverses.get(&derive_reverse(&k, None)).cloned()))
+        .collect();
+    Ok(res)
+}
+
+pub fn get_domain_keys_with_reverses(
+    rpc_client: &RpcClient,
+    owner: Pubkey,
+) -> Result<Vec<(Pubkey, Option<String>)>, SnsError> {
+    let keys = get_domains_owner(rpc_client, owner)?;
+    let reverses = resolve_reverse_batch(rpc_client, &keys)?;
+    Ok(keys.into_iter().zip(reverses).collect())
+}
+
 pub fn get_subdomains(rpc_client: &RpcClient, parent: Pubkey) -> Result<Vec<String>, SnsError> {
     let config = RpcProgramAccountsConfig {
         filters: Some(vec![
@@ -154,13 +281,11 @@ pub fn get_subdomains(rpc_client: &RpcClient, parent: Pubkey) -> Result<Vec<Stri
     let res = res
         .into_iter()
         .map(|(_, acc)| {
-            let mut offset = NameRecordHeader::LEN;
-            let len = u32::from_le_bytes(acc.data[offset..offset + 4].try_into().unwrap());
-            offset += 4;
-            String::from_utf8(acc.data[offset..offset + len as usize].to_vec()).unwrap()
+            let (_, data) = deserialize_name_registry(&acc.data)?;
+            let reverse = deserialize_reverse(&data)?;
+            Ok(reverse.strip_prefix('\0').unwrap_or(&reverse).to_owned())
         })
-        .map(|x| x.strip_prefix('\0').unwrap().to_owned())
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, SnsError>>()?;
 
     Ok(res)
 }
@@ -171,11 +296,13 @@ pub fn resolve_nft_owner(
 ) -> Result<Option<Pubkey>, SnsError> {
     let mint_key = get_domain_mint(domain_key);
     let acc = rpc_client.get_multiple_accounts(&[mint_key])?;
-    let acc = acc.first().ok_or(SnsError::InvalidDomain)?;
-    if acc.is_none() {
+    let acc = acc
+        .first()
+        .ok_or(SnsError::AccountNotFound { key: mint_key })?;
+    let Some(acc) = acc else {
         return Ok(None);
-    }
-    let mint = Mint::unpack(&acc.as_ref().unwrap().data)?;
+    };
+    let mint = Mint::unpack(&acc.data)?;
     if mint.supply != 1 {
         return Ok(None);
     }
@@ -203,11 +330,137 @@ pub fn resolve_nft_owner(
     Ok(None)
 }
 
-pub async fn get_favourite_domain(
+/// Returns the owner of a domain, resolved like [`resolve_owner`] unless the domain is bridged to
+/// another chain, in which case the owner is read from its `XDomain` account
+pub fn resolve_domain_owner(
+    rpc_client: &RpcClient,
+    domain: &str,
+) -> Result<Option<Owner>, SnsError> {
+    let key = get_domain_key(domain)?;
+    let Some((header, _)) = resolve_name_registry(rpc_client, &key)? else {
+        return Ok(None);
+    };
+    match owner_from_registry(rpc_client, &key, header.owner)? {
+        Owner::Solana(_) => resolve_owner_from_registry(rpc_client, domain, &key, &header.owner)
+            .map(|owner| Some(Owner::Solana(owner))),
+        owner => Ok(Some(owner)),
+    }
+}
+
+/// Returns the owner of the name registry of a domain, i.e. the owner that signs its records,
+/// or the owner on the foreign chain when the domain is bridged
+pub fn get_registry_owner(
+    rpc_client: &RpcClient,
+    domain_key: &Pubkey,
+) -> Result<Option<Owner>, SnsError> {
+    let header = match resolve_name_registry(rpc_client, domain_key)? {
+        Some((h, _)) => h,
+        _ => return Ok(None),
+    };
+    owner_from_registry(rpc_client, domain_key, header.owner).map(Some)
+}
+
+/// The [`Owner`] of a domain whose name registry is owned by `registry_owner`, read from its
+/// `XDomain` account when the domain is bridged
+fn owner_from_registry(
+    rpc_client: &RpcClient,
+    domain_key: &Pubkey,
+    registry_owner: Pubkey,
+) -> Result<Owner, SnsError> {
+    let x_domain = if is_bridged(domain_key, &registry_owner) {
+        get_x_domain(rpc_client, domain_key)?
+    } else {
+        None
+    };
+    Ok(Owner::new(domain_key, registry_owner, x_domain.as_ref()))
+}
+
+/// Resolves the content served by a domain, e.g. by a web gateway. The verified V2 records of
+/// [`CONTENT_RECORDS`] are considered first, i.e. signed by the current domain owner and with their
+/// right of association verified when required, then the V1 records. Within each version the
+/// records are considered in the order of [`CONTENT_RECORDS`]: `url`, `IPFS`, `ARWV`, `SHDW` and
+/// `CNAME`. Records whose content cannot be served are skipped, see
+/// [`ContentTarget::from_record`]
+pub fn resolve_content(
+    rpc_client: &RpcClient,
+    domain: &str,
+) -> Result<Option<ContentTarget>, SnsError> {
+    let Some(owner) = get_registry_owner(rpc_client, &get_domain_key(domain)?)? else {
+        return Ok(None);
+    };
+    let records = [RecordVersion::V2, RecordVersion::V1]
+        .into_iter()
+        .flat_map(|version| {
+            CONTENT_RECORDS
+                .into_iter()
+                .map(move |record| (record, version))
+        })
+        .map(|(record, version)| {
+            Ok((
+                get_record_key(domain, record.clone(), version)?,
+                record,
+                version,
+            ))
+        })
+        .collect::<Result<Vec<_>, SnsError>>()?;
+    let keys = records.iter().map(|(key, _, _)| *key).collect::<Vec<_>>();
+    let accounts = rpc_client.get_multiple_accounts(&keys)?;
+    // Only SOL records, which do not hold content, are signed in V1
+    let registry_owner = match owner {
+        Owner::Solana(owner) => owner,
+        Owner::Foreign { .. } => Pubkey::default(),
+    };
+    for ((key, record, version), account) in records.into_iter().zip(accounts) {
+        let Some(account) = account else {
+            continue;
+        };
+        let content = match version {
+            RecordVersion::V2 => {
+                match futures::executor::block_on(parse_record_v2(record.clone(), &account.data)) {
+                    Ok(parsed)
+                        if parsed.verify_staleness(&owner).is_ok()
+                            && parsed.verify_roa().is_ok() =>
+                    {
+                        parsed.content
+                    }
+                    _ => continue,
+                }
+            }
+            RecordVersion::V1 => {
+                let data = account
+                    .data
+                    .get(NameRecordHeader::LEN..)
+                    .unwrap_or_default();
+                match deserialize_record(data, record.clone(), &key, &registry_owner) {
+                    Ok(content) => content,
+                    Err(_) => continue,
+                }
+            }
+        };
+        if let Some(target) = ContentTarget::from_record(&record, &content) {
+            return Ok(Some(target));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the `XDomain` account of a domain bridged to another chain, if any
+pub fn get_x_domain(
+    rpc_client: &RpcClient,
+    domain_key: &Pubkey,
+) -> Result<Option<XDomain>, SnsError> {
+    let key = derive_x_domain_key(domain_key);
+    let account = rpc_client
+        .get_account_with_commitment(&key, rpc_client.commitment())?
+        .value;
+    account.map(|a| deserialize_x_domain(&a.data)).transpose()
+}
+
+pub fn get_favourite_domain(
     rpc_client: &RpcClient,
     owner: &Pubkey,
 ) -> Result<Option<Pubkey>, SnsError> {
-    let favourite_domain_state_key = derive_favorite_domain_key(owner);
+    let favourite_domain_state_key = derive_favourite_domain_key(owner);
     let account = rpc_client
         .get_account_with_commitment(&favourite_domain_state_key, rpc_client.commitment())?
         .value;
@@ -219,15 +472,115 @@ pub async fn get_favourite_domain(
     }
 }
 
+/// Returns the primary domain of `owner`, if any.
+///
+/// Unlike [`get_favourite_domain`], this resolves the name of the domain and checks
+/// whether `owner` still owns it, either directly or through the domain NFT.
+pub fn get_primary_domain(
+    rpc_client: &RpcClient,
+    owner: &Pubkey,
+) -> Result<Option<PrimaryDomain>, SnsError> {
+    let mut res = get_multiple_primary_domains(rpc_client, &[*owner])?;
+    Ok(res.pop().flatten())
+}
+
+fn get_multiple_accounts(
+    rpc_client: &RpcClient,
+    keys: &[Pubkey],
+) -> Result<Vec<Option<solana_sdk::account::Account>>, SnsError> {
+    let mut res = Vec::with_capacity(keys.len());
+    for k in keys.chunks(100) {
+        res.extend(rpc_client.get_multiple_accounts(k)?);
+    }
+    Ok(res)
+}
+
+/// Returns the primary domains of `owners`, in the same order.
+///
+/// The accounts are fetched in batches: favourite domain accounts, then name registries,
+/// then reverse lookups and NFT token accounts. An owner whose accounts cannot be decoded has no
+/// primary domain, like one whose accounts do not exist, so that one corrupt account does not
+/// fail the whole batch.
+pub fn get_multiple_primary_domains(
+    rpc_client: &RpcClient,
+    owners: &[Pubkey],
+) -> Result<Vec<Option<PrimaryDomain>>, SnsError> {
+    let favourite_keys = owners
+        .iter()
+        .map(derive_favourite_domain_key)
+        .collect::<Vec<_>>();
+    let domains = get_multiple_accounts(rpc_client, &favourite_keys)?
+        .into_iter()
+        .map(|acc| acc.and_then(|a| FavouriteDomain::parse(&a.data).ok()))
+        .map(|f| f.map(|f| f.name_account))
+        .collect::<Vec<_>>();
+
+    let domain_keys = domains.iter().flatten().copied().collect::<Vec<_>>();
+    let mut registries = get_multiple_accounts(rpc_client, &domain_keys)?.into_iter();
+    let mut found = vec![];
+    for (owner, domain) in owners.iter().zip(&domains) {
+        let Some(domain) = domain else {
+            found.push(None);
+            continue;
+        };
+        let header = registries
+            .next()
+            .flatten()
+            .and_then(|acc| deserialize_name_registry(&acc.data).ok())
+            .map(|(header, _)| header);
+        found.push(header.map(|header| {
+            let accounts = PrimaryDomainAccounts::new(owner, domain, &header);
+            (*domain, header, accounts)
+        }));
+    }
+
+    let mut keys = vec![];
+    for (_, _, accounts) in found.iter().flatten() {
+        keys.push(accounts.reverse);
+        keys.push(accounts.token_account);
+        keys.extend(accounts.parent_reverse);
+    }
+    let mut accounts = get_multiple_accounts(rpc_client, &keys)?.into_iter();
+    let parse_reverse = |acc: Option<solana_sdk::account::Account>| {
+        let (_, data) = deserialize_name_registry(&acc?.data).ok()?;
+        deserialize_reverse(&data).ok()
+    };
+
+    let mut res = Vec::with_capacity(owners.len());
+    for (owner, found) in owners.iter().zip(found) {
+        let Some((domain, header, keys)) = found else {
+            res.push(None);
+            continue;
+        };
+        let reverse = parse_reverse(accounts.next().flatten());
+        let token_account = accounts.next().flatten();
+        let parent_reverse = keys
+            .parent_reverse
+            .map(|_| parse_reverse(accounts.next().flatten()));
+        res.push(build_primary_domain(
+            owner,
+            &domain,
+            &header,
+            reverse,
+            parent_reverse,
+            token_account.as_ref().map(|a| a.data.as_slice()),
+        ));
+    }
+    Ok(res)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::derivation::get_domain_key;
+    use crate::derivation::{get_domain_key, get_reverse_key, NAME_TOKENIZER_ID};
+    use crate::testing::{self, rpc::LocalRpc};
     use crate::utils::test::generate_random_string;
     use dotenv::dotenv;
     use solana_program::pubkey;
+    use solana_sdk::{signature::Keypair, signer::Signer};
 
     #[test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     fn test_reverse() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -237,6 +590,7 @@ mod tests {
     }
 
     #[test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     fn test_subs() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -247,6 +601,7 @@ mod tests {
     }
 
     #[test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     fn test_resolve_owner() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -282,6 +637,20 @@ mod tests {
     }
 
     #[test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
+    fn test_get_domain_keys_with_reverses() {
+        dotenv().ok();
+        let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
+        let owner = pubkey!("HKKp49qGWXd639QsuH7JiLijfVW5UtCVY4s1n2HANwEA");
+        let domains = get_domain_keys_with_reverses(&client, owner).unwrap();
+        assert!(domains.contains(&(
+            pubkey!("Crf8hzfthWGbGbLTVCiqRqV5MVnbpHB1L9KQMd6gsinb"),
+            Some("bonfida".to_string())
+        )));
+    }
+
+    #[test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     fn test_resolve_record() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -297,4 +666,127 @@ mod tests {
         let res = resolve_record(&client, "bonfida", Record::Backpack).unwrap();
         assert!(res.is_none())
     }
+
+    #[test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
+    fn test_get_primary_domain() {
+        dotenv().ok();
+        let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
+        let owners = [pubkey!("HKKp49qGWXd639QsuH7JiLijfVW5UtCVY4s1n2HANwEA")];
+        let res = get_multiple_primary_domains(&client, &owners).unwrap();
+        assert_eq!(res[0].as_ref().unwrap().reverse, "bonfida");
+        assert_eq!(
+            get_primary_domain(&client, &owners[0]).unwrap(),
+            res[0].clone()
+        );
+    }
+
+    #[test]
+    fn test_local_resolve() {
+        let owner = Keypair::new();
+        let holder = Pubkey::new_unique();
+        let target = Pubkey::new_unique();
+        let tokenized = get_domain_key("tokenized").unwrap();
+        let bridged = get_domain_key("bridged").unwrap();
+        let (x_domain_key, x_domain) = testing::x_domain(&bridged, 4, [1; 32]);
+        let central_state = name_tokenizer::state::CentralState::find_key(&NAME_TOKENIZER_ID).0;
+        let client = LocalRpc::new([
+            testing::domain("bonfida", &owner.pubkey(), &[]).unwrap(),
+            testing::domain("dex.bonfida", &owner.pubkey(), &[]).unwrap(),
+            testing::domain("signed", &owner.pubkey(), &[]).unwrap(),
+            testing::domain("tokenized", &central_state, &[]).unwrap(),
+            testing::domain("bridged", &x_domain_key, &[]).unwrap(),
+            (x_domain_key, x_domain),
+            testing::reverse("bonfida").unwrap(),
+            testing::reverse("dex.bonfida").unwrap(),
+            testing::reverse("signed").unwrap(),
+            testing::record_v1("bonfida", Record::Url, "https://sns.id", &owner.pubkey()).unwrap(),
+            testing::sol_record_v1("signed", &target, &owner).unwrap(),
+            testing::nft_record(&tokenized, &owner.pubkey()),
+            testing::domain_mint(&tokenized, 1),
+            testing::token_account(&get_domain_mint(&tokenized), &holder, 1),
+            testing::favourite_domain(&owner.pubkey(), &get_domain_key("dex.bonfida").unwrap()),
+        ])
+        .into_blocking_client();
+        let bonfida = get_domain_key("bonfida").unwrap();
+
+        assert_eq!(
+            resolve_owner(&client, "bonfida").unwrap(),
+            Some(owner.pubkey())
+        );
+        assert_eq!(resolve_owner(&client, "signed").unwrap(), Some(target));
+        assert_eq!(resolve_owner(&client, "tokenized").unwrap(), Some(holder));
+        assert_eq!(resolve_owner(&client, "unknown").unwrap(), None);
+        assert_eq!(
+            resolve_domain_owner(&client, "bridged").unwrap(),
+            Some(Owner::Foreign {
+                chain: 4,
+                address: vec![1; 32]
+            })
+        );
+        assert_eq!(
+            get_registry_owner(&client, &bonfida).unwrap(),
+            Some(Owner::Solana(owner.pubkey()))
+        );
+        assert!(get_x_domain(&client, &bridged).unwrap().is_some());
+
+        let (_, data) = resolve_record(&client, "bonfida", Record::Url)
+            .unwrap()
+            .unwrap();
+        let key = get_record_key("bonfida", Record::Url, RecordVersion::V1).unwrap();
+        assert_eq!(
+            deserialize_record(&data, Record::Url, &key, &owner.pubkey()).unwrap(),
+            "https://sns.id"
+        );
+        assert_eq!(
+            resolve_content(&client, "bonfida").unwrap(),
+            Some(ContentTarget::Http("https://sns.id/".to_owned()))
+        );
+
+        assert_eq!(
+            resolve_reverse_batch(&client, &[bonfida, Pubkey::new_unique()]).unwrap(),
+            vec![Some("bonfida".to_owned()), None]
+        );
+        assert_eq!(get_subdomains(&client, bonfida).unwrap(), vec!["dex"]);
+        let mut domains = get_domain_keys_with_reverses(&client, owner.pubkey()).unwrap();
+        domains.sort_by(|a, b| a.1.cmp(&b.1));
+        assert_eq!(
+            domains,
+            vec![
+                (bonfida, Some("bonfida".to_owned())),
+                (get_domain_key("signed").unwrap(), Some("signed".to_owned()))
+            ]
+        );
+        assert_eq!(get_all_registered_domains(&client).unwrap().len(), 4);
+
+        let primary = get_primary_domain(&client, &owner.pubkey())
+            .unwrap()
+            .unwrap();
+        assert_eq!(primary.reverse, "dex.bonfida");
+        assert!(!primary.stale);
+    }
+
+    #[test]
+    fn test_local_corrupt_primary_domains() {
+        let owners = [(); 3].map(|_| Pubkey::new_unique());
+        let domain = |name| get_domain_key(name).unwrap();
+        let corrupt = |key| (key, testing::account(&spl_name_service::ID, vec![1, 2, 3]));
+        let client = LocalRpc::new([
+            testing::domain("bonfida", &owners[0], &[]).unwrap(),
+            testing::reverse("bonfida").unwrap(),
+            testing::favourite_domain(&owners[0], &domain("bonfida")),
+            // The name registry is corrupt
+            corrupt(domain("corrupt")),
+            testing::favourite_domain(&owners[1], &domain("corrupt")),
+            // The reverse is corrupt
+            testing::domain("reverse", &owners[2], &[]).unwrap(),
+            corrupt(get_reverse_key("reverse").unwrap()),
+            testing::favourite_domain(&owners[2], &domain("reverse")),
+        ])
+        .into_blocking_client();
+
+        let res = get_multiple_primary_domains(&client, &owners).unwrap();
+        assert_eq!(res[0].as_ref().unwrap().reverse, "bonfida");
+        assert_eq!(res[1..], [None, None]);
+    }
 }
@@ -0,0 +1,174 @@
+//! Domains bridged to other chains through the SNS warp program, which relies on Wormhole.
+//!
+//! A bridged domain is owned by its `XDomain` account. This account is derived from the domain
+//! key, is owned by the warp program and holds the chain and the address of the owner on that
+//! chain. Addresses are stored in the Wormhole format, i.e. left-padded with zeros to 32 bytes.
+use {
+    bech32::ToBase32,
+    borsh::BorshDeserialize,
+    sns_warp_common::{constants::EMITTER_KEY, wormhole::ChainIds},
+    solana_program::pubkey::Pubkey,
+};
+
+pub use sns_warp_common::state::x_domain::XDomain;
+
+use crate::error::SnsError;
+
+/// The address of the `XDomain` account of a domain
+pub fn derive_x_domain_key(domain_key: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[domain_key.as_ref()], &EMITTER_KEY).0
+}
+
+pub fn deserialize_x_domain(data: &[u8]) -> Result<XDomain, SnsError> {
+    Ok(XDomain::deserialize(&mut &data[..])?)
+}
+
+/// The name of a Wormhole chain supported by the warp program
+pub fn chain_name(chain: u16) -> Option<&'static str> {
+    match chain {
+        c if c == ChainIds::Solana as u16 => Some("solana"),
+        c if c == ChainIds::BNB as u16 => Some("bsc"),
+        c if c == ChainIds::Injective as u16 => Some("injective"),
+        c if c == ChainIds::BASE as u16 => Some("base"),
+        _ => None,
+    }
+}
+
+/// The Wormhole id of a chain supported by the warp program, from its name
+pub fn chain_id(name: &str) -> Option<u16> {
+    match name.to_lowercase().as_str() {
+        "solana" => Some(ChainIds::Solana as u16),
+        "bsc" | "bnb" => Some(ChainIds::BNB as u16),
+        "injective" => Some(ChainIds::Injective as u16),
+        "base" => Some(ChainIds::BASE as u16),
+        _ => None,
+    }
+}
+
+/// Formats an address in the Wormhole format as it is usually written on `chain`: base58 on
+/// Solana, `0x` prefixed hex on EVM chains and bech32 on Injective. The addresses of unknown
+/// chains are hex encoded
+pub fn format_chain_address(chain: u16, address: &[u8; 32]) -> String {
+    match chain_name(chain) {
+        Some("solana") => Pubkey::new_from_array(*address).to_string(),
+        Some("bsc" | "base") => format!("0x{}", hex::encode(&address[12..])),
+        Some("injective") => {
+            bech32::encode("inj", (&address[12..]).to_base32(), bech32::Variant::Bech32)
+                .unwrap_or_else(|_| hex::encode(address))
+        }
+        _ => hex::encode(address),
+    }
+}
+
+/// The owner of a domain, which is on another chain when the domain is bridged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Owner {
+    Solana(Pubkey),
+    /// The owner of a bridged domain, even when `chain` is Solana. `chain` is the Wormhole id of
+    /// the chain and `address` is in the Wormhole format (32 bytes)
+    Foreign {
+        chain: u16,
+        address: Vec<u8>,
+    },
+}
+
+impl Owner {
+    /// The owner of a domain whose name registry is owned by `registry_owner`, `x_domain` being
+    /// the `XDomain` account of the domain if it exists
+    pub fn new(domain_key: &Pubkey, registry_owner: Pubkey, x_domain: Option<&XDomain>) -> Self {
+        match x_domain {
+            Some(x_domain) if is_bridged(domain_key, &registry_owner) => Self::Foreign {
+                chain: x_domain.owner_chain,
+                address: x_domain.owner_address.to_vec(),
+            },
+            _ => Self::Solana(registry_owner),
+        }
+    }
+
+    /// The name of the chain of the owner, or its Wormhole id when it is unknown
+    pub fn chain(&self) -> String {
+        match self {
+            Self::Solana(_) => "solana".to_owned(),
+            Self::Foreign { chain, .. } => {
+                chain_name(*chain).map_or_else(|| chain.to_string(), str::to_owned)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Owner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Solana(key) => write!(f, "{key}"),
+            Self::Foreign { chain, address } => match <&[u8; 32]>::try_from(address.as_slice()) {
+                Ok(address) => f.write_str(&format_chain_address(*chain, address)),
+                Err(_) => f.write_str(&hex::encode(address)),
+            },
+        }
+    }
+}
+
+/// Whether a domain whose name registry is owned by `registry_owner` is bridged
+pub fn is_bridged(domain_key: &Pubkey, registry_owner: &Pubkey) -> bool {
+    *registry_owner == EMITTER_KEY || *registry_owner == derive_x_domain_key(domain_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshSerialize;
+
+    #[test]
+    fn test_x_domain() {
+        let domain_key = Pubkey::new_unique();
+        let mut address = [0; 32];
+        address[12..].copy_from_slice(&[0xab; 20]);
+        let mut x_domain = XDomain {
+            nonce: 0,
+            owner_chain: ChainIds::BASE as u16,
+            owner_address: address,
+            name_account: domain_key,
+        };
+        let key = x_domain.derive_key(&EMITTER_KEY);
+        assert_eq!(derive_x_domain_key(&domain_key), key);
+
+        let parsed = deserialize_x_domain(&x_domain.try_to_vec().unwrap()).unwrap();
+        assert_eq!(parsed.name_account, domain_key);
+        assert_eq!(
+            format_chain_address(parsed.owner_chain, &parsed.owner_address),
+            format!("0x{}", "ab".repeat(20))
+        );
+        assert_eq!(chain_id("BASE"), Some(parsed.owner_chain));
+        assert!(deserialize_x_domain(&[0; 10]).is_err());
+    }
+
+    #[test]
+    fn test_owner() {
+        let domain_key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let x_domain = XDomain {
+            nonce: 0,
+            owner_chain: ChainIds::BNB as u16,
+            owner_address: [1; 32],
+            name_account: domain_key,
+        };
+        assert_eq!(
+            Owner::new(&domain_key, owner, Some(&x_domain)),
+            Owner::Solana(owner)
+        );
+        let foreign = Owner::new(
+            &domain_key,
+            derive_x_domain_key(&domain_key),
+            Some(&x_domain),
+        );
+        assert_eq!(
+            foreign,
+            Owner::Foreign {
+                chain: ChainIds::BNB as u16,
+                address: vec![1; 32]
+            }
+        );
+        assert_eq!(foreign.chain(), "bsc");
+        assert_eq!(foreign.to_string(), format!("0x{}", "01".repeat(20)));
+    }
+}
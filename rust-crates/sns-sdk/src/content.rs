@@ -0,0 +1,185 @@
+use {
+    crate::record::{schema::validate_content, Record},
+    solana_program::pubkey::Pubkey,
+    std::str::FromStr,
+};
+
+/// The records holding the content served by a domain, by decreasing priority
+pub const CONTENT_RECORDS: [Record; 5] = [
+    Record::Url,
+    Record::Ipfs,
+    Record::Arwv,
+    Record::Shdw,
+    Record::CNAME,
+];
+
+/// The content served by a domain, as returned by `resolve_content`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ContentTarget {
+    /// A CID, optionally followed by a path
+    Ipfs(String),
+    /// An Arweave transaction id
+    Arweave(String),
+    /// An absolute HTTP(S) URL
+    Http(String),
+    /// A Shadow Drive storage account
+    Shadow(Pubkey),
+}
+
+/// The gateways through which decentralized content is served over HTTP, see
+/// [`ContentTarget::gateway_url_with`]
+#[derive(Clone, Debug)]
+pub struct Gateways {
+    pub ipfs: String,
+    pub arweave: String,
+    pub shadow: String,
+}
+
+impl Default for Gateways {
+    fn default() -> Self {
+        Self {
+            ipfs: "https://ipfs.io/ipfs".to_owned(),
+            arweave: "https://arweave.net".to_owned(),
+            shadow: "https://shdw-drive.genesysgo.net".to_owned(),
+        }
+    }
+}
+
+impl ContentTarget {
+    /// Normalizes the content of one of the [`CONTENT_RECORDS`], `None` when it cannot be served:
+    /// - `url` records pointing to `ipfs://` or `ar://` are IPFS and Arweave content, other
+    ///   schemes than HTTP(S) are ignored
+    /// - `IPFS` and `ARWV` records must hold a valid CID or transaction id, with or without their
+    ///   `ipfs://` and `ar://` prefixes
+    /// - `SHDW` records hold the address of a storage account
+    /// - `CNAME` records are served over HTTPS
+    pub fn from_record(record: &Record, content: &str) -> Option<Self> {
+        match record {
+            Record::Url => {
+                if let Some(cid) = content.strip_prefix("ipfs://") {
+                    return Self::from_record(&Record::Ipfs, cid);
+                }
+                if let Some(id) = content.strip_prefix("ar://") {
+                    return Self::from_record(&Record::Arwv, id);
+                }
+                let url = url::Url::parse(content).ok()?;
+                (matches!(url.scheme(), "http" | "https") && url.has_host())
+                    .then(|| Self::Http(url.into()))
+            }
+            Record::Ipfs => {
+                let path = content.strip_prefix("ipfs://").unwrap_or(content);
+                let cid = path.split('/').next()?;
+                validate_content(record, cid).ok()?;
+                Some(Self::Ipfs(path.to_owned()))
+            }
+            Record::Arwv => {
+                let id = content.strip_prefix("ar://").unwrap_or(content);
+                validate_content(record, id).ok()?;
+                Some(Self::Arweave(id.to_owned()))
+            }
+            Record::Shdw => Pubkey::from_str(content).ok().map(Self::Shadow),
+            Record::CNAME => {
+                validate_content(record, content).ok()?;
+                let host = content.strip_suffix('.').unwrap_or(content);
+                Some(Self::Http(format!("https://{host}/")))
+            }
+            _ => None,
+        }
+    }
+
+    /// The URL of the content through the default [`Gateways`]
+    pub fn gateway_url(&self) -> String {
+        self.gateway_url_with(&Gateways::default())
+    }
+
+    /// The URL of the content through `gateways`, HTTP content being served as is
+    pub fn gateway_url_with(&self, gateways: &Gateways) -> String {
+        match self {
+            Self::Ipfs(path) => format!("{}/{path}", gateways.ipfs.trim_end_matches('/')),
+            Self::Arweave(id) => format!("{}/{id}", gateways.arweave.trim_end_matches('/')),
+            Self::Shadow(account) => format!("{}/{account}", gateways.shadow.trim_end_matches('/')),
+            Self::Http(url) => url.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CID: &str = "QmbWqxBEKC3P8tqsKc98xmWNzrzDtRLMiMPL8wBuTGsMnR";
+    const ARWEAVE_ID: &str = "bNbA3TEQVL60xlgCcqdz4ZPHFZ711cZ3hmkpGttDt_U";
+
+    #[test]
+    fn test_from_record() {
+        let ipfs = ContentTarget::Ipfs(CID.to_owned());
+        let arweave = ContentTarget::Arweave(ARWEAVE_ID.to_owned());
+        let cases = [
+            (
+                Record::Url,
+                "https://sns.id/",
+                Some(ContentTarget::Http("https://sns.id/".to_owned())),
+            ),
+            (Record::Url, &format!("ipfs://{CID}"), Some(ipfs.clone())),
+            (
+                Record::Url,
+                &format!("ar://{ARWEAVE_ID}"),
+                Some(arweave.clone()),
+            ),
+            (Record::Url, "ftp://sns.id", None),
+            (Record::Ipfs, CID, Some(ipfs.clone())),
+            (
+                Record::Ipfs,
+                &format!("ipfs://{CID}/index.html"),
+                Some(ContentTarget::Ipfs(format!("{CID}/index.html"))),
+            ),
+            (Record::Ipfs, "ipfs://", None),
+            (Record::Arwv, &format!("ar://{ARWEAVE_ID}"), Some(arweave)),
+            (Record::Arwv, "arweave", None),
+            (Record::Shdw, "invalid", None),
+            (
+                Record::CNAME,
+                "sns.id.",
+                Some(ContentTarget::Http("https://sns.id/".to_owned())),
+            ),
+            (Record::TXT, "sns.id", None),
+        ];
+        for (record, content, expected) in cases {
+            assert_eq!(
+                ContentTarget::from_record(&record, content),
+                expected,
+                "{record} {content}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_gateway_url() {
+        let ipfs = ContentTarget::Ipfs(format!("{CID}/index.html"));
+        assert_eq!(
+            ipfs.gateway_url(),
+            format!("https://ipfs.io/ipfs/{CID}/index.html")
+        );
+        let gateways = Gateways {
+            ipfs: "https://dweb.link/ipfs/".to_owned(),
+            arweave: "https://ar-io.net".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(
+            ipfs.gateway_url_with(&gateways),
+            format!("https://dweb.link/ipfs/{CID}/index.html")
+        );
+        let arweave = ContentTarget::Arweave(ARWEAVE_ID.to_owned());
+        assert_eq!(
+            arweave.gateway_url_with(&gateways),
+            format!("https://ar-io.net/{ARWEAVE_ID}")
+        );
+        let shadow = ContentTarget::Shadow(Pubkey::default());
+        assert_eq!(
+            shadow.gateway_url(),
+            format!("https://shdw-drive.genesysgo.net/{}", Pubkey::default())
+        );
+        let http = ContentTarget::Http("https://sns.id/".to_owned());
+        assert_eq!(http.gateway_url_with(&gateways), "https://sns.id/");
+    }
+}
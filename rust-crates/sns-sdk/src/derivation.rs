@@ -111,7 +111,9 @@ pub fn get_domain_key_with_parent(domain: &str) -> Result<DomainKeyWithParent, S
         //     let key = derive(&record, &sub_key);
         //     Ok(key)
         // }
-        _ => Err(SnsError::InvalidDomain),
+        _ => Err(SnsError::InvalidDomain {
+            domain: domain.to_owned(),
+        }),
     }
 }
 
@@ -142,7 +144,9 @@ pub fn get_reverse_key(domain: &str) -> Result<Pubkey, SnsError> {
             );
             Ok(key)
         }
-        _ => Err(SnsError::InvalidDomain),
+        _ => Err(SnsError::InvalidDomain {
+            domain: domain.to_owned(),
+        }),
     }
 }
 
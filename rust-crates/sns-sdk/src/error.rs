@@ -3,45 +3,209 @@ use std::array::TryFromSliceError;
 use {
     derive_more::{Display, Error},
     ed25519_dalek::ed25519,
-    solana_client::client_error::ClientError,
-    solana_program::program_error::ProgramError,
+    solana_client::{
+        client_error::{reqwest::StatusCode, ClientError, ClientErrorKind},
+        rpc_custom_error::{
+            JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+            JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET,
+            JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+            JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+        },
+        rpc_request::RpcError,
+    },
+    solana_program::{program_error::ProgramError, pubkey::Pubkey},
+    solana_sdk::{signature::Signature, signer::SignerError, transaction::TransactionError},
     std::string::FromUtf8Error,
 };
 
+use crate::record::{schema::ContentError, Record, RecordVersion};
+
+/// A coarse classification of [`SnsError`], to decide how an error should be handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// An account that was expected to exist does not
+    NotFound,
+    /// The input provided by the caller is malformed (domain name, record content, ...)
+    InvalidInput,
+    /// On-chain data could not be decoded
+    InvalidData,
+    /// A record failed its staleness or right of association verification
+    Verification,
+    /// The RPC node could not be reached or returned an error
+    Transport,
+    /// The operation is not supported
+    Unsupported,
+    /// A transaction failed on chain or expired before landing
+    Transaction,
+}
+
 #[derive(Debug, Display, Error)]
+#[non_exhaustive]
 pub enum SnsError {
-    #[display(fmt = "")]
-    InvalidDomain,
-    SolanaClient(ClientError),
+    #[display(fmt = "Invalid domain {:?}", domain)]
+    InvalidDomain { domain: String },
+    #[display(fmt = "Account {} does not exist", key)]
+    AccountNotFound { key: Pubkey },
+    #[display(fmt = "No NFT record exists for the mint {}", mint)]
+    NftRecordDoesNotExist { mint: Pubkey },
+    #[display(fmt = "RPC error: {}", _0)]
+    SolanaClient(Box<ClientError>),
+    #[display(fmt = "Failed to unpack account: {}", _0)]
     SolanaProgramError(ProgramError),
+    #[display(
+        fmt = "Account data too small: expected at least {} bytes, got {}",
+        expected,
+        len
+    )]
+    AccountTooSmall { expected: usize, len: usize },
+    #[display(fmt = "Invalid reverse lookup data")]
     InvalidReverse,
+    #[display(fmt = "Invalid ed25519 key or signature: {}", _0)]
     ED25519(ed25519::Error),
+    #[display(fmt = "Failed to deserialize account: {}", _0)]
     BorshError(std::io::Error),
-    UnsupportedMint,
-    SerializationError,
-    InvalidPubkey,
+    #[display(fmt = "The mint {} is not supported for registrations", mint)]
+    UnsupportedMint { mint: Pubkey },
+    #[display(fmt = "Invalid public key {:?}", value)]
+    InvalidPubkey { value: String },
+    #[display(fmt = "Invalid UTF-8 data: {}", _0)]
     Utf8(FromUtf8Error),
+    #[display(fmt = "Invalid bech32 data: {}", _0)]
     Bech32(bech32::Error),
-    InvalidRecordData,
+    #[display(fmt = "Invalid {} record data", record)]
+    InvalidRecordData { record: Record },
+    #[display(fmt = "Invalid {} record content {:?}: {}", record, content, reason)]
+    InvalidRecordContent {
+        record: Record,
+        content: String,
+        reason: &'static str,
+    },
+    #[display(fmt = "Invalid {} record content {:?}: {}", record, content, error)]
+    InvalidRecordSchema {
+        record: Record,
+        content: String,
+        error: ContentError,
+    },
+    #[display(fmt = "Invalid hex data: {}", _0)]
     Hex(hex::FromHexError),
-    UnrecognizedRecord,
-    Punycode,
-    InvalidEvmAddress,
-    InvalidInjectiveAddress,
-    InvalidIpv4,
-    InvalidIpv6,
-    SolRecordNotSupported,
-    NftRecordDoesNotExist,
+    #[display(fmt = "Unrecognized record {:?}", record)]
+    UnrecognizedRecord { record: String },
+    #[display(fmt = "The custom record {:?} has the name of a known record", record)]
+    ReservedRecordName { record: String },
+    #[display(fmt = "{} records are not supported in records {:?}", record, version)]
+    UnsupportedRecordVersion {
+        record: Record,
+        version: RecordVersion,
+    },
+    #[display(fmt = "Invalid data alignment")]
     Casting,
+    #[display(fmt = "Invalid data length: {}", _0)]
     TryFromSlice(std::array::TryFromSliceError),
+    #[display(fmt = "Records program error: {}", _0)]
     RecordsError(sns_records::error::SnsRecordsError),
-    StaleRecord,
-    UnverifiedRecord,
+    #[display(fmt = "The {} record is stale", record)]
+    StaleRecord { record: Record },
+    #[display(fmt = "The {} record right of association is not verified", record)]
+    UnverifiedRecord { record: Record },
+    #[display(fmt = "Invalid transaction signature {:?}", signature)]
+    InvalidSignature { signature: String },
+    #[display(fmt = "Invalid transaction {}", signature)]
+    InvalidTransaction { signature: Signature },
+    #[display(fmt = "Failed to sign the transaction: {}", _0)]
+    Signer(SignerError),
+    #[display(
+        fmt = "The instructions for {} do not fit in a transaction ({} bytes)",
+        domain,
+        size
+    )]
+    TransactionTooLarge { domain: String, size: usize },
+    #[display(fmt = "Transaction {} failed: {}", signature, error)]
+    TransactionFailed {
+        signature: Signature,
+        error: TransactionError,
+    },
+    #[display(
+        fmt = "The blockhash of transaction {} expired before it landed",
+        signature
+    )]
+    BlockhashExpired { signature: Signature },
+}
+
+impl SnsError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::AccountNotFound { .. } | Self::NftRecordDoesNotExist { .. } => {
+                ErrorKind::NotFound
+            }
+            Self::InvalidDomain { .. }
+            | Self::InvalidPubkey { .. }
+            | Self::InvalidRecordContent { .. }
+            | Self::InvalidRecordSchema { .. }
+            | Self::UnrecognizedRecord { .. }
+            | Self::ReservedRecordName { .. }
+            | Self::Signer(_)
+            | Self::TransactionTooLarge { .. } => ErrorKind::InvalidInput,
+            Self::SolanaProgramError(_)
+            | Self::AccountTooSmall { .. }
+            | Self::InvalidReverse
+            | Self::BorshError(_)
+            | Self::Utf8(_)
+            | Self::Bech32(_)
+            | Self::InvalidRecordData { .. }
+            | Self::Hex(_)
+            | Self::Casting
+            | Self::TryFromSlice(_)
+            | Self::RecordsError(_)
+            | Self::InvalidSignature { .. }
+            | Self::InvalidTransaction { .. } => ErrorKind::InvalidData,
+            Self::ED25519(_) | Self::StaleRecord { .. } | Self::UnverifiedRecord { .. } => {
+                ErrorKind::Verification
+            }
+            Self::SolanaClient(_) => ErrorKind::Transport,
+            Self::UnsupportedMint { .. } | Self::UnsupportedRecordVersion { .. } => {
+                ErrorKind::Unsupported
+            }
+            Self::TransactionFailed { .. } | Self::BlockhashExpired { .. } => {
+                ErrorKind::Transaction
+            }
+        }
+    }
+
+    /// Returns `true` for transient transport errors and expired blockhashes, for which retrying may succeed
+    pub fn is_retryable(&self) -> bool {
+        let err = match self {
+            Self::SolanaClient(err) => err,
+            Self::BlockhashExpired { .. } => return true,
+            _ => return false,
+        };
+        match err.kind() {
+            ClientErrorKind::Io(_) => true,
+            ClientErrorKind::Reqwest(err) => match err.status() {
+                Some(status) => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+                None => true,
+            },
+            ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => matches!(
+                *code,
+                JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE
+                    | JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+                    | JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET
+                    | JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED
+            ),
+            _ => false,
+        }
+    }
 }
 
 impl From<ClientError> for SnsError {
     fn from(e: ClientError) -> Self {
-        Self::SolanaClient(e)
+        Self::SolanaClient(Box::new(e))
+    }
+}
+
+impl From<SignerError> for SnsError {
+    fn from(e: SignerError) -> Self {
+        Self::Signer(e)
     }
 }
 
@@ -98,3 +262,41 @@ impl From<sns_records::error::SnsRecordsError> for SnsError {
         Self::RecordsError(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::derivation::get_domain_key;
+
+    #[test]
+    fn test_display() {
+        let err = get_domain_key("a.b.c").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid domain \"a.b.c\"");
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(!err.is_retryable());
+
+        let err = SnsError::StaleRecord {
+            record: Record::Url,
+        };
+        assert_eq!(err.to_string(), "The url record is stale");
+        assert_eq!(err.kind(), ErrorKind::Verification);
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        let err = SnsError::from(ClientError::from(io));
+        assert_eq!(err.kind(), ErrorKind::Transport);
+        assert!(err.is_retryable());
+
+        let err = SnsError::from(ClientError::from(RpcError::RpcResponseError {
+            code: JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+            message: "Node is behind".to_owned(),
+            data: solana_client::rpc_request::RpcResponseErrorData::Empty,
+        }));
+        assert!(err.is_retryable());
+
+        let err = SnsError::from(ClientError::from(RpcError::ForUser("invalid".to_owned())));
+        assert!(!err.is_retryable());
+    }
+}
@@ -2,10 +2,20 @@ use std::io::ErrorKind;
 
 use bonfida_utils::InstructionsAccount;
 use borsh::BorshDeserialize;
-use solana_program::pubkey::Pubkey;
+use name_tokenizer::state::CentralState;
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
 use solana_sdk::instruction::Instruction;
+use spl_associated_token_account::get_associated_token_address;
+use spl_name_service::state::NameRecordHeader;
 
-use crate::NAME_OFFERS_PROGRAM_ID;
+use crate::{
+    derivation::{
+        derive_reverse, get_domain_key_with_parent, get_domain_mint, NAME_TOKENIZER_ID,
+        ROOT_DOMAIN_ACCOUNT,
+    },
+    error::SnsError,
+    NAME_OFFERS_PROGRAM_ID,
+};
 
 pub fn derive_favourite_domain_key(owner: &Pubkey) -> Pubkey {
     Pubkey::find_program_address(
@@ -15,13 +25,23 @@ pub fn derive_favourite_domain_key(owner: &Pubkey) -> Pubkey {
     .0
 }
 
-#[derive(BorshDeserialize)]
+/// The account tags of the name offers program
+#[derive(BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Tag {
-    _A,
-    _B,
-    _C,
-    _D,
-    FavouriteDomain = 4,
+    Uninitialized,
+    ActiveOffer,
+    CancelledOffer,
+    AcceptedOffer,
+    FavouriteDomain,
+}
+
+/// The instructions of the name offers program related to favourite domains.
+///
+/// The program has no instruction removing a favourite domain, it can only be replaced
+#[repr(u8)]
+pub enum ProgramInstruction {
+    /// Sets or replaces the favourite domain of a wallet
+    RegisterFavourite = 6,
 }
 
 #[derive(BorshDeserialize)]
@@ -41,26 +61,91 @@ impl FavouriteDomain {
     }
 }
 
+/// The primary (favourite) domain of a wallet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrimaryDomain {
+    /// The domain or subdomain name account
+    pub domain: Pubkey,
+    /// The human readable name, without the `.sol` suffix (e.g `bonfida` or `dex.bonfida`)
+    pub reverse: String,
+    /// `true` when the wallet no longer owns the domain, either directly or through its NFT
+    pub stale: bool,
+}
+
+/// The accounts required to resolve the name and the ownership of a primary domain
+pub struct PrimaryDomainAccounts {
+    pub reverse: Pubkey,
+    /// Only set for subdomains
+    pub parent_reverse: Option<Pubkey>,
+    /// The associated token account of the owner for the domain NFT
+    pub token_account: Pubkey,
+}
+
+impl PrimaryDomainAccounts {
+    pub fn new(owner: &Pubkey, domain: &Pubkey, header: &NameRecordHeader) -> Self {
+        let is_sub = header.parent_name != ROOT_DOMAIN_ACCOUNT;
+        Self {
+            reverse: derive_reverse(domain, is_sub.then_some(&header.parent_name)),
+            parent_reverse: is_sub.then(|| derive_reverse(&header.parent_name, None)),
+            token_account: get_associated_token_address(owner, &get_domain_mint(domain)),
+        }
+    }
+}
+
+/// Builds the primary domain of `owner` from the fetched accounts.
+///
+/// Returns `None` when the reverse lookup of the domain (or of its parent) does not exist.
+/// A tokenized domain is considered owned when `token_account` holds its NFT.
+pub fn build_primary_domain(
+    owner: &Pubkey,
+    domain: &Pubkey,
+    header: &NameRecordHeader,
+    reverse: Option<String>,
+    parent_reverse: Option<Option<String>>,
+    token_account: Option<&[u8]>,
+) -> Option<PrimaryDomain> {
+    let mut reverse = reverse?;
+    if let Some(parent_reverse) = parent_reverse {
+        reverse = format!(
+            "{}.{}",
+            reverse.strip_prefix('\0').unwrap_or(&reverse),
+            parent_reverse?
+        );
+    }
+    let tokenized_owner = header.owner == CentralState::find_key(&NAME_TOKENIZER_ID).0
+        && token_account
+            .and_then(|data| spl_token::state::Account::unpack(data).ok())
+            .is_some_and(|acc| acc.amount == 1 && acc.owner == *owner);
+    Some(PrimaryDomain {
+        domain: *domain,
+        reverse,
+        stale: header.owner != *owner && !tokenized_owner,
+    })
+}
+
 pub mod register_favourite {
     use bonfida_utils::{BorshSize, InstructionsAccount};
     use borsh::{BorshDeserialize, BorshSerialize};
     use solana_sdk::pubkey::Pubkey;
 
     #[derive(InstructionsAccount)]
-    /// The required accounts for the `create` instruction
+    /// The required accounts for the `register_favourite` instruction
     pub struct Accounts<'a, T> {
-        /// The name account
-        #[cons(writable)]
+        /// The domain or subdomain name account
         pub name: &'a T,
+        /// The favourite domain account of the owner
         #[cons(writable)]
         pub favourite_domain: &'a T,
+        /// The owner of the name account
         #[cons(writable, signer)]
         pub owner: &'a T,
+        /// The system program account
         pub system_program: &'a T,
+        /// The parent domain account, required when `name` is a subdomain
+        pub parent: Option<&'a T>,
     }
 
     #[derive(BorshDeserialize, BorshSerialize, BorshSize, Clone, Copy)]
-    #[cfg_attr(feature = "instruction_params_casting", derive(Zeroable, Pod))]
     #[repr(C)]
     pub struct Params {}
 }
@@ -70,5 +155,178 @@ pub fn get_register_favourite_instruction(
     accounts: register_favourite::Accounts<Pubkey>,
     params: register_favourite::Params,
 ) -> Instruction {
-    accounts.get_instruction(program_id, 6, params)
+    accounts.get_instruction(
+        program_id,
+        ProgramInstruction::RegisterFavourite as u8,
+        params,
+    )
+}
+
+/// Builds the instruction setting `domain` (e.g `bonfida` or `dex.bonfida`) as the favourite domain of `owner`.
+///
+/// The current favourite domain of `owner`, if any, is replaced. The name offers program
+/// does not support removing a favourite domain.
+pub fn get_set_favourite_instruction(
+    owner: &Pubkey,
+    domain: &str,
+) -> Result<Instruction, SnsError> {
+    let domain = get_domain_key_with_parent(domain)?;
+    let parent = (domain.parent != ROOT_DOMAIN_ACCOUNT).then_some(&domain.parent);
+    Ok(get_register_favourite_instruction(
+        NAME_OFFERS_PROGRAM_ID,
+        register_favourite::Accounts {
+            name: &domain.key,
+            favourite_domain: &derive_favourite_domain_key(owner),
+            owner,
+            system_program: &solana_program::system_program::ID,
+            parent,
+        },
+        register_favourite::Params {},
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::derivation::{derive, get_domain_key};
+    use solana_program::program_option::COption;
+    use solana_sdk::{signature::Keypair, signer::Signer};
+
+    fn header(parent_name: Pubkey, owner: Pubkey) -> NameRecordHeader {
+        NameRecordHeader {
+            parent_name,
+            owner,
+            class: Pubkey::default(),
+        }
+    }
+
+    fn token_account(owner: Pubkey, mint: Pubkey, amount: u64) -> Vec<u8> {
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            mint,
+            owner,
+            amount,
+            delegate: COption::None,
+            state: spl_token::state::AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        }
+        .pack_into_slice(&mut data);
+        data
+    }
+
+    #[test]
+    fn test_build_primary_domain() {
+        let owner = Keypair::new().pubkey();
+        let domain = get_domain_key("bonfida").unwrap();
+
+        let res = build_primary_domain(
+            &owner,
+            &domain,
+            &header(ROOT_DOMAIN_ACCOUNT, owner),
+            Some("bonfida".to_owned()),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(res.reverse, "bonfida");
+        assert!(!res.stale);
+
+        let res = build_primary_domain(
+            &owner,
+            &domain,
+            &header(ROOT_DOMAIN_ACCOUNT, Keypair::new().pubkey()),
+            Some("bonfida".to_owned()),
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(res.stale);
+
+        assert!(build_primary_domain(
+            &owner,
+            &domain,
+            &header(ROOT_DOMAIN_ACCOUNT, owner),
+            None,
+            None,
+            None,
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_build_primary_subdomain() {
+        let owner = Keypair::new().pubkey();
+        let parent = get_domain_key("bonfida").unwrap();
+        let sub = derive("\0dex", &parent, None);
+        let accounts = PrimaryDomainAccounts::new(&owner, &sub, &header(parent, owner));
+        assert_eq!(accounts.reverse, derive_reverse(&sub, Some(&parent)));
+        assert_eq!(accounts.parent_reverse, Some(derive_reverse(&parent, None)));
+
+        let res = build_primary_domain(
+            &owner,
+            &sub,
+            &header(parent, owner),
+            Some("\0dex".to_owned()),
+            Some(Some("bonfida".to_owned())),
+            None,
+        )
+        .unwrap();
+        assert_eq!(res.reverse, "dex.bonfida");
+    }
+
+    #[test]
+    fn test_build_primary_domain_tokenized() {
+        let owner = Keypair::new().pubkey();
+        let domain = get_domain_key("bonfida").unwrap();
+        let central_state = CentralState::find_key(&NAME_TOKENIZER_ID).0;
+        let mint = get_domain_mint(&domain);
+
+        let build = |amount| {
+            let data = token_account(owner, mint, amount);
+            build_primary_domain(
+                &owner,
+                &domain,
+                &header(ROOT_DOMAIN_ACCOUNT, central_state),
+                Some("bonfida".to_owned()),
+                None,
+                Some(&data),
+            )
+            .unwrap()
+        };
+        assert!(!build(1).stale);
+        assert!(build(0).stale);
+    }
+
+    #[test]
+    fn test_set_favourite_instruction() {
+        let owner = Keypair::new().pubkey();
+        let ix = get_set_favourite_instruction(&owner, "bonfida").unwrap();
+        assert_eq!(ix.data, vec![ProgramInstruction::RegisterFavourite as u8]);
+        assert_eq!(ix.accounts.len(), 4);
+        assert_eq!(ix.accounts[1].pubkey, derive_favourite_domain_key(&owner));
+
+        let ix = get_set_favourite_instruction(&owner, "dex.bonfida").unwrap();
+        assert_eq!(ix.accounts.len(), 5);
+        assert_eq!(
+            ix.accounts[0].pubkey,
+            derive("\0dex", &ix.accounts[4].pubkey, None)
+        );
+        assert_eq!(ix.accounts[4].pubkey, get_domain_key("bonfida").unwrap());
+        assert!(!ix.accounts[0].is_writable);
+    }
+
+    #[test]
+    fn test_parse_favourite_domain() {
+        let name_account = Keypair::new().pubkey();
+        let mut data = vec![Tag::FavouriteDomain as u8];
+        data.extend_from_slice(&name_account.to_bytes());
+        let parsed = FavouriteDomain::parse(&data).unwrap();
+        assert_eq!(parsed.tag, Tag::FavouriteDomain);
+        assert_eq!(parsed.name_account, name_account);
+
+        data[0] = Tag::ActiveOffer as u8;
+        assert!(FavouriteDomain::parse(&data).is_err());
+    }
 }
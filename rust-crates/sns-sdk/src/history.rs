@@ -0,0 +1,292 @@
+use {
+    borsh::BorshDeserialize,
+    name_tokenizer::{instruction::ProgramInstruction as TokenizerInstruction, state::NftRecord},
+    solana_program::{instruction::CompiledInstruction, pubkey::Pubkey},
+    solana_sdk::{clock::UnixTimestamp, signature::Signature},
+    spl_name_service::instruction::NameRegistryInstruction,
+};
+
+use crate::{
+    derivation::{get_domain_key, get_reverse_key, NAME_TOKENIZER_ID},
+    error::SnsError,
+    record::{get_record_key, Record, RecordVersion},
+};
+
+/// The set of accounts whose transaction history makes up the history of a domain
+pub struct HistoryAccounts {
+    pub domain: Pubkey,
+    pub reverse: Pubkey,
+    pub nft_record: Pubkey,
+    pub records: Vec<(Pubkey, Record, RecordVersion)>,
+}
+
+impl HistoryAccounts {
+    pub fn new(domain: &str) -> Result<Self, SnsError> {
+        let domain_key = get_domain_key(domain)?;
+        let mut records = Vec::with_capacity(2 * Record::ALL.len());
+        for version in [RecordVersion::V1, RecordVersion::V2] {
+            for record in Record::ALL {
+                records.push((
+                    get_record_key(domain, record.clone(), version)?,
+                    record,
+                    version,
+                ));
+            }
+        }
+        Ok(Self {
+            domain: domain_key,
+            reverse: get_reverse_key(domain)?,
+            nft_record: NftRecord::find_key(&domain_key, &NAME_TOKENIZER_ID).0,
+            records,
+        })
+    }
+
+    /// All the addresses that need to be scanned, the domain registry first
+    pub fn addresses(&self) -> Vec<Pubkey> {
+        let mut res = vec![self.domain, self.reverse, self.nft_record];
+        res.extend(self.records.iter().map(|(k, _, _)| *k));
+        res
+    }
+
+    fn find_record(&self, key: &Pubkey) -> Option<(Record, RecordVersion)> {
+        self.records
+            .iter()
+            .find(|(k, _, _)| k == key)
+            .map(|(_, r, v)| (r.clone(), *v))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DomainEvent {
+    /// The domain registry was created with `owner`
+    Created { owner: Pubkey },
+    /// The domain registry was transferred to `new_owner`
+    Transfer { new_owner: Pubkey },
+    /// The domain registry was deleted
+    Burn,
+    /// The domain was tokenized into `mint` by `owner`
+    Tokenized { mint: Pubkey, owner: Pubkey },
+    /// The tokenized domain was redeemed by `owner`
+    Redeemed { mint: Pubkey, owner: Pubkey },
+    /// A record account was created
+    RecordCreated {
+        record: Record,
+        version: RecordVersion,
+        owner: Pubkey,
+    },
+    /// Record data was written at `offset`
+    RecordWrite {
+        record: Record,
+        version: RecordVersion,
+        offset: u32,
+        data: Vec<u8>,
+    },
+    /// A record account was deleted
+    RecordDeleted {
+        record: Record,
+        version: RecordVersion,
+    },
+    /// The reverse lookup registry was created
+    ReverseCreated,
+}
+
+#[derive(Debug, Clone)]
+pub struct DomainHistoryEntry {
+    pub signature: Signature,
+    pub slot: u64,
+    pub block_time: Option<UnixTimestamp>,
+    pub event: DomainEvent,
+}
+
+fn get_key(account_keys: &[Pubkey], accounts: &[u8], idx: usize) -> Option<Pubkey> {
+    accounts
+        .get(idx)
+        .and_then(|i| account_keys.get(*i as usize))
+        .copied()
+}
+
+fn decode_name_service_instruction(
+    accounts: &HistoryAccounts,
+    account_keys: &[Pubkey],
+    ix: &CompiledInstruction,
+) -> Option<DomainEvent> {
+    let instruction = NameRegistryInstruction::try_from_slice(&ix.data).ok()?;
+    let key = |idx| get_key(account_keys, &ix.accounts, idx);
+    match instruction {
+        NameRegistryInstruction::Create { .. } => {
+            let target = key(2)?;
+            let owner = key(3)?;
+            if target == accounts.domain {
+                Some(DomainEvent::Created { owner })
+            } else if target == accounts.reverse {
+                Some(DomainEvent::ReverseCreated)
+            } else {
+                let (record, version) = accounts.find_record(&target)?;
+                Some(DomainEvent::RecordCreated {
+                    record,
+                    version,
+                    owner,
+                })
+            }
+        }
+        NameRegistryInstruction::Transfer { new_owner } => {
+            (key(0)? == accounts.domain).then_some(DomainEvent::Transfer { new_owner })
+        }
+        NameRegistryInstruction::Update { offset, data } => {
+            let (record, version) = accounts.find_record(&key(0)?)?;
+            Some(DomainEvent::RecordWrite {
+                record,
+                version,
+                offset,
+                data,
+            })
+        }
+        NameRegistryInstruction::Delete => {
+            let target = key(0)?;
+            if target == accounts.domain {
+                return Some(DomainEvent::Burn);
+            }
+            let (record, version) = accounts.find_record(&target)?;
+            Some(DomainEvent::RecordDeleted { record, version })
+        }
+        NameRegistryInstruction::Realloc { .. } => None,
+    }
+}
+
+fn decode_tokenizer_instruction(
+    accounts: &HistoryAccounts,
+    account_keys: &[Pubkey],
+    ix: &CompiledInstruction,
+) -> Option<DomainEvent> {
+    let tag = *ix.data.first()?;
+    let key = |idx| get_key(account_keys, &ix.accounts, idx);
+    if tag == TokenizerInstruction::CreateNft as u8 && key(2)? == accounts.domain {
+        Some(DomainEvent::Tokenized {
+            mint: key(0)?,
+            owner: key(4)?,
+        })
+    } else if tag == TokenizerInstruction::RedeemNft as u8 && key(4)? == accounts.domain {
+        Some(DomainEvent::Redeemed {
+            mint: key(0)?,
+            owner: key(2)?,
+        })
+    } else {
+        None
+    }
+}
+
+/// Decodes a single compiled instruction into a domain event, if it concerns the domain
+pub fn decode_instruction(
+    accounts: &HistoryAccounts,
+    account_keys: &[Pubkey],
+    ix: &CompiledInstruction,
+) -> Option<DomainEvent> {
+    let program_id = account_keys.get(ix.program_id_index as usize)?;
+    if *program_id == spl_name_service::ID {
+        decode_name_service_instruction(accounts, account_keys, ix)
+    } else if *program_id == NAME_TOKENIZER_ID {
+        decode_tokenizer_instruction(accounts, account_keys, ix)
+    } else {
+        None
+    }
+}
+
+/// Decodes the events of a transaction.
+///
+/// `account_keys` must contain the static keys followed by the loaded writable and readonly addresses.
+/// `inner_instructions` are grouped by the index of the top-level instruction they were invoked from.
+/// Name service CPIs issued by the tokenizer are folded into the corresponding tokenization event.
+pub fn decode_transaction(
+    accounts: &HistoryAccounts,
+    account_keys: &[Pubkey],
+    instructions: &[CompiledInstruction],
+    inner_instructions: &[(u8, Vec<CompiledInstruction>)],
+) -> Vec<DomainEvent> {
+    let mut events = vec![];
+    for (idx, ix) in instructions.iter().enumerate() {
+        if let Some(event) = decode_instruction(accounts, account_keys, ix) {
+            let is_tokenizer = matches!(
+                event,
+                DomainEvent::Tokenized { .. } | DomainEvent::Redeemed { .. }
+            );
+            events.push(event);
+            if is_tokenizer {
+                continue;
+            }
+        }
+        inner_instructions
+            .iter()
+            .filter(|(i, _)| *i as usize == idx)
+            .flat_map(|(_, ixs)| ixs)
+            .filter_map(|ix| decode_instruction(accounts, account_keys, ix))
+            .for_each(|e| events.push(e));
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::message::Message;
+    use solana_sdk::{signature::Keypair, signer::Signer};
+
+    #[test]
+    fn test_decode_transfer() {
+        let domain = "bonfida";
+        let accounts = HistoryAccounts::new(domain).unwrap();
+        let owner = Keypair::new().pubkey();
+        let new_owner = Keypair::new().pubkey();
+        let ix = spl_name_service::instruction::transfer(
+            spl_name_service::ID,
+            new_owner,
+            accounts.domain,
+            owner,
+            None,
+        )
+        .unwrap();
+        let message = Message::new(&[ix], Some(&owner));
+        let events =
+            decode_transaction(&accounts, &message.account_keys, &message.instructions, &[]);
+        assert_eq!(events, vec![DomainEvent::Transfer { new_owner }]);
+    }
+
+    #[test]
+    fn test_decode_record_write() {
+        let domain = "bonfida";
+        let accounts = HistoryAccounts::new(domain).unwrap();
+        let owner = Keypair::new().pubkey();
+        let record_key = get_record_key(domain, Record::Url, RecordVersion::V1).unwrap();
+        let ix = spl_name_service::instruction::update(
+            spl_name_service::ID,
+            0,
+            b"https://sns.id".to_vec(),
+            record_key,
+            owner,
+            None,
+        )
+        .unwrap();
+        // The update is issued as a CPI of an unrelated program
+        let outer = solana_program::instruction::Instruction::new_with_bytes(
+            Keypair::new().pubkey(),
+            &[],
+            vec![],
+        );
+        let message = Message::new(&[outer, ix], Some(&owner));
+        let events = decode_transaction(
+            &accounts,
+            &message.account_keys,
+            &message.instructions[..1],
+            &[(0, message.instructions[1..].to_vec())],
+        );
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events[0],
+            DomainEvent::RecordWrite {
+                record: Record::Url,
+                version: RecordVersion::V1,
+                offset: 0,
+                ..
+            }
+        ));
+    }
+}
@@ -1,9 +1,16 @@
 use solana_program::{pubkey, pubkey::Pubkey};
 
+pub mod bridge;
+pub mod content;
 pub mod derivation;
 pub mod error;
 pub mod favourite_domain;
+pub mod history;
 pub mod register;
+pub mod snapshot;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transaction;
 mod utils;
 
 pub mod record;
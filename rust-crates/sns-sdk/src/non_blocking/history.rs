@@ -0,0 +1,227 @@
+use {
+    futures::{stream, StreamExt, TryStreamExt},
+    solana_client::{
+        nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config,
+        rpc_config::RpcTransactionConfig, rpc_response::RpcConfirmedTransactionStatusWithSignature,
+    },
+    solana_program::{instruction::CompiledInstruction, pubkey::Pubkey},
+    solana_sdk::signature::Signature,
+    solana_transaction_status::{UiInstruction, UiLoadedAddresses, UiTransactionEncoding},
+    std::{collections::HashSet, str::FromStr},
+};
+
+use crate::{
+    error::SnsError,
+    history::{decode_transaction, DomainHistoryEntry, HistoryAccounts},
+};
+
+const SIGNATURES_PAGE_SIZE: usize = 1_000;
+const MAX_CONCURRENT_REQUESTS: usize = 10;
+
+/// Returns every successful transaction signature that touched `address`, most recent first
+pub async fn get_all_signatures(
+    rpc_client: &RpcClient,
+    address: &Pubkey,
+) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, SnsError> {
+    let mut res = vec![];
+    let mut before = None;
+    loop {
+        let page = rpc_client
+            .get_signatures_for_address_with_config(
+                address,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until: None,
+                    limit: Some(SIGNATURES_PAGE_SIZE),
+                    commitment: Some(rpc_client.commitment()),
+                },
+            )
+            .await?;
+        let len = page.len();
+        before = page
+            .last()
+            .map(|s| parse_signature(&s.signature))
+            .transpose()?;
+        res.extend(page.into_iter().filter(|s| s.err.is_none()));
+        if len < SIGNATURES_PAGE_SIZE {
+            break;
+        }
+    }
+    Ok(res)
+}
+
+fn parse_signature(signature: &str) -> Result<Signature, SnsError> {
+    Signature::from_str(signature).map_err(|_| SnsError::InvalidSignature {
+        signature: signature.to_owned(),
+    })
+}
+
+async fn get_transaction_events(
+    rpc_client: &RpcClient,
+    accounts: &HistoryAccounts,
+    signature: Signature,
+) -> Result<Vec<DomainHistoryEntry>, SnsError> {
+    let tx = rpc_client
+        .get_transaction_with_config(
+            &signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(rpc_client.commitment()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await?;
+    let invalid = || SnsError::InvalidTransaction { signature };
+    let meta = tx.transaction.meta.ok_or_else(invalid)?;
+    if meta.err.is_some() {
+        return Ok(vec![]);
+    }
+    let versioned = tx.transaction.transaction.decode().ok_or_else(invalid)?;
+
+    let mut account_keys = versioned.message.static_account_keys().to_vec();
+    if let Some(loaded) = Option::<UiLoadedAddresses>::from(meta.loaded_addresses) {
+        for k in loaded.writable.iter().chain(loaded.readonly.iter()) {
+            account_keys.push(
+                Pubkey::from_str(k).map_err(|_| SnsError::InvalidPubkey { value: k.clone() })?,
+            );
+        }
+    }
+
+    let mut inner_instructions = vec![];
+    for inner in Option::<Vec<_>>::from(meta.inner_instructions).unwrap_or_default() {
+        let mut ixs = vec![];
+        for ix in inner.instructions {
+            if let UiInstruction::Compiled(ix) = ix {
+                ixs.push(CompiledInstruction {
+                    program_id_index: ix.program_id_index,
+                    accounts: ix.accounts,
+                    data: solana_sdk::bs58::decode(ix.data)
+                        .into_vec()
+                        .map_err(|_| invalid())?,
+                });
+            }
+        }
+        inner_instructions.push((inner.index, ixs));
+    }
+
+    let events = decode_transaction(
+        accounts,
+        &account_keys,
+        versioned.message.instructions(),
+        &inner_instructions,
+    );
+    Ok(events
+        .into_iter()
+        .map(|event| DomainHistoryEntry {
+            signature,
+            slot: tx.slot,
+            block_time: tx.block_time,
+            event,
+        })
+        .collect())
+}
+
+/// Merges the signatures of several addresses, oldest first and without duplicates.
+///
+/// Each list is most recent first, as returned by `get_signatures_for_address`: they are reversed
+/// and sorted by slot with a stable sort, so that transactions of the same slot keep the order
+/// given by the RPC
+fn chronological_signatures(
+    pages: Vec<Vec<RpcConfirmedTransactionStatusWithSignature>>,
+) -> Vec<RpcConfirmedTransactionStatusWithSignature> {
+    let mut seen = HashSet::new();
+    let mut signatures = pages
+        .into_iter()
+        .flat_map(|page| page.into_iter().rev())
+        .filter(|s| seen.insert(s.signature.clone()))
+        .collect::<Vec<_>>();
+    signatures.sort_by_key(|s| s.slot);
+    signatures
+}
+
+/// Builds the ownership and record timeline of a domain, oldest event first.
+///
+/// The history is reconstructed from the transactions that touched the domain registry,
+/// its reverse lookup, its NFT record and all of its V1 and V2 record accounts. Transfers of
+/// the NFT itself while the domain is tokenized are not part of the timeline.
+pub async fn get_domain_history(
+    rpc_client: &RpcClient,
+    domain: &str,
+) -> Result<Vec<DomainHistoryEntry>, SnsError> {
+    let accounts = HistoryAccounts::new(domain)?;
+
+    let pages = stream::iter(accounts.addresses())
+        .map(|address| async move { get_all_signatures(rpc_client, &address).await })
+        .buffered(MAX_CONCURRENT_REQUESTS)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let signatures = chronological_signatures(pages)
+        .iter()
+        .map(|s| parse_signature(&s.signature))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let entries = stream::iter(signatures)
+        .map(|sig| get_transaction_events(rpc_client, &accounts, sig))
+        .buffered(MAX_CONCURRENT_REQUESTS)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    Ok(entries.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{history::DomainEvent, testing::rpc::LocalRpc};
+    use dotenv::dotenv;
+
+    #[test]
+    fn test_chronological_signatures() {
+        let status = |signature: &str, slot| RpcConfirmedTransactionStatusWithSignature {
+            signature: signature.to_owned(),
+            slot,
+            err: None,
+            memo: None,
+            block_time: None,
+            confirmation_status: None,
+        };
+        let registry = vec![
+            status("d", 3),
+            status("c", 2),
+            status("b", 2),
+            status("a", 1),
+        ];
+        let record = vec![status("e", 2), status("c", 2)];
+        let signatures = chronological_signatures(vec![registry, record])
+            .into_iter()
+            .map(|s| s.signature)
+            .collect::<Vec<_>>();
+        assert_eq!(signatures, ["a", "b", "c", "e", "d"]);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
+    async fn test_get_domain_history() {
+        dotenv().ok();
+        let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
+        let history = get_domain_history(&client, "bonfida").await.unwrap();
+        assert!(matches!(
+            history.first().map(|e| &e.event),
+            Some(DomainEvent::Created { .. })
+        ));
+        assert!(history.windows(2).all(|w| w[0].slot <= w[1].slot));
+    }
+
+    #[tokio::test]
+    async fn test_local_domain_history() {
+        // The local node has not processed any transaction
+        let client = LocalRpc::default().into_client();
+        let signatures = get_all_signatures(&client, &Pubkey::new_unique())
+            .await
+            .unwrap();
+        assert!(signatures.is_empty());
+        let history = get_domain_history(&client, "bonfida").await.unwrap();
+        assert!(history.is_empty());
+    }
+}
@@ -1,2 +1,5 @@
+pub mod history;
 pub mod register;
 pub mod resolve;
+pub mod snapshot;
+pub mod transaction;
@@ -68,7 +68,7 @@ pub async fn register_domain_name(
                 None
             }
         })
-        .ok_or(SnsError::UnsupportedMint)?;
+        .ok_or(SnsError::UnsupportedMint { mint: *mint })?;
     let vault = get_associated_token_address(&VAULT_OWNER, mint);
     let instruction = get_register_instruction(
         REGISTER_PROGRAM_ID,
@@ -106,10 +106,12 @@ pub async fn register_domain_name(
 mod test {
     use super::*;
     use crate::register::FIDA_MINT;
+    use crate::testing::{self, rpc::LocalRpc};
     use crate::utils::test::generate_random_string;
     use dotenv::dotenv;
 
     #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     async fn test_registration() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -131,6 +133,7 @@ mod test {
     }
 
     #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     async fn test_registration_ref() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -150,4 +153,81 @@ mod test {
         let res = client.simulate_transaction(&tx).await.unwrap();
         assert!(res.value.err.is_none())
     }
+
+    #[tokio::test]
+    async fn test_local_registration() {
+        let buyer = Pubkey::new_unique();
+        let buyer_token_account = get_associated_token_address(&buyer, &FIDA_MINT);
+        let referrer_token_account = get_associated_token_address(&REFERRERS[2], &FIDA_MINT);
+
+        // The token account of the referrer is created when it does not exist
+        let client = LocalRpc::default().into_client();
+        let tx = register_domain_name(
+            &client,
+            "bonfida",
+            1_000,
+            &buyer,
+            &buyer_token_account,
+            Some(&FIDA_MINT),
+            Some(&REFERRERS[2]),
+        )
+        .await
+        .unwrap();
+        let keys = &tx.message.account_keys;
+        let programs = tx
+            .message
+            .instructions
+            .iter()
+            .map(|ix| keys[ix.program_id_index as usize])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            programs,
+            vec![spl_associated_token_account::ID, REGISTER_PROGRAM_ID]
+        );
+        assert_eq!(keys[0], buyer);
+        assert!(keys.contains(&get_domain_key("bonfida").unwrap()));
+        assert!(keys.contains(&referrer_token_account));
+
+        let client =
+            LocalRpc::new([testing::token_account(&FIDA_MINT, &REFERRERS[2], 0)]).into_client();
+        let tx = register_domain_name(
+            &client,
+            "bonfida",
+            1_000,
+            &buyer,
+            &buyer_token_account,
+            Some(&FIDA_MINT),
+            Some(&REFERRERS[2]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(tx.message.instructions.len(), 1);
+        assert!(tx.message.account_keys.contains(&referrer_token_account));
+
+        // Unknown referrers are ignored
+        let tx = register_domain_name(
+            &client,
+            "bonfida",
+            1_000,
+            &buyer,
+            &buyer_token_account,
+            None,
+            Some(&Pubkey::new_unique()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(tx.message.instructions.len(), 1);
+
+        let res = register_domain_name(
+            &client,
+            "bonfida",
+            1_000,
+            &buyer,
+            &buyer_token_account,
+            Some(&Pubkey::new_unique()),
+            None,
+        )
+        .await;
+        assert!(matches!(res, Err(SnsError::UnsupportedMint { .. })));
+    }
 }
@@ -1,30 +1,45 @@
 use {
     borsh::BorshDeserialize,
     name_tokenizer::state::NftRecord,
-    solana_account_decoder::UiAccountEncoding,
+    solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig},
     solana_client::{
-        client_error::{ClientError, ClientErrorKind},
         nonblocking::rpc_client::RpcClient,
         rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
         rpc_filter::{Memcmp, RpcFilterType},
-        rpc_request::RpcError::RpcRequestError,
     },
     solana_program::{program_pack::Pack, pubkey::Pubkey},
     spl_name_service::state::{get_seeds_and_key, NameRecordHeader},
     spl_token::state::Account,
     spl_token::state::Mint,
+    std::collections::HashMap,
 };
 
 use crate::{
+    bridge::{derive_x_domain_key, deserialize_x_domain, is_bridged, Owner, XDomain},
+    content::{ContentTarget, CONTENT_RECORDS},
     derivation::{
-        get_domain_key, get_domain_mint, get_hashed_name, NAME_TOKENIZER_ID, REVERSE_LOOKUP_CLASS,
-        ROOT_DOMAIN_ACCOUNT,
+        derive_reverse, get_domain_key, get_domain_mint, get_hashed_name, NAME_TOKENIZER_ID,
+        REVERSE_LOOKUP_CLASS, ROOT_DOMAIN_ACCOUNT,
+    },
+    error::{ErrorKind, SnsError},
+    favourite_domain::{
+        build_primary_domain, derive_favourite_domain_key, FavouriteDomain, PrimaryDomain,
+        PrimaryDomainAccounts,
+    },
+    record::{
+        get_record_key,
+        record_v1::{deserialize_record, verify_sol_record},
+        record_v2::parse_record_v2,
+        Record, RecordVersion,
     },
-    error::SnsError,
-    favourite_domain::{derive_favourite_domain_key, FavouriteDomain},
-    record::{get_record_key, record_v1::check_sol_record, Record},
 };
 
+/// Resolves the Solana owner of a domain: the holder of its NFT when it is tokenized, else the
+/// address of its SOL record V1 when it is signed by the registry owner, else the registry owner.
+///
+/// For a domain bridged to another chain, this returns the registry owner, i.e. the warp emitter
+/// or the `XDomain` account, and not its owner. Use [`resolve_domain_owner`] to get the owner on
+/// the foreign chain
 pub async fn resolve_owner(
     rpc_client: &RpcClient,
     domain: &str,
@@ -36,41 +51,37 @@ pub async fn resolve_owner(
         _ => return Ok(None),
     };
 
-    let nft_owner = resolve_nft_owner(rpc_client, &key).await?;
+    resolve_owner_from_registry(rpc_client, domain, &key, &header.owner)
+        .await
+        .map(Some)
+}
+
+/// [`resolve_owner`] for a domain whose name registry is already fetched
+async fn resolve_owner_from_registry(
+    rpc_client: &RpcClient,
+    domain: &str,
+    key: &Pubkey,
+    registry_owner: &Pubkey,
+) -> Result<Pubkey, SnsError> {
+    let nft_owner = resolve_nft_owner(rpc_client, key).await?;
 
     if let Some(nft_owner) = nft_owner {
-        return Ok(Some(nft_owner));
+        return Ok(nft_owner);
     }
 
     let sol_record_key = get_record_key(domain, Record::Sol, crate::record::RecordVersion::V1)?;
     match resolve_name_registry(rpc_client, &sol_record_key).await {
         Ok(Some((_, data))) => {
-            let data = &data[..96];
-            let record = [&data[..32], &sol_record_key.to_bytes()].concat();
-            let sig = &data[32..];
-            let encoded = hex::encode(record);
-            if check_sol_record(encoded.as_bytes(), sig, header.owner)? {
-                let owner = Pubkey::new_from_array(
-                    data[0..32]
-                        .try_into()
-                        .map_err(|_| SnsError::InvalidPubkey)?,
-                );
-                return Ok(Some(owner));
+            // Malformed and unverified SOL records are ignored
+            if let Ok(dst) = verify_sol_record(&data, &sol_record_key, registry_owner) {
+                return Ok(dst);
             }
         }
-        Err(SnsError::SolanaClient(ClientError {
-            request: None,
-            kind: ClientErrorKind::RpcError(RpcRequestError(err)),
-        })) => {
-            return Err(SnsError::SolanaClient(ClientError {
-                request: None,
-                kind: ClientErrorKind::RpcError(RpcRequestError(err)),
-            }))
-        }
+        Err(e) if e.kind() == ErrorKind::Transport => return Err(e),
         _ => {}
     }
 
-    Ok(Some(header.owner))
+    Ok(*registry_owner)
 }
 
 pub async fn resolve_record(
@@ -88,16 +99,25 @@ pub async fn resolve_record(
 }
 
 pub fn deserialize_name_registry(data: &[u8]) -> Result<(NameRecordHeader, Vec<u8>), SnsError> {
-    let header = NameRecordHeader::unpack_unchecked(&data[0..NameRecordHeader::LEN])?;
-    let data = data[NameRecordHeader::LEN..].to_vec();
-    Ok((header, data))
+    if data.len() < NameRecordHeader::LEN {
+        return Err(SnsError::AccountTooSmall {
+            expected: NameRecordHeader::LEN,
+            len: data.len(),
+        });
+    }
+    let (header, data) = data.split_at(NameRecordHeader::LEN);
+    let header = NameRecordHeader::unpack_unchecked(header)?;
+    Ok((header, data.to_vec()))
 }
 
 pub fn deserialize_reverse(data: &[u8]) -> Result<String, SnsError> {
-    let len = u32::from_le_bytes(data[0..4].try_into().unwrap());
-    let reverse =
-        String::from_utf8(data[4..4 + len as usize].to_vec()).or(Err(SnsError::InvalidReverse))?;
-    Ok(reverse)
+    let (len, data) = data
+        .split_first_chunk::<4>()
+        .ok_or(SnsError::InvalidReverse)?;
+    let reverse = data
+        .get(..u32::from_le_bytes(*len) as usize)
+        .ok_or(SnsError::InvalidReverse)?;
+    String::from_utf8(reverse.to_vec()).or(Err(SnsError::InvalidReverse))
 }
 
 pub async fn resolve_name_registry(
@@ -210,6 +230,75 @@ pub async fn get_domains_owner(
     Ok(keys)
 }
 
+/// Maximum length of the reverse lookup data fetched by `get_all_registered_domains`
+const MAX_REVERSE_LEN: usize = 256;
+
+pub async fn get_all_registered_domains(
+    rpc_client: &RpcClient,
+) -> Result<Vec<(Pubkey, Option<String>)>, SnsError> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            0,
+            ROOT_DOMAIN_ACCOUNT.to_bytes().to_vec(),
+        ))]),
+        with_context: None,
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: Some(UiDataSliceConfig {
+                offset: 0,
+                length: 0,
+            }),
+            ..Default::default()
+        },
+    };
+    let domains = rpc_client
+        .get_program_accounts_with_config(&spl_name_service::ID, config)
+        .await?;
+
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                0,
+                Pubkey::default().to_bytes().to_vec(),
+            )),
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                64,
+                REVERSE_LOOKUP_CLASS.to_bytes().to_vec(),
+            )),
+        ]),
+        with_context: None,
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: Some(UiDataSliceConfig {
+                offset: NameRecordHeader::LEN,
+                length: MAX_REVERSE_LEN,
+            }),
+            ..Default::default()
+        },
+    };
+    let reverses = rpc_client
+        .get_program_accounts_with_config(&spl_name_service::ID, config)
+        .await?
+        .into_iter()
+        .filter_map(|(k, acc)| Some((k, deserialize_reverse(&acc.data).ok()?)))
+        .collect::<HashMap<_, _>>();
+
+    let res = domains
+        .into_iter()
+        .map(|(k, _)| (k, reverses.get(&derive_reverse(&k, None)).cloned()))
+        .collect();
+    Ok(res)
+}
+
+pub async fn get_domain_keys_with_reverses(
+    rpc_client: &RpcClient,
+    owner: Pubkey,
+) -> Result<Vec<(Pubkey, Option<String>)>, SnsError> {
+    let keys = get_domains_owner(rpc_client, owner).await?;
+    let reverses = resolve_reverse_batch(rpc_client, &keys).await?;
+    Ok(keys.into_iter().zip(reverses).collect())
+}
+
 pub async fn get_record_from_mint(
     rpc_client: &RpcClient,
     mint: &Pubkey,
@@ -256,9 +345,7 @@ pub async fn get_nft_records(
         .get_program_accounts_with_config(&spl_token::ID, config.clone())
         .await?
         .into_iter()
-        .map(|(_, acc)| Account::unpack(&acc.data))
-        .filter(Result::is_ok)
-        .map(Result::unwrap)
+        .filter_map(|(_, acc)| Account::unpack(&acc.data).ok())
         .collect::<Vec<_>>();
 
     async fn closure(rpc_client: &RpcClient, acc: &Account) -> Result<NftRecord, SnsError> {
@@ -267,7 +354,7 @@ pub async fn get_nft_records(
             let des = NftRecord::deserialize(&mut acc.data.as_slice())?;
             return Ok(des);
         }
-        Err(SnsError::NftRecordDoesNotExist)
+        Err(SnsError::NftRecordDoesNotExist { mint: acc.mint })
     }
 
     let futures = res.iter().map(|acc| closure(rpc_client, acc));
@@ -275,8 +362,7 @@ pub async fn get_nft_records(
     let records = futures::future::join_all(futures)
         .await
         .into_iter()
-        .filter(Result::is_ok)
-        .map(Result::unwrap)
+        .filter_map(Result::ok)
         .collect::<Vec<_>>();
 
     Ok(records)
@@ -330,14 +416,11 @@ pub async fn get_subdomains(
     let res = res
         .into_iter()
         .map(|(_, acc)| {
-            let mut offset = NameRecordHeader::LEN;
-            let len = u32::from_le_bytes(acc.data[offset..offset + 4].try_into().unwrap());
-            offset += 4;
-
-            String::from_utf8(acc.data[offset..offset + len as usize].to_vec()).unwrap()
+            let (_, data) = deserialize_name_registry(&acc.data)?;
+            let reverse = deserialize_reverse(&data)?;
+            Ok(reverse.strip_prefix('\0').unwrap_or(&reverse).to_owned())
         })
-        .map(|x| x.strip_prefix('\0').unwrap().to_owned())
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, SnsError>>()?;
 
     Ok(res)
 }
@@ -348,11 +431,13 @@ pub async fn resolve_nft_owner(
 ) -> Result<Option<Pubkey>, SnsError> {
     let mint_key = get_domain_mint(domain_key);
     let acc = rpc_client.get_multiple_accounts(&[mint_key]).await?;
-    let acc = acc.first().ok_or(SnsError::InvalidDomain)?;
-    if acc.is_none() {
+    let acc = acc
+        .first()
+        .ok_or(SnsError::AccountNotFound { key: mint_key })?;
+    let Some(acc) = acc else {
         return Ok(None);
-    }
-    let mint = Mint::unpack(&acc.as_ref().unwrap().data)?;
+    };
+    let mint = Mint::unpack(&acc.data)?;
     if mint.supply != 1 {
         return Ok(None);
     }
@@ -382,6 +467,133 @@ pub async fn resolve_nft_owner(
     Ok(None)
 }
 
+/// Returns the owner of a domain, resolved like [`resolve_owner`] unless the domain is bridged to
+/// another chain, in which case the owner is read from its `XDomain` account
+pub async fn resolve_domain_owner(
+    rpc_client: &RpcClient,
+    domain: &str,
+) -> Result<Option<Owner>, SnsError> {
+    let key = get_domain_key(domain)?;
+    let Some((header, _)) = resolve_name_registry(rpc_client, &key).await? else {
+        return Ok(None);
+    };
+    match owner_from_registry(rpc_client, &key, header.owner).await? {
+        Owner::Solana(_) => resolve_owner_from_registry(rpc_client, domain, &key, &header.owner)
+            .await
+            .map(|owner| Some(Owner::Solana(owner))),
+        owner => Ok(Some(owner)),
+    }
+}
+
+/// Returns the owner of the name registry of a domain, i.e. the owner that signs its records,
+/// or the owner on the foreign chain when the domain is bridged
+pub async fn get_registry_owner(
+    rpc_client: &RpcClient,
+    domain_key: &Pubkey,
+) -> Result<Option<Owner>, SnsError> {
+    let header = match resolve_name_registry(rpc_client, domain_key).await? {
+        Some((h, _)) => h,
+        _ => return Ok(None),
+    };
+    owner_from_registry(rpc_client, domain_key, header.owner)
+        .await
+        .map(Some)
+}
+
+/// The [`Owner`] of a domain whose name registry is owned by `registry_owner`, read from its
+/// `XDomain` account when the domain is bridged
+async fn owner_from_registry(
+    rpc_client: &RpcClient,
+    domain_key: &Pubkey,
+    registry_owner: Pubkey,
+) -> Result<Owner, SnsError> {
+    let x_domain = if is_bridged(domain_key, &registry_owner) {
+        get_x_domain(rpc_client, domain_key).await?
+    } else {
+        None
+    };
+    Ok(Owner::new(domain_key, registry_owner, x_domain.as_ref()))
+}
+
+/// Resolves the content served by a domain, e.g. by a web gateway. The verified V2 records of
+/// [`CONTENT_RECORDS`] are considered first, i.e. signed by the current domain owner and with their
+/// right of association verified when required, then the V1 records. Within each version the
+/// records are considered in the order of [`CONTENT_RECORDS`]: `url`, `IPFS`, `ARWV`, `SHDW` and
+/// `CNAME`. Records whose content cannot be served are skipped, see
+/// [`ContentTarget::from_record`]
+pub async fn resolve_content(
+    rpc_client: &RpcClient,
+    domain: &str,
+) -> Result<Option<ContentTarget>, SnsError> {
+    let Some(owner) = get_registry_owner(rpc_client, &get_domain_key(domain)?).await? else {
+        return Ok(None);
+    };
+    let records = [RecordVersion::V2, RecordVersion::V1]
+        .into_iter()
+        .flat_map(|version| {
+            CONTENT_RECORDS
+                .into_iter()
+                .map(move |record| (record, version))
+        })
+        .map(|(record, version)| {
+            Ok((
+                get_record_key(domain, record.clone(), version)?,
+                record,
+                version,
+            ))
+        })
+        .collect::<Result<Vec<_>, SnsError>>()?;
+    let keys = records.iter().map(|(key, _, _)| *key).collect::<Vec<_>>();
+    let accounts = rpc_client.get_multiple_accounts(&keys).await?;
+    // Only SOL records, which do not hold content, are signed in V1
+    let registry_owner = match owner {
+        Owner::Solana(owner) => owner,
+        Owner::Foreign { .. } => Pubkey::default(),
+    };
+    for ((key, record, version), account) in records.into_iter().zip(accounts) {
+        let Some(account) = account else {
+            continue;
+        };
+        let content = match version {
+            RecordVersion::V2 => match parse_record_v2(record.clone(), &account.data).await {
+                Ok(parsed)
+                    if parsed.verify_staleness(&owner).is_ok() && parsed.verify_roa().is_ok() =>
+                {
+                    parsed.content
+                }
+                _ => continue,
+            },
+            RecordVersion::V1 => {
+                let data = account
+                    .data
+                    .get(NameRecordHeader::LEN..)
+                    .unwrap_or_default();
+                match deserialize_record(data, record.clone(), &key, &registry_owner) {
+                    Ok(content) => content,
+                    Err(_) => continue,
+                }
+            }
+        };
+        if let Some(target) = ContentTarget::from_record(&record, &content) {
+            return Ok(Some(target));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the `XDomain` account of a domain bridged to another chain, if any
+pub async fn get_x_domain(
+    rpc_client: &RpcClient,
+    domain_key: &Pubkey,
+) -> Result<Option<XDomain>, SnsError> {
+    let key = derive_x_domain_key(domain_key);
+    let account = rpc_client
+        .get_account_with_commitment(&key, rpc_client.commitment())
+        .await?
+        .value;
+    account.map(|a| deserialize_x_domain(&a.data)).transpose()
+}
+
 pub async fn get_favourite_domain(
     rpc_client: &RpcClient,
     owner: &Pubkey,
@@ -399,19 +611,140 @@ pub async fn get_favourite_domain(
     }
 }
 
+/// Returns the primary domain of `owner`, if any.
+///
+/// Unlike [`get_favourite_domain`], this resolves the name of the domain and checks
+/// whether `owner` still owns it, either directly or through the domain NFT.
+pub async fn get_primary_domain(
+    rpc_client: &RpcClient,
+    owner: &Pubkey,
+) -> Result<Option<PrimaryDomain>, SnsError> {
+    let mut res = get_multiple_primary_domains(rpc_client, &[*owner]).await?;
+    Ok(res.pop().flatten())
+}
+
+async fn get_multiple_accounts(
+    rpc_client: &RpcClient,
+    keys: &[Pubkey],
+) -> Result<Vec<Option<solana_sdk::account::Account>>, SnsError> {
+    let mut res = Vec::with_capacity(keys.len());
+    for k in keys.chunks(100) {
+        res.extend(rpc_client.get_multiple_accounts(k).await?);
+    }
+    Ok(res)
+}
+
+/// Returns the primary domains of `owners`, in the same order.
+///
+/// The accounts are fetched in batches: favourite domain accounts, then name registries,
+/// then reverse lookups and NFT token accounts. An owner whose accounts cannot be decoded has no
+/// primary domain, like one whose accounts do not exist, so that one corrupt account does not
+/// fail the whole batch.
+pub async fn get_multiple_primary_domains(
+    rpc_client: &RpcClient,
+    owners: &[Pubkey],
+) -> Result<Vec<Option<PrimaryDomain>>, SnsError> {
+    let favourite_keys = owners
+        .iter()
+        .map(derive_favourite_domain_key)
+        .collect::<Vec<_>>();
+    let domains = get_multiple_accounts(rpc_client, &favourite_keys)
+        .await?
+        .into_iter()
+        .map(|acc| acc.and_then(|a| FavouriteDomain::parse(&a.data).ok()))
+        .map(|f| f.map(|f| f.name_account))
+        .collect::<Vec<_>>();
+
+    let domain_keys = domains.iter().flatten().copied().collect::<Vec<_>>();
+    let mut registries = get_multiple_accounts(rpc_client, &domain_keys)
+        .await?
+        .into_iter();
+    let mut found = vec![];
+    for (owner, domain) in owners.iter().zip(&domains) {
+        let Some(domain) = domain else {
+            found.push(None);
+            continue;
+        };
+        let header = registries
+            .next()
+            .flatten()
+            .and_then(|acc| deserialize_name_registry(&acc.data).ok())
+            .map(|(header, _)| header);
+        found.push(header.map(|header| {
+            let accounts = PrimaryDomainAccounts::new(owner, domain, &header);
+            (*domain, header, accounts)
+        }));
+    }
+
+    let mut keys = vec![];
+    for (_, _, accounts) in found.iter().flatten() {
+        keys.push(accounts.reverse);
+        keys.push(accounts.token_account);
+        keys.extend(accounts.parent_reverse);
+    }
+    let mut accounts = get_multiple_accounts(rpc_client, &keys).await?.into_iter();
+    let parse_reverse = |acc: Option<solana_sdk::account::Account>| {
+        let (_, data) = deserialize_name_registry(&acc?.data).ok()?;
+        deserialize_reverse(&data).ok()
+    };
+
+    let mut res = Vec::with_capacity(owners.len());
+    for (owner, found) in owners.iter().zip(found) {
+        let Some((domain, header, keys)) = found else {
+            res.push(None);
+            continue;
+        };
+        let reverse = parse_reverse(accounts.next().flatten());
+        let token_account = accounts.next().flatten();
+        let parent_reverse = keys
+            .parent_reverse
+            .map(|_| parse_reverse(accounts.next().flatten()));
+        res.push(build_primary_domain(
+            owner,
+            &domain,
+            &header,
+            reverse,
+            parent_reverse,
+            token_account.as_ref().map(|a| a.data.as_slice()),
+        ));
+    }
+    Ok(res)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::derivation::get_domain_key;
+    use crate::derivation::{get_domain_key, get_reverse_key};
     use crate::record::record_v1::deserialize_record;
-    use crate::record::Record;
+    use crate::record::{Record, RecordVersion};
+    use crate::testing::{self, rpc::LocalRpc};
     use crate::utils::test::generate_random_string;
     use dotenv::dotenv;
     use solana_program::pubkey;
     use solana_sdk::signature::Keypair;
     use solana_sdk::signer::Signer;
 
+    #[test]
+    fn test_deserialize_malformed() {
+        assert!(matches!(
+            deserialize_name_registry(&[0; 95]),
+            Err(SnsError::AccountTooSmall {
+                expected: 96,
+                len: 95
+            })
+        ));
+        let (_, data) = deserialize_name_registry(&[0; 96]).unwrap();
+        assert!(data.is_empty());
+
+        assert!(deserialize_reverse(&[]).is_err());
+        assert!(deserialize_reverse(&[5, 0, 0, 0, b'a']).is_err());
+        assert!(deserialize_reverse(&[0xff, 0xff, 0xff, 0xff]).is_err());
+        assert!(deserialize_reverse(&[1, 0, 0, 0, 0xff]).is_err());
+        assert_eq!(deserialize_reverse(&[1, 0, 0, 0, b'a', 0]).unwrap(), "a");
+    }
+
     #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     async fn reverse() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -424,6 +757,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     async fn subs() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -434,6 +768,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     async fn resolve() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -467,6 +802,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     async fn batch_resolve_reverses() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -486,15 +822,17 @@ mod tests {
     }
 
     #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     async fn test_resolve_record() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
+        let key = Pubkey::default();
 
         let res = resolve_record(&client, "bonfida", Record::Url)
             .await
             .unwrap();
         assert_eq!(
-            deserialize_record(&res.unwrap().1, Record::Url, &Pubkey::default()).unwrap(),
+            deserialize_record(&res.unwrap().1, Record::Url, &key, &key).unwrap(),
             "https://sns.id"
         );
 
@@ -505,12 +843,47 @@ mod tests {
 
         let res = resolve_record(&client, "🍍", Record::Eth).await.unwrap();
         assert_eq!(
-            deserialize_record(&res.unwrap().1, Record::Eth, &Pubkey::default()).unwrap(),
+            deserialize_record(&res.unwrap().1, Record::Eth, &key, &key).unwrap(),
             "0x570eDC13f9D406a2b4E6477Ddf75D5E9cCF51cd6"
         );
+
+        // SOL record signed by the registry owner of the domain
+        let (header, _) =
+            resolve_name_registry(&client, &get_domain_key("wallet-guide-4").unwrap())
+                .await
+                .unwrap()
+                .unwrap();
+        let key = get_record_key("wallet-guide-4", Record::Sol, RecordVersion::V1).unwrap();
+        let (_, mut data) = resolve_record(&client, "wallet-guide-4", Record::Sol)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            deserialize_record(&data, Record::Sol, &key, &header.owner).unwrap(),
+            "Hf4daCT4tC2Vy9RCe9q8avT68yAsNJ1dQe6xiQqyGuqZ"
+        );
+        data[63] ^= 1;
+        assert!(matches!(
+            deserialize_record(&data, Record::Sol, &key, &header.owner),
+            Err(SnsError::UnverifiedRecord { .. })
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
+    async fn test_resolve_content() {
+        dotenv().ok();
+        let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
+        let content = resolve_content(&client, "bonfida").await.unwrap();
+        assert!(content.is_some_and(|c| c.gateway_url().starts_with("https://")));
+        let content = resolve_content(&client, &generate_random_string(20))
+            .await
+            .unwrap();
+        assert!(content.is_none());
     }
 
     #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     async fn test_resolve_registry() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -524,6 +897,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     async fn test_get_favourite_domain() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -540,6 +914,20 @@ mod tests {
     }
 
     #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
+    async fn test_get_domain_keys_with_reverses() {
+        dotenv().ok();
+        let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
+        let owner = pubkey!("HKKp49qGWXd639QsuH7JiLijfVW5UtCVY4s1n2HANwEA");
+        let domains = get_domain_keys_with_reverses(&client, owner).await.unwrap();
+        assert!(domains.contains(&(
+            pubkey!("Crf8hzfthWGbGbLTVCiqRqV5MVnbpHB1L9KQMd6gsinb"),
+            Some("bonfida".to_string())
+        )));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
     async fn test_get_tokenized_domains() {
         dotenv().ok();
         let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
@@ -547,4 +935,404 @@ mod tests {
         let domains = get_tokenized_domains(&client, &owner).await.unwrap();
         println!("{domains:?}");
     }
+
+    #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
+    async fn test_get_primary_domain() {
+        dotenv().ok();
+        let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
+        let owner = pubkey!("HKKp49qGWXd639QsuH7JiLijfVW5UtCVY4s1n2HANwEA");
+        let res = get_primary_domain(&client, &owner).await.unwrap().unwrap();
+        assert_eq!(
+            res.domain.to_string(),
+            "Crf8hzfthWGbGbLTVCiqRqV5MVnbpHB1L9KQMd6gsinb"
+        );
+        assert_eq!(res.reverse, "bonfida");
+        assert!(!res.stale);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires RPC_URL, a mainnet RPC node"]
+    async fn test_get_multiple_primary_domains() {
+        dotenv().ok();
+        let client = RpcClient::new(std::env::var("RPC_URL").unwrap());
+        let owners = [
+            pubkey!("HKKp49qGWXd639QsuH7JiLijfVW5UtCVY4s1n2HANwEA"),
+            Keypair::new().pubkey(),
+        ];
+        let res = get_multiple_primary_domains(&client, &owners)
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].as_ref().unwrap().reverse, "bonfida");
+        assert!(res[1].is_none());
+    }
+
+    /// A local state with a plain, a signed, a tokenized and a bridged domain
+    struct LocalState {
+        owner: Keypair,
+        holder: Pubkey,
+        target: Pubkey,
+        client: RpcClient,
+    }
+
+    fn local_state() -> LocalState {
+        let owner = Keypair::new();
+        let holder = Pubkey::new_unique();
+        let target = Pubkey::new_unique();
+        let other = Keypair::new();
+        let tokenized = get_domain_key("tokenized").unwrap();
+        let bridged = get_domain_key("bridged").unwrap();
+        let (x_domain_key, x_domain) = testing::x_domain(&bridged, 4, [1; 32]);
+        let central_state = name_tokenizer::state::CentralState::find_key(&NAME_TOKENIZER_ID).0;
+
+        let mut fixtures = vec![
+            testing::domain("bonfida", &owner.pubkey(), &[]).unwrap(),
+            testing::domain("dex.bonfida", &owner.pubkey(), &[]).unwrap(),
+            testing::domain("naming.bonfida", &holder, &[]).unwrap(),
+            testing::domain("signed", &owner.pubkey(), &[]).unwrap(),
+            testing::domain("unsigned", &owner.pubkey(), &[]).unwrap(),
+            testing::domain("tokenized", &central_state, &[]).unwrap(),
+            testing::domain("bridged", &x_domain_key, &[]).unwrap(),
+            (x_domain_key, x_domain),
+            testing::record_v1("bonfida", Record::Url, "https://sns.id", &owner.pubkey()).unwrap(),
+            testing::record_v1(
+                "dex.bonfida",
+                Record::Url,
+                "https://dex.sns.id",
+                &owner.pubkey(),
+            )
+            .unwrap(),
+            testing::verified_record_v2(
+                "bonfida",
+                Record::Url,
+                "https://v2.sns.id",
+                &owner.pubkey(),
+            )
+            .unwrap(),
+            // Signed by a previous owner
+            testing::verified_record_v2(
+                "dex.bonfida",
+                Record::Url,
+                "https://stale.sns.id",
+                &other.pubkey(),
+            )
+            .unwrap(),
+            testing::sol_record_v1("signed", &target, &owner).unwrap(),
+            testing::sol_record_v1("unsigned", &target, &other).unwrap(),
+            testing::nft_record(&tokenized, &owner.pubkey()),
+            testing::domain_mint(&tokenized, 1),
+            testing::token_account(&get_domain_mint(&tokenized), &holder, 1),
+            testing::favourite_domain(&owner.pubkey(), &get_domain_key("dex.bonfida").unwrap()),
+            testing::favourite_domain(&holder, &tokenized),
+            testing::favourite_domain(&other.pubkey(), &get_domain_key("bonfida").unwrap()),
+        ];
+        for domain in [
+            "bonfida",
+            "dex.bonfida",
+            "naming.bonfida",
+            "signed",
+            "unsigned",
+            "tokenized",
+            "bridged",
+        ] {
+            fixtures.push(testing::reverse(domain).unwrap());
+        }
+
+        LocalState {
+            owner,
+            holder,
+            target,
+            client: LocalRpc::new(fixtures).into_client(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_resolve_owner() {
+        let state = local_state();
+        let client = &state.client;
+        let owner = state.owner.pubkey();
+
+        let res = resolve_owner(client, "bonfida").await.unwrap();
+        assert_eq!(res, Some(owner));
+        let res = resolve_owner(client, "naming.bonfida").await.unwrap();
+        assert_eq!(res, Some(state.holder));
+        let res = resolve_owner(client, "signed").await.unwrap();
+        assert_eq!(res, Some(state.target));
+        let res = resolve_owner(client, "unsigned").await.unwrap();
+        assert_eq!(res, Some(owner));
+        let res = resolve_owner(client, "tokenized").await.unwrap();
+        assert_eq!(res, Some(state.holder));
+        let res = resolve_owner(client, "bridged").await.unwrap();
+        assert_eq!(
+            res,
+            Some(derive_x_domain_key(&get_domain_key("bridged").unwrap()))
+        );
+        let res = resolve_owner(client, "unknown").await.unwrap();
+        assert_eq!(res, None);
+
+        let res = resolve_nft_owner(client, &get_domain_key("tokenized").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res, Some(state.holder));
+        let res = resolve_nft_owner(client, &get_domain_key("bonfida").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res, None);
+    }
+
+    #[tokio::test]
+    async fn test_local_domain_owner() {
+        let state = local_state();
+        let client = &state.client;
+        let bridged = Owner::Foreign {
+            chain: 4,
+            address: vec![1; 32],
+        };
+
+        let res = resolve_domain_owner(client, "signed").await.unwrap();
+        assert_eq!(res, Some(Owner::Solana(state.target)));
+        let res = resolve_domain_owner(client, "bridged").await.unwrap();
+        assert_eq!(res, Some(bridged.clone()));
+        let res = resolve_domain_owner(client, "unknown").await.unwrap();
+        assert_eq!(res, None);
+
+        let key = get_domain_key("signed").unwrap();
+        let res = get_registry_owner(client, &key).await.unwrap();
+        assert_eq!(res, Some(Owner::Solana(state.owner.pubkey())));
+        let key = get_domain_key("bridged").unwrap();
+        let res = get_registry_owner(client, &key).await.unwrap();
+        assert_eq!(res, Some(bridged));
+
+        let x_domain = get_x_domain(client, &key).await.unwrap().unwrap();
+        assert_eq!(x_domain.name_account, key);
+        let key = get_domain_key("bonfida").unwrap();
+        assert!(get_x_domain(client, &key).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_local_records() {
+        let state = local_state();
+        let client = &state.client;
+        let owner = state.owner.pubkey();
+
+        let (_, data) = resolve_record(client, "bonfida", Record::Url)
+            .await
+            .unwrap()
+            .unwrap();
+        let key = get_record_key("bonfida", Record::Url, RecordVersion::V1).unwrap();
+        assert_eq!(
+            deserialize_record(&data, Record::Url, &key, &owner).unwrap(),
+            "https://sns.id"
+        );
+        let res = resolve_record(client, "bonfida", Record::Backpack)
+            .await
+            .unwrap();
+        assert!(res.is_none());
+
+        let key = get_record_key("signed", Record::Sol, RecordVersion::V1).unwrap();
+        let (_, data) = resolve_record(client, "signed", Record::Sol)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            deserialize_record(&data, Record::Sol, &key, &owner).unwrap(),
+            state.target.to_string()
+        );
+
+        let keys = [
+            get_domain_key("bonfida").unwrap(),
+            get_domain_key("unknown").unwrap(),
+        ];
+        let res = resolve_name_registry_batch(client, &keys).await.unwrap();
+        assert_eq!(res[0].as_ref().unwrap().0.owner, owner);
+        assert!(res[1].is_none());
+    }
+
+    #[tokio::test]
+    async fn test_local_resolve_content() {
+        let state = local_state();
+        let client = &state.client;
+
+        // The verified V2 record comes first
+        let res = resolve_content(client, "bonfida").await.unwrap();
+        assert_eq!(
+            res,
+            Some(ContentTarget::Http("https://v2.sns.id/".to_owned()))
+        );
+        // The V2 record signed by a previous owner is skipped
+        let res = resolve_content(client, "dex.bonfida").await.unwrap();
+        assert_eq!(
+            res,
+            Some(ContentTarget::Http("https://dex.sns.id/".to_owned()))
+        );
+        let res = resolve_content(client, "signed").await.unwrap();
+        assert_eq!(res, None);
+        let res = resolve_content(client, "unknown").await.unwrap();
+        assert_eq!(res, None);
+    }
+
+    #[tokio::test]
+    async fn test_local_reverses() {
+        let state = local_state();
+        let client = &state.client;
+        let bonfida = get_domain_key("bonfida").unwrap();
+
+        let res = resolve_reverse(client, &bonfida).await.unwrap();
+        assert_eq!(res.as_deref(), Some("bonfida"));
+        let res = resolve_reverse(client, &Pubkey::new_unique())
+            .await
+            .unwrap();
+        assert_eq!(res, None);
+        let res = resolve_reverse_batch(client, &[bonfida, Pubkey::new_unique(), bonfida])
+            .await
+            .unwrap();
+        assert_eq!(
+            res,
+            vec![Some("bonfida".to_owned()), None, Some("bonfida".to_owned())]
+        );
+
+        let mut subdomains = get_subdomains(client, &bonfida).await.unwrap();
+        subdomains.sort();
+        assert_eq!(subdomains, vec!["dex", "naming"]);
+    }
+
+    #[tokio::test]
+    async fn test_local_domains_of_owner() {
+        let state = local_state();
+        let client = &state.client;
+        let owner = state.owner.pubkey();
+
+        let mut res = get_domains_owner(client, owner).await.unwrap();
+        res.sort();
+        let mut expected = ["bonfida", "signed", "unsigned"]
+            .map(|d| get_domain_key(d).unwrap())
+            .to_vec();
+        expected.sort();
+        assert_eq!(res, expected);
+
+        let res = get_domain_keys_with_reverses(client, owner).await.unwrap();
+        assert_eq!(res.len(), 3);
+        assert!(res.contains(&(get_domain_key("signed").unwrap(), Some("signed".to_owned()))));
+
+        let mut res = get_all_registered_domains(client).await.unwrap();
+        res.sort_by(|a, b| a.1.cmp(&b.1));
+        let names = res
+            .iter()
+            .map(|(_, n)| n.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["bonfida", "bridged", "signed", "tokenized", "unsigned"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_tokenized_domains() {
+        let state = local_state();
+        let client = &state.client;
+        let tokenized = get_domain_key("tokenized").unwrap();
+
+        let records = get_nft_records(client, &state.holder).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].name_account, tokenized);
+        assert_eq!(records[0].owner, state.owner.pubkey());
+
+        let res = get_record_from_mint(client, &get_domain_mint(&tokenized))
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 1);
+
+        let res = get_tokenized_domains(client, &state.holder).await.unwrap();
+        assert_eq!(res, vec![("tokenized".to_owned(), tokenized)]);
+        let res = get_tokenized_domains(client, &state.owner.pubkey())
+            .await
+            .unwrap();
+        assert!(res.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_local_primary_domains() {
+        let state = local_state();
+        let client = &state.client;
+        let owner = state.owner.pubkey();
+
+        let res = get_favourite_domain(client, &owner).await.unwrap();
+        assert_eq!(res, Some(get_domain_key("dex.bonfida").unwrap()));
+        let res = get_favourite_domain(client, &Pubkey::new_unique())
+            .await
+            .unwrap();
+        assert_eq!(res, None);
+
+        let res = get_primary_domain(client, &owner).await.unwrap().unwrap();
+        assert_eq!(res.reverse, "dex.bonfida");
+        assert!(!res.stale);
+
+        let favourites =
+            get_multiple_primary_domains(client, &[state.holder, Pubkey::new_unique(), owner])
+                .await
+                .unwrap();
+        let favourites = favourites
+            .into_iter()
+            .map(|f| f.map(|f| (f.reverse, f.stale)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            favourites,
+            vec![
+                Some(("tokenized".to_owned(), false)),
+                None,
+                Some(("dex.bonfida".to_owned(), false))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_stale_primary_domain() {
+        let owner = Pubkey::new_unique();
+        let previous_owner = Pubkey::new_unique();
+        let client = LocalRpc::new([
+            testing::domain("bonfida", &owner, &[]).unwrap(),
+            testing::reverse("bonfida").unwrap(),
+            testing::favourite_domain(&previous_owner, &get_domain_key("bonfida").unwrap()),
+        ])
+        .into_client();
+        let res = get_primary_domain(&client, &previous_owner)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.reverse, "bonfida");
+        assert!(res.stale);
+    }
+
+    #[tokio::test]
+    async fn test_local_corrupt_primary_domains() {
+        let owners = [(); 4].map(|_| Pubkey::new_unique());
+        let domain = |name| get_domain_key(name).unwrap();
+        let corrupt = |key| (key, testing::account(&spl_name_service::ID, vec![1, 2, 3]));
+        let client = LocalRpc::new([
+            testing::domain("bonfida", &owners[0], &[]).unwrap(),
+            testing::reverse("bonfida").unwrap(),
+            testing::favourite_domain(&owners[0], &domain("bonfida")),
+            // The name registry is corrupt
+            corrupt(domain("corrupt")),
+            testing::favourite_domain(&owners[1], &domain("corrupt")),
+            // The reverse is corrupt
+            testing::domain("reverse", &owners[2], &[]).unwrap(),
+            corrupt(get_reverse_key("reverse").unwrap()),
+            testing::favourite_domain(&owners[2], &domain("reverse")),
+            // The reverse of the parent is corrupt
+            testing::domain("parent", &owners[3], &[]).unwrap(),
+            testing::domain("sub.parent", &owners[3], &[]).unwrap(),
+            testing::reverse("sub.parent").unwrap(),
+            corrupt(get_reverse_key("parent").unwrap()),
+            testing::favourite_domain(&owners[3], &domain("sub.parent")),
+        ])
+        .into_client();
+
+        let res = get_multiple_primary_domains(&client, &owners)
+            .await
+            .unwrap();
+        assert_eq!(res[0].as_ref().unwrap().reverse, "bonfida");
+        assert_eq!(res[1..], [None, None, None]);
+    }
 }
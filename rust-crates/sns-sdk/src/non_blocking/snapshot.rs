@@ -0,0 +1,202 @@
+use {
+    serde_json::json,
+    solana_account_decoder::UiAccountEncoding,
+    solana_client::{
+        client_error::ClientError,
+        nonblocking::rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+        rpc_request::{RpcError, RpcRequest},
+        rpc_response::{OptionalContext, RpcKeyedAccount},
+    },
+    solana_program::pubkey::Pubkey,
+    solana_sdk::account::Account,
+    std::str::FromStr,
+};
+
+use crate::{
+    derivation::{NAME_TOKENIZER_ID, REVERSE_LOOKUP_CLASS},
+    error::SnsError,
+    favourite_domain::Tag as FavouriteTag,
+    record::CENTRAL_STATE_RECORD_V2,
+    snapshot::{Snapshot, SnapshotAccounts, NFT_RECORD_LEN},
+    NAME_OFFERS_PROGRAM_ID,
+};
+
+fn parse_error(message: String) -> SnsError {
+    ClientError::from(RpcError::ParseError(message)).into()
+}
+
+/// Scans the accounts of a program at `min_slot` or later, returns them with the slot the scan
+/// was served at
+async fn get_program_accounts(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    filters: Vec<RpcFilterType>,
+    min_slot: Option<u64>,
+) -> Result<(u64, Vec<(Pubkey, Account)>), SnsError> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(filters),
+        with_context: Some(true),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(rpc_client.commitment()),
+            min_context_slot: min_slot,
+            ..Default::default()
+        },
+    };
+    let res = rpc_client
+        .send::<OptionalContext<Vec<RpcKeyedAccount>>>(
+            RpcRequest::GetProgramAccounts,
+            json!([program_id.to_string(), config]),
+        )
+        .await?;
+    let OptionalContext::Context(res) = res else {
+        return Err(parse_error(
+            "getProgramAccounts returned no context slot".to_owned(),
+        ));
+    };
+    let accounts = res
+        .value
+        .into_iter()
+        .map(|RpcKeyedAccount { pubkey, account }| {
+            let key = Pubkey::from_str(&pubkey)
+                .map_err(|_| parse_error(format!("Invalid account key {pubkey}")))?;
+            let account = account
+                .decode()
+                .ok_or_else(|| parse_error(format!("Failed to decode account {pubkey}")))?;
+            Ok((key, account))
+        })
+        .collect::<Result<_, SnsError>>()?;
+    Ok((res.context.slot, accounts))
+}
+
+fn class_filter(class: &Pubkey) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp::new_raw_bytes(64, class.to_bytes().to_vec()))
+}
+
+/// Fetches every account of the name service and classifies them.
+///
+/// The slot of the snapshot is the slot the first program scan is served at. The following scans
+/// are served at that slot or a later one, the slot of the most recent scan is `end_slot`: the
+/// snapshot is only consistent at a single slot when both are equal. Every call downloads all the
+/// accounts again, there is no incremental fetch.
+/// This requires an RPC node that allows `getProgramAccounts` on the name service program.
+pub async fn get_snapshot(rpc_client: &RpcClient) -> Result<Snapshot, SnsError> {
+    let (slot, name_accounts) = get_program_accounts(
+        rpc_client,
+        &spl_name_service::ID,
+        vec![class_filter(&Pubkey::default())],
+        None,
+    )
+    .await?;
+    let min_slot = Some(slot);
+    let (reverse_slot, reverse_accounts) = get_program_accounts(
+        rpc_client,
+        &spl_name_service::ID,
+        vec![class_filter(&REVERSE_LOOKUP_CLASS)],
+        min_slot,
+    )
+    .await?;
+    let (record_v2_slot, record_v2_accounts) = get_program_accounts(
+        rpc_client,
+        &spl_name_service::ID,
+        vec![class_filter(&CENTRAL_STATE_RECORD_V2)],
+        min_slot,
+    )
+    .await?;
+    let (nft_record_slot, nft_record_accounts) = get_program_accounts(
+        rpc_client,
+        &NAME_TOKENIZER_ID,
+        vec![RpcFilterType::DataSize(NFT_RECORD_LEN)],
+        min_slot,
+    )
+    .await?;
+    let (favourite_slot, favourite_accounts) = get_program_accounts(
+        rpc_client,
+        &NAME_OFFERS_PROGRAM_ID,
+        vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            0,
+            vec![FavouriteTag::FavouriteDomain as u8],
+        ))],
+        min_slot,
+    )
+    .await?;
+
+    let mut snapshot = Snapshot::from_accounts(
+        slot,
+        SnapshotAccounts {
+            name_accounts,
+            reverse_accounts,
+            record_v2_accounts,
+            nft_record_accounts,
+            favourite_accounts,
+        },
+    );
+    snapshot.end_slot = [
+        reverse_slot,
+        record_v2_slot,
+        nft_record_slot,
+        favourite_slot,
+    ]
+    .into_iter()
+    .fold(slot, u64::max);
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        derivation::get_domain_key,
+        record::{Record, RecordVersion},
+        testing::{self, rpc::LocalRpc},
+    };
+    use solana_sdk::{signature::Keypair, signer::Signer};
+
+    #[tokio::test]
+    async fn test_get_snapshot() {
+        let owner = Keypair::new();
+        let bonfida = get_domain_key("bonfida").unwrap();
+        let client = LocalRpc::new([
+            testing::domain("bonfida", &owner.pubkey(), &[]).unwrap(),
+            testing::domain("dex.bonfida", &owner.pubkey(), &[]).unwrap(),
+            testing::reverse("bonfida").unwrap(),
+            testing::reverse("dex.bonfida").unwrap(),
+            testing::record_v1("bonfida", Record::Url, "https://sns.id", &owner.pubkey()).unwrap(),
+            testing::sol_record_v1("dex.bonfida", &Pubkey::new_unique(), &owner).unwrap(),
+            testing::verified_record_v2("bonfida", Record::TXT, "hello", &owner.pubkey()).unwrap(),
+            testing::nft_record(&bonfida, &owner.pubkey()),
+            testing::favourite_domain(&owner.pubkey(), &bonfida),
+        ])
+        .with_slot(42)
+        .into_client();
+
+        let snapshot = get_snapshot(&client).await.unwrap();
+        assert_eq!((snapshot.slot, snapshot.end_slot), (42, 42));
+        assert!(snapshot.skipped.is_empty());
+        assert_eq!(snapshot.domains.len(), 1);
+        assert_eq!(snapshot.domains[0].name.as_deref(), Some("bonfida"));
+        assert_eq!(snapshot.subdomains.len(), 1);
+        assert_eq!(snapshot.subdomains[0].name, "dex");
+        assert_eq!(snapshot.reverses.len(), 2);
+        let mut records = snapshot
+            .records
+            .iter()
+            .map(|r| (r.record.clone().unwrap(), r.version))
+            .collect::<Vec<_>>();
+        records.sort_by_key(|(r, _)| r.as_str().to_owned());
+        assert_eq!(
+            records,
+            vec![
+                (Record::Sol, RecordVersion::V1),
+                (Record::TXT, RecordVersion::V2),
+                (Record::Url, RecordVersion::V1)
+            ]
+        );
+        assert_eq!(snapshot.nft_records.len(), 1);
+        assert_eq!(snapshot.nft_records[0].name_account, bonfida);
+        assert_eq!(snapshot.favourites.len(), 1);
+        assert_eq!(snapshot.favourites[0].owner, Some(owner.pubkey()));
+    }
+}
@@ -0,0 +1,405 @@
+use {
+    futures::{stream, StreamExt},
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_program::{hash::Hash, instruction::Instruction, pubkey::Pubkey},
+    solana_sdk::{
+        signature::Signature, signer::SignerError, signers::Signers, transaction::Transaction,
+    },
+    std::time::Duration,
+};
+
+use crate::{
+    error::SnsError,
+    transaction::{pack_instruction_groups, InstructionGroup, SendConfig},
+};
+
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// The number of transient RPC errors in a row after which a transaction in flight is given up
+const MAX_TRANSIENT_ERRORS: usize = 20;
+
+/// The outcome of the transaction which carried the instructions of `domains`
+#[derive(Debug)]
+pub struct TransactionResult {
+    pub domains: Vec<String>,
+    pub result: Result<Signature, SnsError>,
+}
+
+/// Counts the transient RPC errors met in a row while a transaction is in flight
+struct TransientErrors {
+    count: usize,
+}
+
+impl TransientErrors {
+    /// Returns `err` unless it is transient and fewer than [`MAX_TRANSIENT_ERRORS`] were met
+    fn tolerate(&mut self, err: SnsError) -> Result<(), SnsError> {
+        if !err.is_retryable() || self.count >= MAX_TRANSIENT_ERRORS {
+            return Err(err);
+        }
+        self.count += 1;
+        Ok(())
+    }
+}
+
+/// Sends `tx` and waits for its confirmation. Returns `None` once its blockhash expired without
+/// it landing, which is the only case where signing the instructions again cannot execute them
+/// twice.
+///
+/// Transient RPC errors do not stop the confirmation: the same transaction is sent again if it
+/// could not be sent and its status is polled until it lands or its blockhash expires
+async fn send_and_confirm(
+    rpc_client: &RpcClient,
+    tx: &Transaction,
+    last_valid_block_height: u64,
+) -> Result<Option<Signature>, SnsError> {
+    let signature = tx.signatures[0];
+    let mut errors = TransientErrors { count: 0 };
+    let mut sent = false;
+    let mut expired = false;
+    loop {
+        if !sent {
+            match rpc_client.send_transaction(tx).await {
+                Ok(_) => sent = true,
+                Err(err) => errors.tolerate(err.into())?,
+            }
+        }
+        match rpc_client
+            .get_signature_status_with_commitment(&signature, rpc_client.commitment())
+            .await
+        {
+            Ok(Some(Ok(()))) => return Ok(Some(signature)),
+            Ok(Some(Err(error))) => return Err(SnsError::TransactionFailed { signature, error }),
+            // The status was checked after the expiry of the blockhash
+            Ok(None) if expired => return Ok(None),
+            Ok(None) => errors.count = 0,
+            Err(err) => errors.tolerate(err.into())?,
+        }
+        if !expired {
+            match rpc_client.get_block_height().await {
+                // Check the status once more, the transaction may have landed since
+                Ok(height) if height > last_valid_block_height => {
+                    expired = true;
+                    continue;
+                }
+                Ok(_) => {}
+                Err(err) => errors.tolerate(err.into())?,
+            }
+        }
+        tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await;
+    }
+}
+
+/// Signs a transaction with the signers it requires, the others are ignored so that the same
+/// signers can be used for transactions carrying different instructions
+fn sign_transaction<S: Signers + ?Sized>(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    signers: &S,
+    blockhash: Hash,
+) -> Result<Transaction, SnsError> {
+    let mut tx = Transaction::new_with_payer(instructions, Some(payer));
+    tx.message.recent_blockhash = blockhash;
+    let positions = tx
+        .get_signing_keypair_positions(&signers.pubkeys())
+        .map_err(|e| SignerError::Custom(e.to_string()))?;
+    let signatures = signers.try_sign_message(&tx.message_data())?;
+    for (position, signature) in positions.into_iter().zip(signatures) {
+        if let Some(position) = position {
+            tx.signatures[position] = signature;
+        }
+    }
+    if !tx.is_signed() {
+        return Err(SignerError::NotEnoughSigners.into());
+    }
+    Ok(tx)
+}
+
+/// Signs and sends a transaction, signing it again with a new blockhash when the previous one
+/// expired before the transaction landed, up to `max_retries` times.
+///
+/// A transaction is never signed again while it can still land: transient RPC errors met while it
+/// is in flight are retried with the same transaction, see `send_and_confirm`
+pub async fn send_with_retries<S: Signers + ?Sized>(
+    rpc_client: &RpcClient,
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    signers: &S,
+    max_retries: usize,
+) -> Result<Signature, SnsError> {
+    let mut retries = 0;
+    loop {
+        let (blockhash, last_valid_block_height) = match rpc_client
+            .get_latest_blockhash_with_commitment(rpc_client.commitment())
+            .await
+        {
+            Ok(blockhash) => blockhash,
+            // Nothing was sent yet
+            Err(err) => {
+                let err = SnsError::from(err);
+                if !err.is_retryable() || retries >= max_retries {
+                    return Err(err);
+                }
+                retries += 1;
+                tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await;
+                continue;
+            }
+        };
+        let tx = sign_transaction(payer, instructions, signers, blockhash)?;
+
+        match send_and_confirm(rpc_client, &tx, last_valid_block_height).await? {
+            Some(signature) => return Ok(signature),
+            None if retries >= max_retries => {
+                return Err(SnsError::BlockhashExpired {
+                    signature: tx.signatures[0],
+                })
+            }
+            None => retries += 1,
+        }
+    }
+}
+
+/// Packs the instructions of each domain into as few transactions as possible, then sends them
+/// with at most `config.max_concurrency` transactions in flight.
+///
+/// The fee payer is the first of `signers`, each transaction is signed by the signers it
+/// requires among `signers`. Each domain is reported in exactly one result, in the
+/// order of `groups`; a failed transaction does not prevent the others from being sent
+pub async fn send_instruction_groups<S: Signers + ?Sized>(
+    rpc_client: &RpcClient,
+    signers: &S,
+    groups: &[InstructionGroup],
+    config: &SendConfig,
+) -> Result<Vec<TransactionResult>, SnsError> {
+    let payer = *signers
+        .pubkeys()
+        .first()
+        .ok_or(SignerError::NotEnoughSigners)?;
+    let packed = pack_instruction_groups(&payer, groups, config)?;
+
+    let results = stream::iter(packed)
+        .map(|tx| async move {
+            let result = send_with_retries(
+                rpc_client,
+                &payer,
+                &tx.instructions,
+                signers,
+                config.max_retries,
+            )
+            .await;
+            TransactionResult {
+                domains: tx
+                    .groups
+                    .iter()
+                    .map(|i| groups[*i].domain.clone())
+                    .collect(),
+                result,
+            }
+        })
+        .buffered(config.max_concurrency.max(1))
+        .collect()
+        .await;
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+    use solana_client::{
+        client_error::{ClientError, Result as ClientResult},
+        rpc_client::RpcClientConfig,
+        rpc_request::{RpcError, RpcRequest},
+        rpc_response::{Response, RpcBlockhash, RpcResponseContext, RpcVersionInfo},
+        rpc_sender::{RpcSender, RpcTransportStats},
+    };
+    use solana_program::instruction::AccountMeta;
+    use solana_sdk::{signature::Keypair, signer::Signer};
+    use solana_transaction_status::{
+        EncodedTransaction, TransactionBinaryEncoding, TransactionConfirmationStatus,
+        TransactionStatus,
+    };
+    use std::sync::{Arc, Mutex};
+
+    const LAST_VALID_BLOCK_HEIGHT: u64 = 10;
+
+    #[derive(Default)]
+    struct FlakyState {
+        /// The signatures of the transactions received
+        sent: Vec<Signature>,
+        /// The number of status requests failing with a timeout before the others succeed
+        status_errors: usize,
+        /// Whether the transaction lands before its blockhash expires, or right after
+        lands: Option<bool>,
+        block_height: u64,
+        /// The increase of the block height between two requests
+        block_step: u64,
+    }
+
+    /// An RPC node whose status requests time out and whose block height grows on each request
+    #[derive(Clone, Default)]
+    struct FlakyRpc(Arc<Mutex<FlakyState>>);
+
+    #[async_trait]
+    impl RpcSender for FlakyRpc {
+        async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+            let mut state = self.0.lock().unwrap();
+            let context = RpcResponseContext {
+                slot: 1,
+                api_version: None,
+            };
+            match request {
+                RpcRequest::GetVersion => Ok(serde_json::to_value(RpcVersionInfo {
+                    solana_core: "1.18.15".to_owned(),
+                    feature_set: None,
+                })?),
+                RpcRequest::GetLatestBlockhash => Ok(serde_json::to_value(Response {
+                    context,
+                    value: RpcBlockhash {
+                        blockhash: Hash::new_unique().to_string(),
+                        last_valid_block_height: state.block_height + LAST_VALID_BLOCK_HEIGHT,
+                    },
+                })?),
+                RpcRequest::SendTransaction => {
+                    let tx = EncodedTransaction::Binary(
+                        params[0].as_str().unwrap().to_owned(),
+                        TransactionBinaryEncoding::Base64,
+                    )
+                    .decode()
+                    .unwrap();
+                    state.sent.push(tx.signatures[0]);
+                    Ok(json!(tx.signatures[0].to_string()))
+                }
+                RpcRequest::GetSignatureStatuses => {
+                    if state.status_errors > 0 {
+                        state.status_errors -= 1;
+                        return Err(ClientError::from(std::io::Error::new(
+                            std::io::ErrorKind::TimedOut,
+                            "timed out",
+                        )));
+                    }
+                    let expired = state.block_height > LAST_VALID_BLOCK_HEIGHT;
+                    let landed = matches!(state.lands, Some(before) if before || expired);
+                    let status = landed.then_some(TransactionStatus {
+                        slot: 1,
+                        confirmations: None,
+                        status: Ok(()),
+                        err: None,
+                        confirmation_status: Some(TransactionConfirmationStatus::Finalized),
+                    });
+                    Ok(serde_json::to_value(Response {
+                        context,
+                        value: vec![status],
+                    })?)
+                }
+                RpcRequest::GetBlockHeight => {
+                    state.block_height += state.block_step;
+                    Ok(json!(state.block_height))
+                }
+                request => Err(RpcError::RpcRequestError(format!("{request}")).into()),
+            }
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "flaky".to_owned()
+        }
+    }
+
+    async fn send(state: FlakyState, max_retries: usize) -> (Result<Signature, SnsError>, usize) {
+        let rpc = FlakyRpc(Arc::new(Mutex::new(state)));
+        let client = RpcClient::new_sender(rpc.clone(), RpcClientConfig::default());
+        let payer = Keypair::new();
+        let ix = Instruction::new_with_bytes(spl_name_service::ID, &[0], vec![]);
+        let res = send_with_retries(&client, &payer.pubkey(), &[ix], &[&payer], max_retries).await;
+        let sent = rpc.0.lock().unwrap().sent.len();
+        (res, sent)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_send_with_transient_errors() {
+        // Timeouts while the transaction is in flight do not sign it again
+        let (res, sent) = send(
+            FlakyState {
+                status_errors: 5,
+                lands: Some(true),
+                block_step: 1,
+                ..Default::default()
+            },
+            3,
+        )
+        .await;
+        assert!(res.is_ok());
+        assert_eq!(sent, 1);
+
+        // Too many timeouts in a row give up without signing it again
+        let (res, sent) = send(
+            FlakyState {
+                status_errors: MAX_TRANSIENT_ERRORS + 1,
+                lands: Some(true),
+                block_step: 1,
+                ..Default::default()
+            },
+            3,
+        )
+        .await;
+        assert!(matches!(res, Err(SnsError::SolanaClient(_))));
+        assert_eq!(sent, 1);
+
+        // Lands between the last status check and the expiry of its blockhash
+        let (res, sent) = send(
+            FlakyState {
+                lands: Some(false),
+                block_step: 4,
+                ..Default::default()
+            },
+            3,
+        )
+        .await;
+        assert!(res.is_ok());
+        assert_eq!(sent, 1);
+
+        // Signed again only once its blockhash expired
+        let (res, sent) = send(
+            FlakyState {
+                lands: None,
+                block_step: 4,
+                ..Default::default()
+            },
+            1,
+        )
+        .await;
+        assert!(matches!(res, Err(SnsError::BlockhashExpired { .. })));
+        assert_eq!(sent, 2);
+    }
+
+    #[test]
+    fn test_sign_transaction() {
+        let payer = Keypair::new();
+        let owner = Keypair::new();
+        let other = Keypair::new();
+        let ix = Instruction::new_with_bytes(
+            spl_name_service::ID,
+            &[0],
+            vec![AccountMeta::new_readonly(owner.pubkey(), true)],
+        );
+
+        let tx = sign_transaction(
+            &payer.pubkey(),
+            std::slice::from_ref(&ix),
+            &[&payer, &other, &owner],
+            Hash::new_unique(),
+        )
+        .unwrap();
+        assert_eq!(tx.signatures.len(), 2);
+        tx.verify().unwrap();
+
+        let err = sign_transaction(&payer.pubkey(), &[ix], &[&payer], Hash::default()).unwrap_err();
+        assert!(matches!(
+            err,
+            SnsError::Signer(SignerError::NotEnoughSigners)
+        ));
+    }
+}
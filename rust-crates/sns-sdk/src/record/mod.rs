@@ -7,10 +7,12 @@ use solana_program::pubkey;
 use {bech32::u5, solana_program::pubkey::Pubkey};
 pub mod record_v1;
 pub mod record_v2;
+pub mod schema;
 
 pub const CENTRAL_STATE_RECORD_V2: Pubkey = pubkey!("2pMnqHvei2N5oDcVGCRdZx48gqti199wr5CsyTTafsbo");
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum Record {
     Ipfs,
     Arwv,
@@ -37,16 +39,70 @@ pub enum Record {
     CNAME,
     TXT,
     BASE,
+    /// The mint of the NFT used as the background of the domain profile
+    Background,
+    Polygon,
+    Arbitrum,
+    Sui,
+    Apt,
+    /// A Nostr public key, encoded as a `npub` bech32 string
+    Nostr,
+    Ipns,
+    /// Any other record, whose content is raw UTF-8 or bytes. Its name must not be the name of a
+    /// known record, see [`Record::from_name`]
+    Custom(String),
 }
 
-#[derive(Copy, Clone, Debug)]
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum RecordVersion {
     V1 = 1,
     V2 = 2,
 }
 
 impl Record {
-    pub fn as_str(&self) -> &'static str {
+    /// Every record type except [`Record::Custom`]
+    pub const ALL: [Record; 32] = [
+        Record::Ipfs,
+        Record::Arwv,
+        Record::Sol,
+        Record::Eth,
+        Record::Btc,
+        Record::Ltc,
+        Record::Doge,
+        Record::Email,
+        Record::Url,
+        Record::Discord,
+        Record::Github,
+        Record::Reddit,
+        Record::Twitter,
+        Record::Telegram,
+        Record::Pic,
+        Record::Shdw,
+        Record::Point,
+        Record::Bsc,
+        Record::Injective,
+        Record::Backpack,
+        Record::A,
+        Record::AAAA,
+        Record::CNAME,
+        Record::TXT,
+        Record::BASE,
+        Record::Background,
+        Record::Polygon,
+        Record::Arbitrum,
+        Record::Sui,
+        Record::Apt,
+        Record::Nostr,
+        Record::Ipns,
+    ];
+
+    pub fn as_str(&self) -> &str {
         match self {
             Record::Ipfs => "IPFS",
             Record::Arwv => "ARWV",
@@ -73,9 +129,18 @@ impl Record {
             Record::CNAME => "CNAME",
             Record::TXT => "TXT",
             Record::BASE => "BASE",
+            Record::Background => "background",
+            Record::Polygon => "POLYGON",
+            Record::Arbitrum => "ARBITRUM",
+            Record::Sui => "SUI",
+            Record::Apt => "APT",
+            Record::Nostr => "NOSTR",
+            Record::Ipns => "IPNS",
+            Record::Custom(record) => record,
         }
     }
 
+    /// Parses one of the [`Record::ALL`] record names, use [`Record::Custom`] for any other name
     pub fn try_from_str(input: &str) -> Result<Record, SnsError> {
         match input {
             "IPFS" => Ok(Record::Ipfs),
@@ -103,7 +168,34 @@ impl Record {
             "CNAME" => Ok(Record::CNAME),
             "TXT" => Ok(Record::TXT),
             "BASE" => Ok(Record::BASE),
-            _ => Err(SnsError::UnrecognizedRecord),
+            "background" => Ok(Record::Background),
+            "POLYGON" => Ok(Record::Polygon),
+            "ARBITRUM" => Ok(Record::Arbitrum),
+            "SUI" => Ok(Record::Sui),
+            "APT" => Ok(Record::Apt),
+            "NOSTR" => Ok(Record::Nostr),
+            "IPNS" => Ok(Record::Ipns),
+            _ => Err(SnsError::UnrecognizedRecord {
+                record: input.to_owned(),
+            }),
+        }
+    }
+
+    /// The record named `name`: one of [`Record::ALL`], else a [`Record::Custom`] record
+    pub fn from_name(name: &str) -> Record {
+        Record::try_from_str(name).unwrap_or_else(|_| Record::Custom(name.to_owned()))
+    }
+
+    /// Rejects [`Record::Custom`] records named like a known record, whose key is the key of the
+    /// known record but whose content would be encoded as raw bytes
+    pub(crate) fn check_custom(&self) -> Result<(), SnsError> {
+        match self {
+            Record::Custom(name) if Record::try_from_str(name).is_ok() => {
+                Err(SnsError::ReservedRecordName {
+                    record: name.clone(),
+                })
+            }
+            _ => Ok(()),
         }
     }
 
@@ -127,13 +219,20 @@ impl Record {
                 | Record::Backpack
                 | Record::TXT
                 | Record::CNAME
+                | Record::Ipns
+                | Record::Custom(_)
         )
     }
 
     pub fn roa_validation(&self) -> Validation {
         match self {
             Record::Sol | Record::CNAME | Record::Url => Validation::Solana,
-            Record::Injective | Record::Eth | Record::Bsc | Record::BASE => Validation::Ethereum,
+            Record::Injective
+            | Record::Eth
+            | Record::Bsc
+            | Record::BASE
+            | Record::Polygon
+            | Record::Arbitrum => Validation::Ethereum,
             _ => Validation::None,
         }
     }
@@ -146,38 +245,41 @@ pub fn get_record_class(record_version: RecordVersion) -> Option<Pubkey> {
     }
 }
 
+pub fn get_record_key_from_parent(
+    parent: &Pubkey,
+    record: Record,
+    record_version: RecordVersion,
+) -> Pubkey {
+    let prefix = get_prefix(Domain::Record(record_version));
+    derive(
+        &format!("{}{}", prefix, record.as_str()),
+        parent,
+        get_record_class(record_version),
+    )
+}
+
 pub fn get_record_key(
     domain: &str,
     record: Record,
     record_version: RecordVersion,
 ) -> Result<Pubkey, SnsError> {
+    record.check_custom()?;
     let domain = trim_tld(domain);
     let splitted = domain.split('.').collect::<Vec<_>>();
     match splitted.len() {
         1 => {
             let parent = derive(domain, &ROOT_DOMAIN_ACCOUNT, None);
-            let prefix = get_prefix(Domain::Record(record_version));
-            let key = derive(
-                &format!("{}{}", prefix, record.as_str()),
-                &parent,
-                get_record_class(record_version),
-            );
-            Ok(key)
+            Ok(get_record_key_from_parent(&parent, record, record_version))
         }
         2 => {
             let parent = derive(splitted[1], &ROOT_DOMAIN_ACCOUNT, None);
-            let sub_domain = get_prefix(Domain::Sub) + splitted[1];
+            let sub_domain = get_prefix(Domain::Sub) + splitted[0];
             let sub_key = derive(&sub_domain, &parent, None);
-
-            let record_prefix = get_prefix(Domain::Record(record_version));
-            let key = derive(
-                &format!("{record_prefix}{}", record.as_str()),
-                &sub_key,
-                get_record_class(record_version),
-            );
-            Ok(key)
+            Ok(get_record_key_from_parent(&sub_key, record, record_version))
         }
-        _ => Err(SnsError::InvalidDomain),
+        _ => Err(SnsError::InvalidDomain {
+            domain: domain.to_owned(),
+        }),
     }
 }
 
@@ -211,7 +313,153 @@ pub fn convert_u5_array(u5_data: &[u5]) -> Vec<u8> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use solana_sdk::pubkey;
+    use crate::derivation::get_domain_key;
+    use proptest::prelude::*;
+    use solana_sdk::{
+        pubkey,
+        signer::{keypair::keypair_from_seed, Signer},
+    };
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    /// Canonical content of `record`, i.e. as returned by the deserializers
+    fn content(record: Record) -> BoxedStrategy<String> {
+        match record {
+            Record::Sol | Record::Background => any::<[u8; 32]>()
+                .prop_map(|k| Pubkey::new_from_array(k).to_string())
+                .boxed(),
+            Record::Eth | Record::Bsc | Record::BASE | Record::Polygon | Record::Arbitrum => {
+                any::<[u8; 20]>()
+                    .prop_map(|a| format!("0x{}", hex::encode(a)))
+                    .boxed()
+            }
+            Record::Sui | Record::Apt => any::<[u8; 32]>()
+                .prop_map(|a| format!("0x{}", hex::encode(a)))
+                .boxed(),
+            Record::Nostr => any::<[u8; 32]>()
+                .prop_map(|k| {
+                    bech32::encode(
+                        "npub",
+                        bech32::ToBase32::to_base32(&k),
+                        bech32::Variant::Bech32,
+                    )
+                    .unwrap()
+                })
+                .boxed(),
+            Record::Injective => any::<[u8; 20]>()
+                .prop_map(|a| {
+                    bech32::encode(
+                        "inj",
+                        bech32::ToBase32::to_base32(&a),
+                        bech32::Variant::Bech32,
+                    )
+                    .unwrap()
+                })
+                .boxed(),
+            Record::A => any::<[u8; 4]>()
+                .prop_map(|ip| Ipv4Addr::from(ip).to_string())
+                .boxed(),
+            Record::AAAA => any::<[u8; 16]>()
+                .prop_map(|ip| Ipv6Addr::from(ip).to_string())
+                .boxed(),
+            // Zero bytes cannot be told apart from the padding of V1 records
+            _ => "[^\\x00]{0,64}".boxed(),
+        }
+    }
+
+    fn record_and_content() -> impl Strategy<Value = (Record, String)> {
+        let custom = "[a-z]{1,16}"
+            .prop_filter("known record", |name| Record::try_from_str(name).is_err())
+            .prop_map(Record::Custom);
+        prop_oneof![proptest::sample::select(Record::ALL.to_vec()), custom]
+            .prop_flat_map(|record| (Just(record.clone()), content(record)))
+    }
+
+    proptest! {
+        #[test]
+        fn test_record_v1_round_trip((record, content) in record_and_content(), padding in 0..16usize) {
+            prop_assume!(record != Record::Sol);
+            // The punycode of content without ASCII characters cannot be told apart from plain
+            // text, see `record_v1::deserialize_record`
+            prop_assume!(
+                !matches!(record, Record::CNAME | Record::TXT)
+                    || content.is_empty()
+                    || content.chars().any(|c| c.is_ascii())
+            );
+            let mut data = record_v1::serialize_record_unchecked(&content, record.clone()).unwrap();
+            data.extend(std::iter::repeat_n(0, padding));
+            let key = get_record_key("bonfida", record.clone(), RecordVersion::V1).unwrap();
+            prop_assert_eq!(record_v1::deserialize_record(&data, record, &key, &key).unwrap(), content);
+        }
+
+        #[test]
+        fn test_sol_record_v1_round_trip(content in any::<[u8; 32]>(), seed in any::<[u8; 32]>(), padding in 0..16usize) {
+            let owner = keypair_from_seed(&seed).unwrap();
+            let content = Pubkey::new_from_array(content);
+            let key = get_record_key("bonfida", Record::Sol, RecordVersion::V1).unwrap();
+            let mut data = record_v1::serialize_sol_record(&content, &key, &owner).unwrap();
+            data.extend(std::iter::repeat_n(0, padding));
+            prop_assert_eq!(
+                record_v1::deserialize_record(&data, Record::Sol, &key, &owner.pubkey()).unwrap(),
+                content.to_string()
+            );
+        }
+
+        #[test]
+        fn test_record_v2_round_trip((record, content) in record_and_content()) {
+            let data = record_v2::serialize_record_v2_content_unchecked(&content, record.clone()).unwrap();
+            prop_assert_eq!(
+                record_v2::deserialize_record_v2_content(&data, record).unwrap(),
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn test_padded_aaaa_record_v1() {
+        // IPv6 addresses ending with zero bytes cannot be told apart from the padding
+        let content = "::ed:9ef4:cf00";
+        let mut data = record_v1::serialize_record(content, Record::AAAA).unwrap();
+        data.resize(31, 0);
+        let key = get_record_key("bonfida", Record::AAAA, RecordVersion::V1).unwrap();
+        assert_eq!(
+            record_v1::deserialize_record(&data, Record::AAAA, &key, &key).unwrap(),
+            content
+        );
+    }
+
+    #[test]
+    fn test_record_names() {
+        for record in Record::ALL {
+            assert_eq!(Record::try_from_str(record.as_str()).unwrap(), record);
+        }
+        assert!(matches!(
+            Record::try_from_str("discord-id"),
+            Err(SnsError::UnrecognizedRecord { .. })
+        ));
+        assert_eq!(Record::from_name("IPNS"), Record::Ipns);
+        assert_eq!(
+            Record::from_name("discord-id"),
+            Record::Custom("discord-id".to_owned())
+        );
+        // Custom records cannot share the key of a known record
+        let record = Record::Custom("SOL".to_owned());
+        assert!(matches!(
+            get_record_key("bonfida", record.clone(), RecordVersion::V2),
+            Err(SnsError::ReservedRecordName { .. })
+        ));
+        assert!(record_v2::serialize_record_v2_content("abc", record.clone()).is_err());
+        assert!(record_v1::serialize_record("abc", record).is_err());
+        let record = Record::Custom("discord-id".to_owned());
+        assert_eq!(record.as_str(), "discord-id");
+        assert_eq!(
+            get_record_key("bonfida", record, RecordVersion::V2).unwrap(),
+            derive(
+                "\x02discord-id",
+                &get_domain_key("bonfida").unwrap(),
+                Some(CENTRAL_STATE_RECORD_V2)
+            )
+        );
+    }
 
     #[test]
     fn test_get_record_key() {
@@ -226,5 +474,11 @@ mod test {
             get_record_key(domain, Record::CNAME, RecordVersion::V2).unwrap(),
             v2
         );
+
+        let sub = get_domain_key("dex.bonfida").unwrap();
+        assert_eq!(
+            get_record_key("dex.bonfida.sol", Record::CNAME, RecordVersion::V2).unwrap(),
+            get_record_key_from_parent(&sub, Record::CNAME, RecordVersion::V2)
+        );
     }
 }
@@ -1,14 +1,25 @@
-use super::Record;
-use crate::error::SnsError;
+use super::{get_record_key, schema::validate_content, Record, RecordVersion};
+use crate::{
+    derivation::{get_domain_key, get_hashed_name, get_prefix, Domain},
+    error::SnsError,
+};
 use {
     bech32,
     bech32::u5,
     bech32::ToBase32,
     ed25519_dalek,
-    solana_program::pubkey::Pubkey,
-    std::net::{Ipv4Addr, Ipv6Addr},
+    solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey, rent::Rent},
+    solana_sdk::signer::Signer,
+    spl_name_service::{instruction::NameRegistryInstruction, state::NameRecordHeader},
+    std::{
+        net::{Ipv4Addr, Ipv6Addr},
+        str::FromStr,
+    },
 };
 
+/// Size of the data of a V1 SOL record: the content followed by the signature of the domain owner
+pub const SOL_RECORD_LEN: usize = 96;
+
 pub fn check_sol_record(
     record: &[u8],
     signed_record: &[u8],
@@ -20,82 +31,78 @@ pub fn check_sol_record(
     Ok(res)
 }
 
-pub fn get_record_size(record: Record) -> Option<usize> {
+/// Returns the address of a SOL record, whose data is the address followed by the signature of
+/// [`get_sol_record_message`] by `owner`, the registry owner of the domain. Records signed by
+/// anyone else, e.g. a previous owner of the domain, are unverified
+pub fn verify_sol_record(
+    data: &[u8],
+    record_key: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Pubkey, SnsError> {
+    let (content, signature) = data
+        .get(..SOL_RECORD_LEN)
+        .and_then(|data| data.split_first_chunk::<32>())
+        .ok_or(SnsError::InvalidRecordData {
+            record: Record::Sol,
+        })?;
+    let content = Pubkey::new_from_array(*content);
+    let message = get_sol_record_message(&content, record_key);
+    match check_sol_record(&message, signature, *owner) {
+        Ok(true) => Ok(content),
+        _ => Err(SnsError::UnverifiedRecord {
+            record: Record::Sol,
+        }),
+    }
+}
+
+pub fn get_record_size(record: &Record) -> Option<usize> {
     match record {
-        Record::Sol => Some(96),
+        Record::Sol => Some(SOL_RECORD_LEN),
         Record::Eth | Record::Bsc | Record::Injective => Some(20),
         Record::A => Some(4),
         Record::AAAA => Some(16),
+        Record::Background => Some(32),
         _ => None,
     }
 }
 
+/// Deserializes the content of a V1 record. `owner` is the registry owner of the domain, which
+/// must have signed SOL records
 pub fn deserialize_record(
     data: &[u8],
     record: Record,
     record_key: &Pubkey,
+    owner: &Pubkey,
 ) -> Result<String, SnsError> {
-    let size = get_record_size(record);
-
-    if size.is_none() {
-        let des = String::from_utf8(data.to_vec())?
-            .trim_end_matches('\0')
-            .to_string();
-        return Ok(des);
-    }
-
-    let size = size.unwrap();
     let idx = data
         .iter()
         .rposition(|&byte| byte != 0)
         .map_or(0, |pos| pos + 1);
 
+    let Some(size) = get_record_size(&record) else {
+        let des = String::from_utf8(data[..idx].to_vec())?;
+        if matches!(record, Record::CNAME | Record::TXT) {
+            return Ok(decode_punycode(des));
+        }
+        return Ok(des);
+    };
+
     // Old record UTF-8 encoded
     if size != idx {
-        let address = String::from_utf8(data[0..idx].to_vec())?;
-        match record {
-            Record::Injective => {
-                let (prefix, data, _) = bech32::decode(&address)?;
-                if prefix == "inj" && data.len() == 32 {
-                    return Ok(address);
-                }
-            }
-            Record::Eth | Record::Bsc => {
-                let prefix = address.get(0..2).ok_or(SnsError::InvalidRecordData)?;
-                let hex = address.get(2..).ok_or(SnsError::InvalidRecordData)?;
-                let decoded = hex::decode(hex)?;
-                if prefix == "0x" && decoded.len() == 20 {
-                    return Ok(address);
-                }
-            }
-            Record::A => {
-                let des = address.parse::<Ipv4Addr>();
-                if des.is_ok() {
-                    return Ok(address);
-                }
-            }
-            Record::AAAA => {
-                let des = address.parse::<Ipv6Addr>();
-                if des.is_ok() {
-                    return Ok(address);
-                }
-            }
-            _ => {}
+        if let Some(address) = deserialize_legacy_record(&data[..idx], &record) {
+            return Ok(address);
+        }
+        // Otherwise, only properly sized content ending with zero bytes is valid, e.g. 10.0.0.0
+        if idx > size || data.len() < size {
+            return Err(SnsError::InvalidRecordData { record });
         }
-        return Err(SnsError::InvalidReverse);
     }
 
-    // Properly sized record
+    // Properly sized record, without the zero padding
+    let data = &data[..size];
     match record {
         Record::Sol => {
-            let signature = data.get(32..).ok_or(SnsError::InvalidRecordData)?;
-            let dst = data.get(0..32).ok_or(SnsError::InvalidRecordData)?;
-            let expected = [dst, &record_key.to_bytes()].concat();
-            let valid = check_sol_record(&expected, signature, *record_key)?;
-            if valid {
-                let pubkey = Pubkey::new_from_array(dst.try_into().unwrap());
-                return Ok(pubkey.to_string());
-            }
+            return verify_sol_record(data, record_key, owner).map(|dst| dst.to_string());
         }
         Record::Eth | Record::Bsc => {
             let des = format!("0x{}", hex::encode(data));
@@ -106,28 +113,85 @@ pub fn deserialize_record(
             return Ok(des);
         }
         Record::A => {
-            let bytes: [u8; 4] = data.try_into().unwrap();
+            let bytes: [u8; 4] = data.try_into()?;
             let ip = Ipv4Addr::from(bytes);
             return Ok(ip.to_string());
         }
         Record::AAAA => {
-            let bytes: [u8; 16] = data.try_into().unwrap();
+            let bytes: [u8; 16] = data.try_into()?;
             let ip = Ipv6Addr::from(bytes);
             return Ok(ip.to_string());
         }
+        Record::Background => {
+            let bytes: [u8; 32] = data.try_into()?;
+            return Ok(Pubkey::new_from_array(bytes).to_string());
+        }
         _ => {}
     }
 
-    Err(SnsError::InvalidRecordData)
+    Err(SnsError::InvalidRecordData { record })
+}
+
+/// Decodes the content of a V1 CNAME or TXT record written by [`serialize_record`], or returns it
+/// as is when it was written as plain text by another tool.
+///
+/// Plain text is often valid punycode too, e.g. `hello` decodes to `㗁㖼㖶`. The content is only
+/// decoded when it holds the `-` delimiter that punycode adds after ASCII characters and encoding
+/// the decoded value again gives back the content. Content without any ASCII character is thus
+/// returned encoded, e.g. `wgv71a` for `日本`
+fn decode_punycode(content: String) -> String {
+    if !content.contains('-') {
+        return content;
+    }
+    match punycode::decode(&content) {
+        Ok(decoded) if punycode::encode(&decoded).is_ok_and(|e| e == content) => decoded,
+        _ => content,
+    }
+}
+
+/// Records written before their binary encoding hold the address as a string
+fn deserialize_legacy_record(data: &[u8], record: &Record) -> Option<String> {
+    let address = std::str::from_utf8(data).ok()?;
+    let valid = match record {
+        Record::Injective => bech32::decode(address)
+            .is_ok_and(|(prefix, data, _)| prefix == "inj" && data.len() == 32),
+        Record::Eth | Record::Bsc => address
+            .strip_prefix("0x")
+            .and_then(|hex| hex::decode(hex).ok())
+            .is_some_and(|decoded| decoded.len() == 20),
+        Record::A => address.parse::<Ipv4Addr>().is_ok(),
+        Record::AAAA => address.parse::<Ipv6Addr>().is_ok(),
+        Record::Background => Pubkey::from_str(address).is_ok(),
+        _ => false,
+    };
+    valid.then(|| address.to_owned())
 }
 
+/// Serializes the content of a V1 record after checking it with [`validate_content`].
+///
+/// CNAME and TXT records are punycode encoded like in the other SNS SDKs, so ASCII content gets a
+/// trailing `-`, e.g. `this is a test-`, which the deserializers remove
 pub fn serialize_record(content: &str, record: Record) -> Result<Vec<u8>, SnsError> {
-    let size = get_record_size(record);
+    validate_content(&record, content)?;
+    serialize_record_unchecked(content, record)
+}
+
+/// Same as [`serialize_record`] without the schema validation, the content only has to be
+/// encodable
+pub fn serialize_record_unchecked(content: &str, record: Record) -> Result<Vec<u8>, SnsError> {
+    record.check_custom()?;
+    let invalid = |reason| SnsError::InvalidRecordContent {
+        record: record.clone(),
+        content: content.to_owned(),
+        reason,
+    };
+    let size = get_record_size(&record);
 
     if size.is_none() {
         match record {
             Record::CNAME | Record::TXT => {
-                let encoded = punycode::encode(content).map_err(|_| SnsError::Punycode)?;
+                let encoded =
+                    punycode::encode(content).map_err(|_| invalid("cannot be punycode encoded"))?;
                 return Ok(encoded.as_bytes().to_vec());
             }
             _ => return Ok(content.as_bytes().to_vec()),
@@ -137,42 +201,148 @@ pub fn serialize_record(content: &str, record: Record) -> Result<Vec<u8>, SnsErr
     match record {
         Record::Eth | Record::Bsc => {
             if !content.starts_with("0x") {
-                return Err(SnsError::InvalidEvmAddress);
+                return Err(invalid("missing 0x prefix"));
             }
-            let decoded = hex::decode(content.get(2..).ok_or(SnsError::InvalidEvmAddress)?)?;
+            let decoded = hex::decode(&content[2..]).map_err(|_| invalid("invalid hex"))?;
             if decoded.len() != 20 {
-                return Err(SnsError::InvalidEvmAddress);
+                return Err(invalid("expected a 20 bytes address"));
             }
             Ok(decoded)
         }
         Record::Injective => {
             if !content.starts_with("inj") {
-                return Err(SnsError::InvalidInjectiveAddress);
+                return Err(invalid("missing inj prefix"));
             }
-            let (_, data, _) = bech32::decode(content)?;
+            let (_, data, _) = bech32::decode(content).map_err(|_| invalid("invalid bech32"))?;
             let data = convert_u5_array(&data);
             if data.len() != 20 {
-                return Err(SnsError::InvalidInjectiveAddress);
+                return Err(invalid("expected a 20 bytes address"));
             }
             Ok(data)
         }
         Record::A => {
             let ip = content
                 .parse::<Ipv4Addr>()
-                .map_err(|_| SnsError::InvalidIpv4)?;
+                .map_err(|_| invalid("invalid IPv4 address"))?;
             Ok(ip.octets().to_vec())
         }
         Record::AAAA => {
             let ip = content
                 .parse::<Ipv6Addr>()
-                .map_err(|_| SnsError::InvalidIpv6)?;
+                .map_err(|_| invalid("invalid IPv6 address"))?;
             Ok(ip.octets().to_vec())
         }
-        Record::Sol => Err(SnsError::SolRecordNotSupported),
+        Record::Background => {
+            let pubkey = Pubkey::from_str(content).map_err(|_| invalid("invalid public key"))?;
+            Ok(pubkey.to_bytes().to_vec())
+        }
+        // SOL records are signed, see `serialize_sol_record`
+        Record::Sol => Err(SnsError::UnsupportedRecordVersion {
+            record,
+            version: RecordVersion::V1,
+        }),
         _ => unreachable!(),
     }
 }
 
+/// The message signed by the domain owner in a SOL record: the hex encoding of the content
+/// followed by the record key. Hardware and multisig wallets sign it with `sign_message`
+pub fn get_sol_record_message(content: &Pubkey, record_key: &Pubkey) -> Vec<u8> {
+    hex::encode([content.as_ref(), record_key.as_ref()].concat()).into_bytes()
+}
+
+/// Builds the data of a SOL record pointing to `content`, signed by `signer`, the domain owner
+pub fn serialize_sol_record(
+    content: &Pubkey,
+    record_key: &Pubkey,
+    signer: &dyn Signer,
+) -> Result<Vec<u8>, SnsError> {
+    let message = get_sol_record_message(content, record_key);
+    let signature = signer.try_sign_message(&message)?;
+    serialize_sol_record_with_signature(content, record_key, &signer.pubkey(), signature.as_ref())
+}
+
+/// Builds the data of a SOL record from a signature of [`get_sol_record_message`] made outside
+/// of the SDK, checking that it was signed by `signer`
+pub fn serialize_sol_record_with_signature(
+    content: &Pubkey,
+    record_key: &Pubkey,
+    signer: &Pubkey,
+    signature: &[u8],
+) -> Result<Vec<u8>, SnsError> {
+    let message = get_sol_record_message(content, record_key);
+    if !check_sol_record(&message, signature, *signer)? {
+        return Err(SnsError::UnverifiedRecord {
+            record: Record::Sol,
+        });
+    }
+    Ok([content.as_ref(), signature].concat())
+}
+
+/// Builds the instructions creating the SOL record of `domain` pointing to `content`, signed by
+/// `signer`, the domain owner, which also owns the record. Its rent is paid by `payer`
+pub fn create_sol_record_instructions(
+    domain: &str,
+    content: &Pubkey,
+    signer: &Pubkey,
+    signature: &[u8],
+    payer: &Pubkey,
+) -> Result<Vec<Instruction>, SnsError> {
+    let key = get_record_key(domain, Record::Sol, RecordVersion::V1)?;
+    let data = serialize_sol_record_with_signature(content, &key, signer, signature)?;
+    let hashed_name =
+        get_hashed_name(&(get_prefix(Domain::Record(RecordVersion::V1)) + Record::Sol.as_str()));
+    let create = spl_name_service::instruction::create(
+        spl_name_service::ID,
+        NameRegistryInstruction::Create {
+            hashed_name,
+            lamports: Rent::default().minimum_balance(NameRecordHeader::LEN + SOL_RECORD_LEN),
+            space: SOL_RECORD_LEN as u32,
+        },
+        key,
+        *payer,
+        *signer,
+        None,
+        Some(get_domain_key(domain)?),
+        Some(*signer),
+    )?;
+    let update =
+        spl_name_service::instruction::update(spl_name_service::ID, 0, data, key, *signer, None)?;
+    Ok(vec![create, update])
+}
+
+/// Builds the instructions updating the existing SOL record of `domain`, whose data is
+/// `current_len` bytes long. Records of another size, e.g. written as a string by older tools,
+/// are deleted and created again with the rent paid by `payer`
+pub fn update_sol_record_instructions(
+    domain: &str,
+    content: &Pubkey,
+    signer: &Pubkey,
+    signature: &[u8],
+    payer: &Pubkey,
+    current_len: usize,
+) -> Result<Vec<Instruction>, SnsError> {
+    let key = get_record_key(domain, Record::Sol, RecordVersion::V1)?;
+    if current_len != SOL_RECORD_LEN {
+        let delete =
+            spl_name_service::instruction::delete(spl_name_service::ID, key, *signer, *payer)?;
+        let mut ixs = vec![delete];
+        ixs.extend(create_sol_record_instructions(
+            domain, content, signer, signature, payer,
+        )?);
+        return Ok(ixs);
+    }
+    let data = serialize_sol_record_with_signature(content, &key, signer, signature)?;
+    Ok(vec![spl_name_service::instruction::update(
+        spl_name_service::ID,
+        0,
+        data,
+        key,
+        *signer,
+        None,
+    )?])
+}
+
 pub fn convert_u5_array(u5_data: &[u5]) -> Vec<u8> {
     let mut u8_data: Vec<u8> = Vec::new();
     let mut buffer: u16 = 0;
@@ -195,6 +365,85 @@ pub fn convert_u5_array(u5_data: &[u5]) -> Vec<u8> {
 #[cfg(test)]
 mod test {
     use super::*;
+
+    #[test]
+    fn test_deserialize_malformed_record() {
+        let key = Pubkey::default();
+        let owner = Pubkey::default();
+        // Properly sized records followed by padding
+        assert_eq!(
+            deserialize_record(&[1, 2, 3, 4, 0, 0], Record::A, &key, &owner).unwrap(),
+            "1.2.3.4"
+        );
+        let mut eth = vec![1; 20];
+        eth.extend_from_slice(&[0; 12]);
+        assert_eq!(
+            deserialize_record(&eth, Record::Eth, &key, &owner).unwrap(),
+            format!("0x{}", "01".repeat(20))
+        );
+        assert!(deserialize_record(&[1; 96], Record::Sol, &key, &owner).is_err());
+        assert!(deserialize_record(&[0xff; 3], Record::A, &key, &owner).is_err());
+        assert!(deserialize_record(&[0xff; 3], Record::Url, &key, &owner).is_err());
+        assert!(matches!(
+            deserialize_record(b"0xinvalid", Record::Eth, &key, &owner),
+            Err(SnsError::InvalidRecordData {
+                record: Record::Eth
+            })
+        ));
+        // Binary content ending with zero bytes is not a legacy record
+        assert_eq!(
+            deserialize_record(&[10, 0, 0, 0], Record::A, &key, &owner).unwrap(),
+            "10.0.0.0"
+        );
+        assert_eq!(
+            deserialize_record(b"1.1.1.1", Record::A, &key, &owner).unwrap(),
+            "1.1.1.1"
+        );
+    }
+
+    #[test]
+    fn test_txt_record() {
+        let key = Pubkey::default();
+        let owner = Pubkey::default();
+        let data = serialize_record("this is a test", Record::TXT).unwrap();
+        assert_eq!(data, b"this is a test-");
+        assert_eq!(
+            deserialize_record(&data, Record::TXT, &key, &owner).unwrap(),
+            "this is a test"
+        );
+
+        // Plain text written without punycode
+        for (record, content) in [
+            (Record::TXT, "hello"),
+            (Record::TXT, "v=spf1 include:_spf.sns.id ~all"),
+            (Record::CNAME, "example.com"),
+            (Record::CNAME, "my-site.com"),
+        ] {
+            assert_eq!(
+                deserialize_record(content.as_bytes(), record, &key, &owner).unwrap(),
+                content
+            );
+        }
+        assert_eq!(
+            deserialize_record(b"maana-pta", Record::TXT, &key, &owner).unwrap(),
+            "mañana"
+        );
+    }
+
+    #[test]
+    fn test_btc_record() {
+        let key = Pubkey::default();
+        let owner = Pubkey::default();
+        // BTC records hold the address as a string, like in the other SNS SDKs
+        let content = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let mut data = serialize_record(content, Record::Btc).unwrap();
+        assert_eq!(data, content.as_bytes());
+        data.resize(64, 0);
+        assert_eq!(
+            deserialize_record(&data, Record::Btc, &key, &owner).unwrap(),
+            content
+        );
+    }
     #[test]
     fn test_serialize_record() {
         let data = serialize_record(
@@ -214,6 +463,147 @@ mod test {
         assert_eq!(data, [192, 168, 0, 1].to_vec());
     }
 
+    #[test]
+    fn test_serialize_sol_record() {
+        use solana_sdk::signature::Keypair;
+
+        let owner = Keypair::new();
+        let content = Pubkey::new_unique();
+        let key = get_record_key("bonfida", Record::Sol, RecordVersion::V1).unwrap();
+        let message = get_sol_record_message(&content, &key);
+        assert_eq!(message.len(), 128);
+
+        let data = serialize_sol_record(&content, &key, &owner).unwrap();
+        assert_eq!(data.len(), SOL_RECORD_LEN);
+        assert_eq!(&data[..32], content.as_ref());
+        assert!(check_sol_record(&message, &data[32..], owner.pubkey()).unwrap());
+
+        // Signed by an external wallet
+        let signature = owner.sign_message(&message);
+        assert_eq!(
+            serialize_sol_record_with_signature(
+                &content,
+                &key,
+                &owner.pubkey(),
+                signature.as_ref()
+            )
+            .unwrap(),
+            data
+        );
+        // Not every address is a valid ed25519 key, the other signer must be one
+        let other = Keypair::new().pubkey();
+        assert!(matches!(
+            serialize_sol_record_with_signature(&content, &key, &other, signature.as_ref()),
+            Err(SnsError::UnverifiedRecord { .. })
+        ));
+
+        let payer = Pubkey::new_unique();
+        let ixs = create_sol_record_instructions(
+            "bonfida",
+            &content,
+            &owner.pubkey(),
+            signature.as_ref(),
+            &payer,
+        )
+        .unwrap();
+        assert_eq!(ixs.len(), 2);
+        assert!(ixs.iter().all(|ix| ix.program_id == spl_name_service::ID
+            && ix.accounts.iter().any(|a| a.pubkey == key)));
+        let update = |len| {
+            update_sol_record_instructions(
+                "bonfida",
+                &content,
+                &owner.pubkey(),
+                signature.as_ref(),
+                &payer,
+                len,
+            )
+            .unwrap()
+        };
+        assert_eq!(update(SOL_RECORD_LEN).len(), 1);
+        assert_eq!(update(44).len(), 3);
+    }
+
+    #[test]
+    fn test_verify_sol_record() {
+        use solana_program::pubkey;
+        use solana_sdk::signature::Keypair;
+        use solana_sdk::signer::keypair::keypair_from_seed;
+
+        // A synthetic SOL record V1 for the key of `wallet-guide-4`, pointing to `content` and
+        // signed by the keypair of seed `[1; 32]`. It is not the record of the domain on mainnet
+        // and `owner` is not its registry owner: the bytes are fixed so that a change of the
+        // signed message is caught, `test_resolve_record` checks the live record
+        let owner = pubkey!("AKnL4NNf3DGWZJS6cPknBuEGnVsV4A4m5tgebLHaRSZ9");
+        assert_eq!(keypair_from_seed(&[1; 32]).unwrap().pubkey(), owner);
+        let content = pubkey!("Hf4daCT4tC2Vy9RCe9q8avT68yAsNJ1dQe6xiQqyGuqZ");
+        let data = hex::decode(
+            "f77b2aa9adfb9609a9dc2c33950023e8c63eb997e5908ef6950887e3ddd74b68\
+             10ec2c17cfceb1ae2340a87f6d4217fc3401816f47bb1cdc4044057120a7ae9e\
+             b6f90ccf8695a7f5005364b5520d996eb9e0b02796f5a7d16bfeec9615763202",
+        )
+        .unwrap();
+        let key = get_record_key("wallet-guide-4", Record::Sol, RecordVersion::V1).unwrap();
+
+        assert_eq!(verify_sol_record(&data, &key, &owner).unwrap(), content);
+        // SOL record accounts are usually allocated with more space than needed
+        let mut padded = data.clone();
+        padded.resize(1_000, 0);
+        assert_eq!(
+            deserialize_record(&padded, Record::Sol, &key, &owner).unwrap(),
+            content.to_string()
+        );
+        // One byte flipped in the content, then in the signature
+        for i in [0, 63] {
+            let mut data = data.clone();
+            data[i] ^= 1;
+            assert!(matches!(
+                verify_sol_record(&data, &key, &owner),
+                Err(SnsError::UnverifiedRecord {
+                    record: Record::Sol
+                })
+            ));
+        }
+        // Signed by a previous owner of the domain
+        assert!(matches!(
+            verify_sol_record(&data, &key, &Keypair::new().pubkey()),
+            Err(SnsError::UnverifiedRecord {
+                record: Record::Sol
+            })
+        ));
+        // Copied from the SOL record of another domain
+        let other_key = get_record_key("bonfida", Record::Sol, RecordVersion::V1).unwrap();
+        assert!(matches!(
+            deserialize_record(&data, Record::Sol, &other_key, &owner),
+            Err(SnsError::UnverifiedRecord { .. })
+        ));
+        assert!(matches!(
+            verify_sol_record(&data[..95], &key, &owner),
+            Err(SnsError::InvalidRecordData { .. })
+        ));
+    }
+
+    #[test]
+    fn test_background_record() {
+        // Written by the JS SDK as the 32 bytes of the NFT mint
+        let mint = "BMQMSDvsWcKC9oEpiNf9c7WCv5jJ1zwcwQwhoPQbCcYv";
+        let key = Pubkey::default();
+        let ser = serialize_record(mint, Record::Background).unwrap();
+        assert_eq!(ser, Pubkey::from_str(mint).unwrap().to_bytes());
+        let mut data = ser.clone();
+        data.resize(64, 0);
+        assert_eq!(
+            deserialize_record(&data, Record::Background, &key, &key).unwrap(),
+            mint
+        );
+        // Written as base58 text by previous versions of this SDK
+        assert_eq!(
+            deserialize_record(mint.as_bytes(), Record::Background, &key, &key).unwrap(),
+            mint
+        );
+        assert!(serialize_record("not a pubkey", Record::Background).is_err());
+    }
+
     #[test]
     fn test_convert_u5_array() {
         let expected = [
@@ -1,19 +1,33 @@
-use borsh::BorshDeserialize;
 use sns_records::state::{
     record_header::RecordHeader,
     validation::{get_validation_length, Validation},
 };
 use solana_program::{program_pack::Pack, pubkey};
 
-use super::{convert_u5_array, get_record_key, Record};
+use super::{convert_u5_array, get_record_key, schema::validate_content, Record, RecordVersion};
+#[cfg(feature = "blocking")]
+use crate::blocking::resolve::{
+    get_registry_owner, resolve_name_registry, resolve_name_registry_batch,
+};
+#[cfg(not(feature = "blocking"))]
+use crate::non_blocking::resolve::{
+    get_registry_owner, resolve_name_registry, resolve_name_registry_batch,
+};
 use crate::{
+    bridge::Owner,
+    derivation::{get_domain_key, get_prefix, Domain},
     error::SnsError,
-    non_blocking::resolve::{resolve_name_registry, resolve_name_registry_batch},
 };
 use {
-    bech32::ToBase32,
-    solana_client::nonblocking::rpc_client::RpcClient,
-    solana_program::pubkey::Pubkey,
+    bech32::{FromBase32, ToBase32},
+    sns_records::instruction::{
+        allocate_and_post_record, delete_record, edit_record, validate_ethereum_signature,
+        validate_solana_signature,
+    },
+    solana_program::{
+        instruction::Instruction, keccak, pubkey::Pubkey, secp256k1_recover::secp256k1_recover,
+        system_program,
+    },
     spl_name_service::state::NameRecordHeader,
     std::net::{Ipv4Addr, Ipv6Addr},
     std::str::FromStr,
@@ -25,47 +39,28 @@ pub struct ParsedRecord<'a> {
     pub roa_id: &'a [u8],
     pub staleness_id: &'a [u8],
     pub content: String,
+    /// The serialized content, e.g. the raw bytes of a [`Record::Custom`] record
+    pub raw_content: &'a [u8],
 }
 
 pub const GUARDIAN_ID: Pubkey = pubkey!("ExXjtfdQe8JacoqP9Z535WzQKjF4CzW1TTRKRgpxvya3");
 
 impl<'a> ParsedRecord<'a> {
-    pub fn verify_staleness(
-        &self,
-        domain_owner_key: Pubkey,
-        domain_owner_account_data: Option<&[u8]>,
-    ) -> Result<(), SnsError> {
-        if domain_owner_key == sns_warp_common::constants::EMITTER_KEY {
-            // The domain is XChain-owned
-            if self.header.staleness_validation != Validation::XChain as u16 {
-                return Err(SnsError::StaleRecord);
-            }
-            let domain_owner_account_data =
-                domain_owner_account_data.ok_or(SnsError::StaleRecord)?;
-            let xchain_record = sns_warp_common::state::x_domain::XDomain::try_from_slice(
-                domain_owner_account_data,
-            )?;
-            let expected_owner_chain = u16::from_le_bytes(
-                self.staleness_id
-                    .get(..2)
-                    .ok_or(SnsError::InvalidRecordData)?
-                    .try_into()
-                    .unwrap(),
-            );
-            let expected_owner_address = self
-                .staleness_id
-                .get(2..)
-                .ok_or(SnsError::InvalidRecordData)?;
-            if expected_owner_chain != xchain_record.owner_chain
-                || expected_owner_address != xchain_record.owner_address
-            {
-                return Err(SnsError::StaleRecord);
-            }
-        }
-        if self.header.staleness_validation != Validation::Solana as u16
-            || self.staleness_id != domain_owner_key.as_ref()
-        {
-            return Err(SnsError::StaleRecord);
+    /// Checks that the record was signed by `owner`, the owner of the domain name registry as
+    /// returned by `get_registry_owner`. Records of bridged domains are signed on the foreign
+    /// chain, their staleness id being the chain id followed by the owner address
+    pub fn verify_staleness(&self, owner: &Owner) -> Result<(), SnsError> {
+        let (validation, id) = match owner {
+            Owner::Solana(key) => (Validation::Solana, key.to_bytes().to_vec()),
+            Owner::Foreign { chain, address } => (
+                Validation::XChain,
+                [chain.to_le_bytes().as_slice(), address].concat(),
+            ),
+        };
+        if self.header.staleness_validation != validation as u16 || self.staleness_id != id {
+            return Err(SnsError::StaleRecord {
+                record: self.kind.clone(),
+            });
         }
         Ok(())
     }
@@ -73,69 +68,417 @@ impl<'a> ParsedRecord<'a> {
     pub fn verify_roa(&self) -> Result<(), SnsError> {
         let validation = self.kind.roa_validation();
         if validation as u16 != self.header.right_of_association_validation {
-            return Err(SnsError::UnverifiedRecord);
+            return Err(SnsError::UnverifiedRecord {
+                record: self.kind.clone(),
+            });
         }
         if matches!(self.kind, Record::CNAME | Record::Url) && self.roa_id != GUARDIAN_ID.as_ref() {
-            return Err(SnsError::UnverifiedRecord);
+            return Err(SnsError::UnverifiedRecord {
+                record: self.kind.clone(),
+            });
+        }
+        // The RoA of SOL and EVM records is signed by the address they contain
+        if matches!(self.kind, Record::Sol) || matches!(validation, Validation::Ethereum) {
+            let address = serialize_record_v2_content_unchecked(&self.content, self.kind.clone())
+                .map_err(|_| SnsError::UnverifiedRecord {
+                record: self.kind.clone(),
+            })?;
+            if address != self.roa_id {
+                return Err(SnsError::UnverifiedRecord {
+                    record: self.kind.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks an Ethereum signature of the record content, e.g. provided by the user linking an
+    /// EVM address, by recovering its signer and comparing it with the RoA id of the record
+    pub fn verify_eth_signature(
+        &self,
+        record_key: &Pubkey,
+        signature: &[u8],
+    ) -> Result<(), SnsError> {
+        let unverified = SnsError::UnverifiedRecord {
+            record: self.kind.clone(),
+        };
+        let staleness_id =
+            Pubkey::try_from(self.staleness_id).map_err(|_| SnsError::StaleRecord {
+                record: self.kind.clone(),
+            })?;
+        let content = serialize_record_v2_content_unchecked(&self.content, self.kind.clone())?;
+        let message = get_eth_roa_message(&content, record_key, &staleness_id);
+        let address = recover_eth_address(&message, signature).map_err(|_| unverified)?;
+        if address != self.roa_id {
+            return Err(SnsError::UnverifiedRecord {
+                record: self.kind.clone(),
+            });
         }
         Ok(())
     }
 }
 
+/// The message signed with an Ethereum wallet (`personal_sign`) to verify the right of association
+/// of a record: its hex encoded content, the record key and its staleness id, i.e. the domain
+/// owner that validated the record
+pub fn get_eth_roa_message(content: &[u8], record_key: &Pubkey, staleness_id: &Pubkey) -> Vec<u8> {
+    [
+        hex::encode(content).as_bytes(),
+        validate_ethereum_signature::RECORD_SUFFIX,
+        record_key.to_string().as_bytes(),
+        validate_ethereum_signature::STALENESS_SUFFIX,
+        staleness_id.to_string().as_bytes(),
+    ]
+    .concat()
+}
+
+/// Recovers the Ethereum address that signed `message` with `personal_sign`. The signature is
+/// 65 bytes long, its recovery id being 27 or 28
+pub fn recover_eth_address(message: &[u8], signature: &[u8]) -> Result<[u8; 20], SnsError> {
+    let invalid = || SnsError::InvalidSignature {
+        signature: hex::encode(signature),
+    };
+    let (signature, recovery_id) = match signature {
+        [signature @ .., v] if signature.len() == 64 => {
+            (signature, v.checked_sub(27).ok_or_else(invalid)?)
+        }
+        _ => return Err(invalid()),
+    };
+    let hash = keccak::hashv(&[
+        validate_ethereum_signature::ETH_PREFIX_BYTES,
+        message.len().to_string().as_bytes(),
+        message,
+    ]);
+    let pubkey = secp256k1_recover(hash.as_ref(), recovery_id, signature).map_err(|_| invalid())?;
+    let hash = keccak::hash(&pubkey.0);
+    let mut address = [0; 20];
+    address.copy_from_slice(&hash.as_ref()[12..]);
+    Ok(address)
+}
+
 pub async fn parse_record_v2(
     record: Record,
     account_data: &[u8],
-) -> Result<ParsedRecord, SnsError> {
-    let record_header = RecordHeader::from_buffer(account_data);
-    let mut offset = spl_name_service::state::NameRecordHeader::LEN + RecordHeader::LEN;
+) -> Result<ParsedRecord<'_>, SnsError> {
+    let mut offset = spl_name_service::state::NameRecordHeader::LEN;
+    let record_header = account_data
+        .get(offset..offset + RecordHeader::LEN)
+        .map(bytemuck::pod_read_unaligned::<RecordHeader>)
+        .ok_or(SnsError::InvalidRecordData {
+            record: record.clone(),
+        })?;
+    offset += RecordHeader::LEN;
     let roa_validation = Validation::try_from(record_header.right_of_association_validation)?;
     let staleness_validation = Validation::try_from(record_header.staleness_validation)?;
-    let mut length = get_validation_length(roa_validation) as usize;
-    let roa_id = account_data
-        .get(offset..offset + length)
-        .ok_or(SnsError::InvalidRecordData)?;
+    // The staleness id is written before the RoA id
+    let mut length = get_validation_length(staleness_validation) as usize;
+    let staleness_id =
+        account_data
+            .get(offset..offset + length)
+            .ok_or(SnsError::InvalidRecordData {
+                record: record.clone(),
+            })?;
     offset += length;
-    length = get_validation_length(staleness_validation) as usize;
-    let staleness_id = account_data
+    length = get_validation_length(roa_validation) as usize;
+    let roa_id = account_data
         .get(offset..offset + length)
-        .ok_or(SnsError::InvalidRecordData)?;
+        .ok_or(SnsError::InvalidRecordData {
+            record: record.clone(),
+        })?;
     offset += length;
-    let content = deserialize_record_v2_content(
-        account_data
-            .get(offset..)
-            .ok_or(SnsError::InvalidRecordData)?,
-        record,
-    )?;
+    let raw_content = account_data
+        .get(offset..)
+        .ok_or(SnsError::InvalidRecordData {
+            record: record.clone(),
+        })?;
+    let content = deserialize_record_v2_content(raw_content, record.clone())?;
     Ok(ParsedRecord {
         kind: record,
         header: record_header,
         roa_id,
         staleness_id,
         content,
+        raw_content,
     })
 }
 
+/// The accounts shared by the instructions of the records program
+struct RecordV2Accounts {
+    record: Pubkey,
+    domain: Pubkey,
+    name: String,
+}
+
+impl RecordV2Accounts {
+    fn new(domain: &str, record: &Record) -> Result<Self, SnsError> {
+        Ok(Self {
+            record: get_record_key(domain, record.clone(), RecordVersion::V2)?,
+            domain: get_domain_key(domain)?,
+            name: get_prefix(Domain::Record(RecordVersion::V2)) + record.as_str(),
+        })
+    }
+}
+
+/// Builds the instruction creating the V2 `record` of `domain` with `content`.
+///
+/// The record is signed by `owner`, the current owner of the domain, and its rent is paid by
+/// `payer`
+pub fn create_record_v2_instruction(
+    domain: &str,
+    record: Record,
+    content: &str,
+    owner: Pubkey,
+    payer: Pubkey,
+) -> Result<Instruction, SnsError> {
+    let data = serialize_record_v2_content(content, record.clone())?;
+    create_record_v2_instruction_with_data(domain, record, data, owner, payer)
+}
+
+/// Same as [`create_record_v2_instruction`] with already serialized content, e.g. the raw bytes
+/// of a [`Record::Custom`] record
+pub fn create_record_v2_instruction_with_data(
+    domain: &str,
+    record: Record,
+    data: Vec<u8>,
+    owner: Pubkey,
+    payer: Pubkey,
+) -> Result<Instruction, SnsError> {
+    let keys = RecordV2Accounts::new(domain, &record)?;
+    Ok(allocate_and_post_record(
+        allocate_and_post_record::Accounts {
+            system_program: &system_program::ID,
+            spl_name_service_program: &spl_name_service::ID,
+            fee_payer: &payer,
+            record: &keys.record,
+            domain: &keys.domain,
+            domain_owner: &owner,
+            central_state: &sns_records::central_state::KEY,
+        },
+        allocate_and_post_record::Params {
+            record: keys.name,
+            content: data,
+        },
+    ))
+}
+
+/// Builds the instruction replacing the content of an existing V2 `record` of `domain`.
+///
+/// The account is resized when needed, and its RoA and staleness validations are reset
+pub fn update_record_v2_instruction(
+    domain: &str,
+    record: Record,
+    content: &str,
+    owner: Pubkey,
+    payer: Pubkey,
+) -> Result<Instruction, SnsError> {
+    let data = serialize_record_v2_content(content, record.clone())?;
+    update_record_v2_instruction_with_data(domain, record, data, owner, payer)
+}
+
+/// Same as [`update_record_v2_instruction`] with already serialized content
+pub fn update_record_v2_instruction_with_data(
+    domain: &str,
+    record: Record,
+    data: Vec<u8>,
+    owner: Pubkey,
+    payer: Pubkey,
+) -> Result<Instruction, SnsError> {
+    let keys = RecordV2Accounts::new(domain, &record)?;
+    Ok(edit_record(
+        edit_record::Accounts {
+            system_program: &system_program::ID,
+            spl_name_service_program: &spl_name_service::ID,
+            fee_payer: &payer,
+            record: &keys.record,
+            domain: &keys.domain,
+            domain_owner: &owner,
+            central_state: &sns_records::central_state::KEY,
+        },
+        edit_record::Params {
+            record: keys.name,
+            content: data,
+        },
+    ))
+}
+
+/// Builds the instruction deleting the V2 `record` of `domain`, its rent is refunded to `payer`
+pub fn delete_record_v2_instruction(
+    domain: &str,
+    record: Record,
+    owner: Pubkey,
+    payer: Pubkey,
+) -> Result<Instruction, SnsError> {
+    let keys = RecordV2Accounts::new(domain, &record)?;
+    Ok(delete_record(
+        delete_record::Accounts {
+            system_program: &system_program::ID,
+            spl_name_service_program: &spl_name_service::ID,
+            fee_payer: &payer,
+            record: &keys.record,
+            domain: &keys.domain,
+            domain_owner: &owner,
+            central_state: &sns_records::central_state::KEY,
+        },
+        delete_record::Params {},
+    ))
+}
+
+/// Builds the instruction signing the V2 `record` of `domain` with `verifier`.
+///
+/// With `staleness`, `verifier` must be the domain owner and the record is marked as written by
+/// it. Otherwise `verifier` signs the right of association of the record content, e.g. the
+/// content of a SOL record
+pub fn validate_record_v2_content_instruction(
+    staleness: bool,
+    domain: &str,
+    record: Record,
+    owner: Pubkey,
+    payer: Pubkey,
+    verifier: Pubkey,
+) -> Result<Instruction, SnsError> {
+    let keys = RecordV2Accounts::new(domain, &record)?;
+    Ok(validate_solana_signature(
+        validate_solana_signature::Accounts {
+            system_program: &system_program::ID,
+            spl_name_service_program: &spl_name_service::ID,
+            fee_payer: &payer,
+            record: &keys.record,
+            domain: &keys.domain,
+            domain_owner: &owner,
+            central_state: &sns_records::central_state::KEY,
+            verifier: &verifier,
+        },
+        validate_solana_signature::Params { staleness },
+    ))
+}
+
+/// Builds the instruction verifying the right of association of the EVM `record` of `domain`
+/// with `signature`, a signature of [`get_eth_roa_message`] by `address`.
+///
+/// The staleness of the record must have been validated by `owner`, the domain owner, first
+pub fn validate_record_v2_eth_instruction(
+    domain: &str,
+    record: Record,
+    owner: Pubkey,
+    payer: Pubkey,
+    signature: Vec<u8>,
+    address: Vec<u8>,
+) -> Result<Instruction, SnsError> {
+    let keys = RecordV2Accounts::new(domain, &record)?;
+    Ok(validate_ethereum_signature(
+        validate_ethereum_signature::Accounts {
+            system_program: &system_program::ID,
+            spl_name_service_program: &spl_name_service::ID,
+            fee_payer: &payer,
+            record: &keys.record,
+            domain: &keys.domain,
+            domain_owner: &owner,
+            central_state: &sns_records::central_state::KEY,
+        },
+        validate_ethereum_signature::Params {
+            validation: Validation::Ethereum,
+            signature,
+            expected_pubkey: address,
+        },
+    ))
+}
+
+#[cfg(not(feature = "blocking"))]
 pub async fn retrieve_record_v2(
-    rpc_client: RpcClient,
+    rpc_client: solana_client::nonblocking::rpc_client::RpcClient,
     record: Record,
     domain: &str,
 ) -> Result<Option<(NameRecordHeader, Vec<u8>)>, SnsError> {
-    let record_key = get_record_key(domain, record, super::RecordVersion::V2)?;
+    let record_key = get_record_key(domain, record.clone(), RecordVersion::V2)?;
     resolve_name_registry(&rpc_client, &record_key).await
 }
 
+#[cfg(not(feature = "blocking"))]
 pub async fn retrieve_records_batch_v2(
-    rpc_client: RpcClient,
+    rpc_client: solana_client::nonblocking::rpc_client::RpcClient,
     records: &[Record],
     domain: &str,
 ) -> Result<Vec<Option<(NameRecordHeader, Vec<u8>)>>, SnsError> {
     let pubkeys: Vec<Pubkey> = records
         .iter()
-        .map(|r| get_record_key(domain, *r, super::RecordVersion::V2))
+        .map(|r| get_record_key(domain, r.clone(), RecordVersion::V2))
         .collect::<Result<Vec<_>, _>>()?;
     resolve_name_registry_batch(&rpc_client, &pubkeys).await
 }
 
+#[cfg(feature = "blocking")]
+pub fn retrieve_record_v2(
+    rpc_client: solana_client::rpc_client::RpcClient,
+    record: Record,
+    domain: &str,
+) -> Result<Option<(NameRecordHeader, Vec<u8>)>, SnsError> {
+    let record_key = get_record_key(domain, record.clone(), RecordVersion::V2)?;
+    resolve_name_registry(&rpc_client, &record_key)
+}
+
+#[cfg(feature = "blocking")]
+#[allow(clippy::type_complexity)]
+pub fn retrieve_records_batch_v2(
+    rpc_client: solana_client::rpc_client::RpcClient,
+    records: &[Record],
+    domain: &str,
+) -> Result<Vec<Option<(NameRecordHeader, Vec<u8>)>>, SnsError> {
+    let pubkeys: Vec<Pubkey> = records
+        .iter()
+        .map(|r| get_record_key(domain, r.clone(), RecordVersion::V2))
+        .collect::<Result<Vec<_>, _>>()?;
+    resolve_name_registry_batch(&rpc_client, &pubkeys)
+}
+
+/// Retrieves the content of a V2 record, checking that it was signed by the current owner of the
+/// domain, on Solana or on the chain the domain is bridged to, and its right of association
+#[cfg(not(feature = "blocking"))]
+pub async fn retrieve_verified_record_v2(
+    rpc_client: &solana_client::nonblocking::rpc_client::RpcClient,
+    record: Record,
+    domain: &str,
+) -> Result<Option<String>, SnsError> {
+    let record_key = get_record_key(domain, record.clone(), RecordVersion::V2)?;
+    let Some(account) = rpc_client
+        .get_account_with_commitment(&record_key, rpc_client.commitment())
+        .await?
+        .value
+    else {
+        return Ok(None);
+    };
+    let Some(owner) = get_registry_owner(rpc_client, &get_domain_key(domain)?).await? else {
+        return Ok(None);
+    };
+    let parsed = parse_record_v2(record, &account.data).await?;
+    parsed.verify_staleness(&owner)?;
+    parsed.verify_roa()?;
+    Ok(Some(parsed.content))
+}
+
+/// Retrieves the content of a V2 record, checking that it was signed by the current owner of the
+/// domain, on Solana or on the chain the domain is bridged to, and its right of association
+#[cfg(feature = "blocking")]
+pub fn retrieve_verified_record_v2(
+    rpc_client: &solana_client::rpc_client::RpcClient,
+    record: Record,
+    domain: &str,
+) -> Result<Option<String>, SnsError> {
+    let record_key = get_record_key(domain, record.clone(), RecordVersion::V2)?;
+    let Some(account) = rpc_client
+        .get_account_with_commitment(&record_key, rpc_client.commitment())?
+        .value
+    else {
+        return Ok(None);
+    };
+    let Some(owner) = get_registry_owner(rpc_client, &get_domain_key(domain)?)? else {
+        return Ok(None);
+    };
+    let parsed = futures::executor::block_on(parse_record_v2(record, &account.data))?;
+    parsed.verify_staleness(&owner)?;
+    parsed.verify_roa()?;
+    Ok(Some(parsed.content))
+}
+
 pub fn deserialize_record_v2_content(content: &[u8], record: Record) -> Result<String, SnsError> {
     match record {
         // UTF-8 encoded record
@@ -156,16 +499,18 @@ pub fn deserialize_record_v2_content(content: &[u8], record: Record) -> Result<S
         | Record::Backpack
         | Record::TXT
         | Record::Btc
+        | Record::Ipns
         | Record::CNAME => {
             let decoded = String::from_utf8(content.to_vec())?;
             if matches!(record, Record::CNAME | Record::TXT) {
-                let decoded = punycode::decode(&decoded).map_err(|_| SnsError::Punycode)?;
+                let decoded = punycode::decode(&decoded)
+                    .map_err(|_| SnsError::InvalidRecordData { record })?;
                 Ok(decoded)
             } else {
                 Ok(decoded)
             }
         }
-        Record::Sol => {
+        Record::Sol | Record::Background => {
             let bytes: [u8; 32] = content.try_into()?;
             let pubkey = Pubkey::new_from_array(bytes);
             Ok(pubkey.to_string())
@@ -174,10 +519,21 @@ pub fn deserialize_record_v2_content(content: &[u8], record: Record) -> Result<S
             let des = bech32::encode("inj", content.to_base32(), bech32::Variant::Bech32)?;
             Ok(des)
         }
-        Record::Bsc | Record::Eth | Record::BASE => {
+        Record::Bsc | Record::Eth | Record::BASE | Record::Polygon | Record::Arbitrum => {
             let des = format!("0x{}", hex::encode(content));
             Ok(des)
         }
+        Record::Sui | Record::Apt => {
+            let bytes: [u8; 32] = content.try_into()?;
+            Ok(format!("0x{}", hex::encode(bytes)))
+        }
+        Record::Nostr => {
+            let bytes: [u8; 32] = content.try_into()?;
+            let des = bech32::encode("npub", bytes.to_base32(), bech32::Variant::Bech32)?;
+            Ok(des)
+        }
+        // Custom records which are not UTF-8 can be read from `ParsedRecord::raw_content`
+        Record::Custom(_) => Ok(String::from_utf8_lossy(content).into_owned()),
         Record::AAAA => {
             let bytes: [u8; 16] = content.try_into()?;
             let ip = Ipv6Addr::from(bytes);
@@ -191,7 +547,26 @@ pub fn deserialize_record_v2_content(content: &[u8], record: Record) -> Result<S
     }
 }
 
+/// Serializes the content of a V2 record after checking it with [`validate_content`]. The content
+/// of CNAME and TXT records is punycode encoded, as in V1 (see
+/// [`super::record_v1::serialize_record`])
 pub fn serialize_record_v2_content(content: &str, record: Record) -> Result<Vec<u8>, SnsError> {
+    validate_content(&record, content)?;
+    serialize_record_v2_content_unchecked(content, record)
+}
+
+/// Same as [`serialize_record_v2_content`] without the schema validation, e.g. to compare the
+/// content of existing records with their RoA id
+pub fn serialize_record_v2_content_unchecked(
+    content: &str,
+    record: Record,
+) -> Result<Vec<u8>, SnsError> {
+    record.check_custom()?;
+    let invalid = |reason| SnsError::InvalidRecordContent {
+        record: record.clone(),
+        content: content.to_owned(),
+        reason,
+    };
     match record {
         // UTF-8 encoded record
         Record::Ipfs
@@ -211,49 +586,74 @@ pub fn serialize_record_v2_content(content: &str, record: Record) -> Result<Vec<
         | Record::Backpack
         | Record::TXT
         | Record::Btc
+        | Record::Ipns
         | Record::CNAME => {
             if matches!(record, Record::CNAME | Record::TXT) {
-                let encoded = punycode::encode(content).map_err(|_| SnsError::Punycode)?;
+                let encoded =
+                    punycode::encode(content).map_err(|_| invalid("cannot be punycode encoded"))?;
                 Ok(encoded.as_bytes().to_vec())
             } else {
                 Ok(content.as_bytes().to_vec())
             }
         }
-        Record::Sol => {
-            let pubkey = Pubkey::from_str(content).map_err(|_| SnsError::InvalidPubkey)?;
+        Record::Custom(_) => Ok(content.as_bytes().to_vec()),
+        Record::Sol | Record::Background => {
+            let pubkey = Pubkey::from_str(content).map_err(|_| invalid("invalid public key"))?;
             Ok(pubkey.to_bytes().to_vec())
         }
         Record::Injective => {
             if !content.starts_with("inj") {
-                return Err(SnsError::InvalidInjectiveAddress);
+                return Err(invalid("missing inj prefix"));
             }
-            let (_, data, _) = bech32::decode(content)?;
+            let (_, data, _) = bech32::decode(content).map_err(|_| invalid("invalid bech32"))?;
             let data = convert_u5_array(&data);
             if data.len() != 20 {
-                return Err(SnsError::InvalidInjectiveAddress);
+                return Err(invalid("expected a 20 bytes address"));
             }
             Ok(data)
         }
-        Record::Bsc | Record::Eth | Record::BASE => {
+        Record::Bsc | Record::Eth | Record::BASE | Record::Polygon | Record::Arbitrum => {
             if !content.starts_with("0x") {
-                return Err(SnsError::InvalidEvmAddress);
+                return Err(invalid("missing 0x prefix"));
             }
-            let decoded = hex::decode(content.get(2..).ok_or(SnsError::InvalidEvmAddress)?)?;
+            let decoded = hex::decode(&content[2..]).map_err(|_| invalid("invalid hex"))?;
             if decoded.len() != 20 {
-                return Err(SnsError::InvalidEvmAddress);
+                return Err(invalid("expected a 20 bytes address"));
             }
             Ok(decoded)
         }
+        Record::Sui | Record::Apt => {
+            let hex = content
+                .strip_prefix("0x")
+                .ok_or_else(|| invalid("missing 0x prefix"))?;
+            let decoded = hex::decode(hex).map_err(|_| invalid("invalid hex"))?;
+            if decoded.len() != 32 {
+                return Err(invalid("expected a 32 bytes address"));
+            }
+            Ok(decoded)
+        }
+        Record::Nostr => {
+            let (prefix, data, variant) =
+                bech32::decode(content).map_err(|_| invalid("invalid bech32"))?;
+            if prefix != "npub" || variant != bech32::Variant::Bech32 {
+                return Err(invalid("expected a npub public key"));
+            }
+            let data = Vec::<u8>::from_base32(&data).map_err(|_| invalid("invalid bech32"))?;
+            if data.len() != 32 {
+                return Err(invalid("expected a 32 bytes public key"));
+            }
+            Ok(data)
+        }
         Record::AAAA => {
             let ip = content
                 .parse::<Ipv6Addr>()
-                .map_err(|_| SnsError::InvalidIpv6)?;
+                .map_err(|_| invalid("invalid IPv6 address"))?;
             Ok(ip.octets().to_vec())
         }
         Record::A => {
             let ip = content
                 .parse::<Ipv4Addr>()
-                .map_err(|_| SnsError::InvalidIpv4)?;
+                .map_err(|_| invalid("invalid IPv4 address"))?;
             Ok(ip.octets().to_vec())
         }
     }
@@ -265,12 +665,11 @@ mod test {
     use super::*;
     #[test]
     fn test_serialize_record_v2_content() {
+        // The punycode encoding of ASCII content ends with the `-` delimiter. It is kept on
+        // purpose: the other SNS SDKs write and read the same bytes
         let content = "this is a test";
-        let buffer = vec![
-            116, 104, 105, 115, 32, 105, 115, 32, 97, 32, 116, 101, 115, 116, 45,
-        ];
         let ser = serialize_record_v2_content(content, Record::TXT).unwrap();
-        assert_eq!(buffer, ser);
+        assert_eq!(ser, b"this is a test-");
 
         let content = "D8mRVSXrE2uU8KDAKQsGbfBNRyunMrmHBdEMrtWz1cUc";
         let buffer = vec![
@@ -299,6 +698,289 @@ mod test {
         assert_eq!(des, content)
     }
 
+    #[test]
+    fn test_new_records_v2_content() {
+        // NIP-19 example
+        let npub = "npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg";
+        let key = "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e";
+        let ser = serialize_record_v2_content(npub, Record::Nostr).unwrap();
+        assert_eq!(hex::encode(&ser), key);
+        assert_eq!(
+            deserialize_record_v2_content(&ser, Record::Nostr).unwrap(),
+            npub
+        );
+        // Keys whose base32 encoding ends with padding bits
+        let npub = "npub1qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqzqujme";
+        let ser = serialize_record_v2_content(npub, Record::Nostr).unwrap();
+        assert_eq!(ser.len(), 32);
+        assert_eq!(
+            deserialize_record_v2_content(&ser, Record::Nostr).unwrap(),
+            npub
+        );
+        assert!(matches!(
+            serialize_record_v2_content(
+                "nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe5",
+                Record::Nostr
+            ),
+            Err(SnsError::InvalidRecordContent { .. })
+        ));
+
+        let address = format!("0x{key}");
+        for record in [Record::Sui, Record::Apt] {
+            let ser = serialize_record_v2_content(&address, record.clone()).unwrap();
+            assert_eq!(ser.len(), 32);
+            assert_eq!(
+                deserialize_record_v2_content(&ser, record.clone()).unwrap(),
+                address
+            );
+            assert!(serialize_record_v2_content("0x1", record).is_err());
+        }
+
+        let ser = serialize_record_v2_content(
+            "0x570eDC13f9D406a2b4E6477Ddf75D5E9cCF51cd6",
+            Record::Polygon,
+        )
+        .unwrap();
+        assert_eq!(ser.len(), 20);
+        assert!(matches!(
+            Record::Arbitrum.roa_validation(),
+            Validation::Ethereum
+        ));
+
+        // Custom records hold raw bytes
+        let record = Record::Custom("discord-id".to_owned());
+        let ser = serialize_record_v2_content("1234", record.clone()).unwrap();
+        assert_eq!(ser, b"1234");
+        assert_eq!(
+            deserialize_record_v2_content(&[0xff, 0x00], record).unwrap(),
+            "\u{fffd}\0"
+        );
+    }
+
+    #[cfg(not(feature = "blocking"))]
+    #[tokio::test]
+    async fn test_local_retrieve_record_v2() {
+        use crate::testing::{self, rpc::LocalRpc};
+        use solana_sdk::{signature::Keypair, signer::Signer};
+
+        let owner = Keypair::new();
+        let previous_owner = Pubkey::new_unique();
+        let address = Pubkey::new_unique().to_string();
+        let client = LocalRpc::new([
+            testing::domain("bonfida", &owner.pubkey(), &[]).unwrap(),
+            testing::verified_record_v2("bonfida", Record::Sol, &address, &owner.pubkey()).unwrap(),
+            testing::verified_record_v2("bonfida", Record::Url, "https://sns.id", &previous_owner)
+                .unwrap(),
+        ])
+        .into_client();
+
+        let res = retrieve_verified_record_v2(&client, Record::Sol, "bonfida")
+            .await
+            .unwrap();
+        assert_eq!(res, Some(address));
+        let res = retrieve_verified_record_v2(&client, Record::Url, "bonfida").await;
+        assert!(res.is_err());
+        let res = retrieve_verified_record_v2(&client, Record::TXT, "bonfida")
+            .await
+            .unwrap();
+        assert_eq!(res, None);
+
+        let res = retrieve_records_batch_v2(client, &[Record::Url, Record::TXT], "bonfida")
+            .await
+            .unwrap();
+        assert_eq!(
+            res[0].as_ref().unwrap().0.owner,
+            crate::record::CENTRAL_STATE_RECORD_V2
+        );
+        assert!(res[1].is_none());
+    }
+
+    #[test]
+    fn test_record_v2_instructions() {
+        let owner = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        for domain in ["bonfida", "dex.bonfida"] {
+            let domain_key = get_domain_key(domain).unwrap();
+            let (expected, _) =
+                sns_records::utils::get_record_key_and_seeds(&domain_key, "\x02IPFS");
+            let cid = "ipfs://QmbWqxBEKC3P8tqsKc98xmWNzrzDtRLMiMPL8wBuTGsMnR";
+            let ix = create_record_v2_instruction(domain, Record::Ipfs, cid, owner, payer).unwrap();
+            // Invalid content is rejected unless written with the unchecked serializer
+            assert!(matches!(
+                create_record_v2_instruction(domain, Record::Ipfs, "ipfs://", owner, payer),
+                Err(SnsError::InvalidRecordSchema { .. })
+            ));
+            let data = serialize_record_v2_content_unchecked("ipfs://", Record::Ipfs).unwrap();
+            create_record_v2_instruction_with_data(domain, Record::Ipfs, data, owner, payer)
+                .unwrap();
+            assert_eq!(ix.program_id, sns_records::ID);
+            assert_eq!(ix.accounts[3].pubkey, expected);
+            assert_eq!(ix.accounts[4].pubkey, domain_key);
+            assert!(ix.accounts[5].is_signer);
+
+            let ix = validate_record_v2_content_instruction(
+                true,
+                domain,
+                Record::Ipfs,
+                owner,
+                payer,
+                owner,
+            )
+            .unwrap();
+            assert_eq!(ix.accounts[3].pubkey, expected);
+            assert!(!ix.accounts[5].is_signer);
+            assert!(ix.accounts[7].is_signer);
+
+            let (expected, _) =
+                sns_records::utils::get_record_key_and_seeds(&domain_key, "\x02discord-id");
+            let record = Record::Custom("discord-id".to_owned());
+            let ix = create_record_v2_instruction_with_data(
+                domain,
+                record,
+                vec![0xff, 0x00],
+                owner,
+                payer,
+            )
+            .unwrap();
+            assert_eq!(ix.accounts[3].pubkey, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_sol_record_roa() {
+        let owner = Pubkey::new_unique();
+        let address = Pubkey::new_unique();
+        let account = |roa_id: &Pubkey| {
+            let header = RecordHeader {
+                staleness_validation: Validation::Solana as u16,
+                right_of_association_validation: Validation::Solana as u16,
+                content_length: 32,
+            };
+            [
+                &[0; NameRecordHeader::LEN][..],
+                bytemuck::bytes_of(&header),
+                owner.as_ref(),
+                roa_id.as_ref(),
+                address.as_ref(),
+            ]
+            .concat()
+        };
+
+        let data = account(&address);
+        let parsed = parse_record_v2(Record::Sol, &data).await.unwrap();
+        assert_eq!(parsed.content, address.to_string());
+        parsed.verify_roa().unwrap();
+        parsed.verify_staleness(&Owner::Solana(owner)).unwrap();
+        assert!(parsed.verify_staleness(&Owner::Solana(address)).is_err());
+
+        let data = account(&owner);
+        let parsed = parse_record_v2(Record::Sol, &data).await.unwrap();
+        assert!(matches!(
+            parsed.verify_roa(),
+            Err(SnsError::UnverifiedRecord { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_eth_signature() {
+        let secret = libsecp256k1::SecretKey::parse(&[7; 32]).unwrap();
+        let pubkey = libsecp256k1::PublicKey::from_secret_key(&secret);
+        let address = keccak::hash(&pubkey.serialize()[1..]).as_ref()[12..].to_vec();
+        let owner = Pubkey::new_unique();
+        let record_key = get_record_key("bonfida", Record::Eth, RecordVersion::V2).unwrap();
+
+        let message = get_eth_roa_message(&address, &record_key, &owner);
+        let hash = keccak::hashv(&[
+            validate_ethereum_signature::ETH_PREFIX_BYTES,
+            message.len().to_string().as_bytes(),
+            &message,
+        ]);
+        let (signature, recovery_id) =
+            libsecp256k1::sign(&libsecp256k1::Message::parse(&hash.0), &secret);
+        let signature = [
+            signature.serialize().as_slice(),
+            &[recovery_id.serialize() + 27],
+        ]
+        .concat();
+        assert_eq!(
+            recover_eth_address(&message, &signature)
+                .unwrap()
+                .as_slice(),
+            address
+        );
+        assert!(recover_eth_address(&message, &signature[..64]).is_err());
+
+        let header = RecordHeader {
+            staleness_validation: Validation::Solana as u16,
+            right_of_association_validation: Validation::Ethereum as u16,
+            content_length: 20,
+        };
+        let data = [
+            &[0; NameRecordHeader::LEN][..],
+            bytemuck::bytes_of(&header),
+            owner.as_ref(),
+            &address,
+            &address,
+        ]
+        .concat();
+        let parsed = parse_record_v2(Record::Eth, &data).await.unwrap();
+        assert_eq!(parsed.content, format!("0x{}", hex::encode(&address)));
+        parsed.verify_roa().unwrap();
+        parsed
+            .verify_eth_signature(&record_key, &signature)
+            .unwrap();
+        let other_key = get_record_key("sns", Record::Eth, RecordVersion::V2).unwrap();
+        assert!(matches!(
+            parsed.verify_eth_signature(&other_key, &signature),
+            Err(SnsError::UnverifiedRecord { .. })
+        ));
+
+        let ix = validate_record_v2_eth_instruction(
+            "bonfida",
+            Record::Eth,
+            owner,
+            owner,
+            signature,
+            address,
+        )
+        .unwrap();
+        assert_eq!(ix.program_id, sns_records::ID);
+        assert_eq!(ix.accounts[3].pubkey, record_key);
+    }
+
+    #[tokio::test]
+    async fn test_verify_x_chain_staleness() {
+        let address = vec![7; 32];
+        let content = serialize_record_v2_content("test", Record::TXT).unwrap();
+        let header = RecordHeader {
+            staleness_validation: Validation::XChain as u16,
+            right_of_association_validation: Validation::None as u16,
+            content_length: content.len() as u32,
+        };
+        let data = [
+            &[0; NameRecordHeader::LEN][..],
+            bytemuck::bytes_of(&header),
+            &30u16.to_le_bytes(),
+            &address,
+            &content,
+        ]
+        .concat();
+        let parsed = parse_record_v2(Record::TXT, &data).await.unwrap();
+        assert_eq!(parsed.content, "test");
+        parsed
+            .verify_staleness(&Owner::Foreign {
+                chain: 30,
+                address: address.clone(),
+            })
+            .unwrap();
+        assert!(parsed
+            .verify_staleness(&Owner::Foreign { chain: 4, address })
+            .is_err());
+        assert!(parsed
+            .verify_staleness(&Owner::Solana(Pubkey::new_from_array([7; 32])))
+            .is_err());
+    }
+
     #[test]
     fn test_des_ser() {
         let content = "test";
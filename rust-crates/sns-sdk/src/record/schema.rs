@@ -0,0 +1,389 @@
+//! Validation of the content of records before it is written, so that malformed values are
+//! rejected instead of being saved on chain. Records without a schema accept any content
+
+use {
+    super::Record,
+    crate::error::SnsError,
+    derive_more::{Display, Error},
+    solana_program::hash::hashv,
+};
+
+/// The reason why a record content does not match the schema of its record
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ContentError {
+    #[display(fmt = "invalid email address")]
+    InvalidEmail,
+    #[display(fmt = "expected an absolute URL")]
+    InvalidUrl,
+    #[display(fmt = "invalid handle")]
+    InvalidHandle,
+    #[display(fmt = "invalid IPFS CID")]
+    InvalidCid,
+    #[display(fmt = "expected a 43 characters Arweave transaction id")]
+    InvalidArweaveId,
+    #[display(fmt = "invalid address")]
+    InvalidAddress,
+    #[display(fmt = "invalid address checksum")]
+    InvalidChecksum,
+    #[display(fmt = "invalid hostname")]
+    InvalidHostname,
+}
+
+/// Checks that `content` matches the schema of `record`:
+/// - `email`: a `local@domain` address with a dot-atom local part and a hostname
+/// - `url`: an absolute URL with a host
+/// - `twitter`, `github`, `telegram` and `discord`: a handle following the rules of the platform,
+///   Twitter and Telegram handles may start with `@`
+/// - `IPFS`: a CIDv0 or a base32, base58 or base16 CIDv1, optionally prefixed with `ipfs://`
+/// - `ARWV`: a 43 characters transaction id, optionally prefixed with `ar://`
+/// - `BTC`, `LTC` and `DOGE`: a base58check or segwit address of the chain, checksum included
+/// - `CNAME`: a hostname, optionally fully qualified
+pub fn validate_content(record: &Record, content: &str) -> Result<(), SnsError> {
+    let valid = match record {
+        Record::Email => validate_email(content),
+        Record::Url => validate_url(content),
+        Record::Twitter => validate_twitter(content),
+        Record::Github => validate_github(content),
+        Record::Telegram => validate_telegram(content),
+        Record::Discord => validate_discord(content),
+        Record::Ipfs => validate_cid(content),
+        Record::Arwv => validate_arweave_id(content),
+        Record::Btc => validate_utxo_address(content, &[0x00, 0x05], Some("bc")),
+        Record::Ltc => validate_utxo_address(content, &[0x30, 0x32, 0x05], Some("ltc")),
+        Record::Doge => validate_utxo_address(content, &[0x1e, 0x16], None),
+        Record::CNAME => validate_hostname(content.strip_suffix('.').unwrap_or(content))
+            .then_some(())
+            .ok_or(ContentError::InvalidHostname),
+        _ => Ok(()),
+    };
+    valid.map_err(|error| SnsError::InvalidRecordSchema {
+        record: record.clone(),
+        content: content.to_owned(),
+        error,
+    })
+}
+
+fn validate_email(content: &str) -> Result<(), ContentError> {
+    let (local, domain) = content.rsplit_once('@').ok_or(ContentError::InvalidEmail)?;
+    let atext = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+/=?^_`{|}~-".contains(c);
+    let valid_local = local.len() <= 64
+        && local
+            .split('.')
+            .all(|atom| !atom.is_empty() && atom.chars().all(atext));
+    if content.len() > 254 || !valid_local || !domain.contains('.') || !validate_hostname(domain) {
+        return Err(ContentError::InvalidEmail);
+    }
+    Ok(())
+}
+
+fn validate_url(content: &str) -> Result<(), ContentError> {
+    let url = url::Url::parse(content).map_err(|_| ContentError::InvalidUrl)?;
+    if !url.has_host() {
+        return Err(ContentError::InvalidUrl);
+    }
+    Ok(())
+}
+
+/// 1 to 15 letters, digits or underscores
+fn validate_twitter(content: &str) -> Result<(), ContentError> {
+    let handle = content.strip_prefix('@').unwrap_or(content);
+    let valid = (1..=15).contains(&handle.len())
+        && handle
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then_some(()).ok_or(ContentError::InvalidHandle)
+}
+
+/// Up to 39 letters, digits or single hyphens, which cannot start or end the handle
+fn validate_github(content: &str) -> Result<(), ContentError> {
+    let valid = (1..=39).contains(&content.len())
+        && content
+            .split('-')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()));
+    valid.then_some(()).ok_or(ContentError::InvalidHandle)
+}
+
+/// 5 to 32 letters, digits or underscores, starting with a letter
+fn validate_telegram(content: &str) -> Result<(), ContentError> {
+    let handle = content.strip_prefix('@').unwrap_or(content);
+    let valid = (5..=32).contains(&handle.len())
+        && handle.starts_with(|c: char| c.is_ascii_alphabetic())
+        && handle
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then_some(()).ok_or(ContentError::InvalidHandle)
+}
+
+/// 2 to 32 lowercase letters, digits, underscores or non consecutive periods, or a legacy
+/// `name#1234` handle
+fn validate_discord(content: &str) -> Result<(), ContentError> {
+    let valid = match content.rsplit_once('#') {
+        Some((name, discriminator)) => {
+            (2..=32).contains(&name.chars().count())
+                && !name.contains(['@', '#', ':'])
+                && discriminator.len() == 4
+                && discriminator.chars().all(|c| c.is_ascii_digit())
+        }
+        None => {
+            (2..=32).contains(&content.len())
+                && !content.contains("..")
+                && content
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_.".contains(c))
+        }
+    };
+    valid.then_some(()).ok_or(ContentError::InvalidHandle)
+}
+
+fn validate_cid(content: &str) -> Result<(), ContentError> {
+    let cid = content.strip_prefix("ipfs://").unwrap_or(content);
+    // CIDv0, the base58 encoding of a sha2-256 multihash
+    if cid.starts_with("Qm") {
+        let decoded = bs58::decode(cid)
+            .into_vec()
+            .map_err(|_| ContentError::InvalidCid)?;
+        let valid = decoded.len() == 34 && decoded[..2] == [0x12, 0x20];
+        return valid.then_some(()).ok_or(ContentError::InvalidCid);
+    }
+    let mut chars = cid.chars();
+    let decoded = match chars.next() {
+        Some('b') => decode_base32(chars.as_str()),
+        Some('z') => bs58::decode(chars.as_str()).into_vec().ok(),
+        Some('f') => hex::decode(chars.as_str()).ok(),
+        _ => None,
+    };
+    let mut bytes = decoded.ok_or(ContentError::InvalidCid)?.into_iter();
+    let mut varint = || read_varint(&mut bytes);
+    // Version, content codec, hash function and digest length
+    let (Some(1), Some(_), Some(_), Some(length)) = (varint(), varint(), varint(), varint()) else {
+        return Err(ContentError::InvalidCid);
+    };
+    let valid = length > 0 && bytes.len() as u64 == length;
+    valid.then_some(()).ok_or(ContentError::InvalidCid)
+}
+
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..63).step_by(7) {
+        let byte = bytes.next()?;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Lowercase RFC 4648 base32 without padding, as used by multibase
+fn decode_base32(content: &str) -> Option<Vec<u8>> {
+    const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+    let mut bytes = Vec::with_capacity(content.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in content.bytes() {
+        let value = ALPHABET.iter().position(|&a| a == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    (buffer == 0).then_some(bytes)
+}
+
+/// The base64url encoding of a 32 bytes hash, whose last character only holds 4 bits
+fn validate_arweave_id(content: &str) -> Result<(), ContentError> {
+    let id = content.strip_prefix("ar://").unwrap_or(content);
+    let valid = id.len() == 43
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && id.ends_with(|c: char| "AEIMQUYcgkosw048".contains(c));
+    valid.then_some(()).ok_or(ContentError::InvalidArweaveId)
+}
+
+/// Base58check addresses starting with one of the `versions` bytes, or segwit addresses of the
+/// `segwit_hrp` network
+fn validate_utxo_address(
+    content: &str,
+    versions: &[u8],
+    segwit_hrp: Option<&str>,
+) -> Result<(), ContentError> {
+    if let Some(hrp) = segwit_hrp {
+        let is_segwit = content
+            .to_ascii_lowercase()
+            .strip_prefix(hrp)
+            .is_some_and(|rest| rest.starts_with('1'));
+        if is_segwit {
+            return validate_segwit_address(content, hrp);
+        }
+    }
+    let decoded = bs58::decode(content)
+        .into_vec()
+        .map_err(|_| ContentError::InvalidAddress)?;
+    if decoded.len() != 25 || !versions.contains(&decoded[0]) {
+        return Err(ContentError::InvalidAddress);
+    }
+    let (payload, checksum) = decoded.split_at(21);
+    let hash = hashv(&[hashv(&[payload]).as_ref()]);
+    if hash.as_ref()[..4] != *checksum {
+        return Err(ContentError::InvalidChecksum);
+    }
+    Ok(())
+}
+
+/// BIP 173 and BIP 350 addresses: version 0 programs are bech32 encoded, later ones bech32m
+fn validate_segwit_address(content: &str, hrp: &str) -> Result<(), ContentError> {
+    use bech32::FromBase32;
+
+    let (decoded_hrp, data, variant) = bech32::decode(content).map_err(|e| match e {
+        bech32::Error::InvalidChecksum => ContentError::InvalidChecksum,
+        _ => ContentError::InvalidAddress,
+    })?;
+    let (version, program) = data.split_first().ok_or(ContentError::InvalidAddress)?;
+    let program = Vec::<u8>::from_base32(program).map_err(|_| ContentError::InvalidAddress)?;
+    let version = version.to_u8();
+    let valid = decoded_hrp == hrp
+        && match version {
+            0 => variant == bech32::Variant::Bech32 && [20, 32].contains(&program.len()),
+            1..=16 => variant == bech32::Variant::Bech32m && (2..=40).contains(&program.len()),
+            _ => false,
+        };
+    valid.then_some(()).ok_or(ContentError::InvalidAddress)
+}
+
+/// Labels of up to 63 alphanumeric characters or inner hyphens, 253 characters in total.
+/// Internationalized labels are accepted as they are punycode encoded when serialized
+fn validate_hostname(hostname: &str) -> bool {
+    (1..=253).contains(&hostname.len())
+        && hostname.split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn error(record: Record, content: &str) -> Option<ContentError> {
+        match validate_content(&record, content) {
+            Ok(()) => None,
+            Err(SnsError::InvalidRecordSchema { error, .. }) => Some(error),
+            Err(e) => panic!("unexpected error {e}"),
+        }
+    }
+
+    fn base58check(version: u8) -> String {
+        let payload = [&[version], [7; 20].as_slice()].concat();
+        let hash = hashv(&[hashv(&[&payload]).as_ref()]);
+        bs58::encode([payload.as_slice(), &hash.as_ref()[..4]].concat()).into_string()
+    }
+
+    #[test]
+    fn test_validate_content() {
+        use ContentError::*;
+
+        let cases = [
+            (Record::Email, "contact@sns.id", None),
+            (Record::Email, "first.last+tag@mail.sns.id", None),
+            (Record::Email, "contact@localhost", Some(InvalidEmail)),
+            (Record::Email, "first..last@sns.id", Some(InvalidEmail)),
+            (Record::Email, "sns.id", Some(InvalidEmail)),
+            (Record::Url, "https://sns.id/path?query", None),
+            (Record::Url, "sns.id", Some(InvalidUrl)),
+            (Record::Url, "mailto:contact@sns.id", Some(InvalidUrl)),
+            (Record::Twitter, "@sns_id", None),
+            (
+                Record::Twitter,
+                "a_very_long_twitter_handle",
+                Some(InvalidHandle),
+            ),
+            (Record::Github, "solana-name-service", None),
+            (Record::Github, "-bonfida", Some(InvalidHandle)),
+            (Record::Github, "bon--fida", Some(InvalidHandle)),
+            (Record::Telegram, "@bonfida", None),
+            (Record::Telegram, "bonf", Some(InvalidHandle)),
+            (Record::Telegram, "1bonfida", Some(InvalidHandle)),
+            (Record::Discord, "bonfida.sns", None),
+            (Record::Discord, "Bonfida#1234", None),
+            (Record::Discord, "Bonfida", Some(InvalidHandle)),
+            (Record::Discord, "bonfida#12", Some(InvalidHandle)),
+            (
+                Record::Ipfs,
+                "ipfs://QmbWqxBEKC3P8tqsKc98xmWNzrzDtRLMiMPL8wBuTGsMnR",
+                None,
+            ),
+            (
+                Record::Ipfs,
+                "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi",
+                None,
+            ),
+            (
+                Record::Ipfs,
+                "QmbWqxBEKC3P8tqsKc98xmWNzrzDtRLMiMPL8wBuTGsMn",
+                Some(InvalidCid),
+            ),
+            (
+                Record::Ipfs,
+                "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzd",
+                Some(InvalidCid),
+            ),
+            (
+                Record::Arwv,
+                "ar://bNbA3TEQVL60xlgCcqdz4ZPHFZ711cZ3hmkpGttDt_U",
+                None,
+            ),
+            (
+                Record::Arwv,
+                "bNbA3TEQVL60xlgCcqdz4ZPHFZ711cZ3hmkpGttDt_V",
+                Some(InvalidArweaveId),
+            ),
+            (Record::Btc, "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", None),
+            (
+                Record::Btc,
+                "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN3",
+                Some(InvalidChecksum),
+            ),
+            (
+                Record::Btc,
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                None,
+            ),
+            (
+                Record::Btc,
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5",
+                Some(InvalidChecksum),
+            ),
+            (
+                Record::Ltc,
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                Some(InvalidAddress),
+            ),
+            (Record::CNAME, "sns.id", None),
+            (Record::CNAME, "www.sns.id.", None),
+            (Record::CNAME, "-sns.id", Some(InvalidHostname)),
+            (Record::CNAME, "sns..id", Some(InvalidHostname)),
+            (Record::TXT, "anything goes", None),
+        ];
+        for (record, content, expected) in cases {
+            assert_eq!(
+                error(record.clone(), content),
+                expected,
+                "{record} {content}"
+            );
+        }
+
+        assert_eq!(error(Record::Ltc, &base58check(0x30)), None);
+        assert_eq!(error(Record::Doge, &base58check(0x1e)), None);
+        assert_eq!(
+            error(Record::Doge, &base58check(0x00)),
+            Some(InvalidAddress)
+        );
+        assert_eq!(error(Record::Btc, &base58check(0x1e)), Some(InvalidAddress));
+    }
+}
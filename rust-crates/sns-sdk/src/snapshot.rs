@@ -0,0 +1,390 @@
+use {
+    borsh::BorshDeserialize, name_tokenizer::state::NftRecord, solana_program::pubkey::Pubkey,
+    solana_sdk::account::Account, std::collections::HashMap,
+};
+
+#[cfg(feature = "blocking")]
+use crate::blocking::resolve::{deserialize_name_registry, deserialize_reverse};
+#[cfg(not(feature = "blocking"))]
+use crate::non_blocking::resolve::{deserialize_name_registry, deserialize_reverse};
+use crate::{
+    derivation::{derive_reverse, ROOT_DOMAIN_ACCOUNT},
+    favourite_domain::{derive_favourite_domain_key, FavouriteDomain},
+    record::{get_record_key_from_parent, Record, RecordVersion},
+};
+
+/// Size of a serialized `NftRecord` account
+pub const NFT_RECORD_LEN: u64 = 98;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomainEntry {
+    pub key: Pubkey,
+    pub name: Option<String>,
+    pub owner: Pubkey,
+    pub class: Pubkey,
+    pub data_len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubdomainEntry {
+    pub key: Pubkey,
+    pub parent: Pubkey,
+    pub name: String,
+    pub owner: Pubkey,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReverseEntry {
+    pub key: Pubkey,
+    pub parent: Pubkey,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordEntry {
+    pub key: Pubkey,
+    /// The domain or subdomain the record belongs to
+    pub domain: Pubkey,
+    /// `None` when the record key does not match any known record type
+    pub record: Option<Record>,
+    pub version: RecordVersion,
+    pub owner: Pubkey,
+    /// The account data following the name registry header
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NftRecordEntry {
+    pub key: Pubkey,
+    pub name_account: Pubkey,
+    pub owner: Pubkey,
+    pub nft_mint: Pubkey,
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FavouriteEntry {
+    pub key: Pubkey,
+    /// The owner is only known when the favourite domain is still owned by the wallet that set it
+    pub owner: Option<Pubkey>,
+    pub name_account: Pubkey,
+}
+
+/// Why an account of the name service is not part of a [`Snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkipReason {
+    /// The account data could not be deserialized
+    Malformed,
+    /// A child of a domain or subdomain that is neither a subdomain with a reverse nor a known V1
+    /// record: a subdomain whose reverse does not exist, or a record with a custom name
+    UnknownChild,
+    /// A name account whose parent is not a domain or subdomain of the snapshot
+    UnknownParent,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedAccount {
+    pub key: Pubkey,
+    pub reason: SkipReason,
+}
+
+/// The accounts of the name service, read by scans served between `slot` and `end_slot`.
+///
+/// Each scan is answered by the RPC node at a single slot, but the node keeps processing slots
+/// between scans: the view is consistent at `slot` only when `end_slot` is equal to it
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    /// The slot of the first scan
+    pub slot: u64,
+    /// The slot of the most recent scan
+    pub end_slot: u64,
+    pub domains: Vec<DomainEntry>,
+    pub subdomains: Vec<SubdomainEntry>,
+    pub reverses: Vec<ReverseEntry>,
+    pub records: Vec<RecordEntry>,
+    pub nft_records: Vec<NftRecordEntry>,
+    pub favourites: Vec<FavouriteEntry>,
+    /// The accounts that are not classified in any of the lists above
+    pub skipped: Vec<SkippedAccount>,
+}
+
+/// The raw program accounts a snapshot is built from
+#[derive(Default)]
+pub struct SnapshotAccounts {
+    /// Name service accounts with the default class: domains, subdomains and V1 records
+    pub name_accounts: Vec<(Pubkey, Account)>,
+    /// Name service accounts with the reverse lookup class
+    pub reverse_accounts: Vec<(Pubkey, Account)>,
+    /// Name service accounts with the records V2 class
+    pub record_v2_accounts: Vec<(Pubkey, Account)>,
+    /// Name tokenizer `NftRecord` accounts
+    pub nft_record_accounts: Vec<(Pubkey, Account)>,
+    /// Name offers `FavouriteDomain` accounts
+    pub favourite_accounts: Vec<(Pubkey, Account)>,
+}
+
+fn record_keys(parent: &Pubkey, version: RecordVersion) -> HashMap<Pubkey, Record> {
+    Record::ALL
+        .into_iter()
+        .map(|r| (get_record_key_from_parent(parent, r.clone(), version), r))
+        .collect()
+}
+
+impl Snapshot {
+    /// Classifies raw program accounts read at `slot`. The accounts that cannot be classified are
+    /// listed in `skipped`.
+    pub fn from_accounts(slot: u64, accounts: SnapshotAccounts) -> Snapshot {
+        let mut snapshot = Snapshot {
+            slot,
+            end_slot: slot,
+            ..Default::default()
+        };
+
+        let mut names = HashMap::new();
+        for (key, acc) in accounts.reverse_accounts {
+            let Ok((header, data)) = deserialize_name_registry(&acc.data) else {
+                snapshot.skip(key, SkipReason::Malformed);
+                continue;
+            };
+            let Ok(name) = deserialize_reverse(&data) else {
+                snapshot.skip(key, SkipReason::Malformed);
+                continue;
+            };
+            names.insert(key, name.clone());
+            snapshot.reverses.push(ReverseEntry {
+                key,
+                parent: header.parent_name,
+                name,
+            });
+        }
+
+        let mut children = vec![];
+        for (key, acc) in accounts.name_accounts {
+            let Ok((header, data)) = deserialize_name_registry(&acc.data) else {
+                snapshot.skip(key, SkipReason::Malformed);
+                continue;
+            };
+            if header.parent_name == ROOT_DOMAIN_ACCOUNT {
+                snapshot.domains.push(DomainEntry {
+                    key,
+                    name: names.get(&derive_reverse(&key, None)).cloned(),
+                    owner: header.owner,
+                    class: header.class,
+                    data_len: data.len(),
+                });
+            } else {
+                children.push((key, header, data));
+            }
+        }
+
+        let mut owners = snapshot
+            .domains
+            .iter()
+            .map(|d| (d.key, d.owner))
+            .collect::<HashMap<_, _>>();
+
+        let mut candidates = vec![];
+        for (key, header, data) in children {
+            if !owners.contains_key(&header.parent_name) {
+                candidates.push((key, header, data));
+                continue;
+            }
+            match names.get(&derive_reverse(&key, Some(&header.parent_name))) {
+                Some(name) => snapshot.subdomains.push(SubdomainEntry {
+                    key,
+                    parent: header.parent_name,
+                    name: name.strip_prefix('\0').unwrap_or(name).to_owned(),
+                    owner: header.owner,
+                }),
+                None => candidates.push((key, header, data)),
+            }
+        }
+        owners.extend(snapshot.subdomains.iter().map(|s| (s.key, s.owner)));
+
+        let mut keys_v1 = HashMap::new();
+        for (key, header, data) in candidates {
+            if !owners.contains_key(&header.parent_name) {
+                snapshot.skip(key, SkipReason::UnknownParent);
+                continue;
+            }
+            let keys = keys_v1
+                .entry(header.parent_name)
+                .or_insert_with(|| record_keys(&header.parent_name, RecordVersion::V1));
+            match keys.get(&key) {
+                Some(record) => snapshot.records.push(RecordEntry {
+                    key,
+                    domain: header.parent_name,
+                    record: Some(record.clone()),
+                    version: RecordVersion::V1,
+                    owner: header.owner,
+                    data,
+                }),
+                None => snapshot.skip(key, SkipReason::UnknownChild),
+            }
+        }
+
+        let mut keys_v2 = HashMap::new();
+        for (key, acc) in accounts.record_v2_accounts {
+            let Ok((header, data)) = deserialize_name_registry(&acc.data) else {
+                snapshot.skip(key, SkipReason::Malformed);
+                continue;
+            };
+            let keys = keys_v2
+                .entry(header.parent_name)
+                .or_insert_with(|| record_keys(&header.parent_name, RecordVersion::V2));
+            snapshot.records.push(RecordEntry {
+                key,
+                domain: header.parent_name,
+                record: keys.get(&key).cloned(),
+                version: RecordVersion::V2,
+                owner: header.owner,
+                data,
+            })
+        }
+
+        for (key, acc) in accounts.nft_record_accounts {
+            let Ok(record) = NftRecord::deserialize(&mut acc.data.as_slice()) else {
+                snapshot.skip(key, SkipReason::Malformed);
+                continue;
+            };
+            snapshot.nft_records.push(NftRecordEntry {
+                key,
+                name_account: record.name_account,
+                owner: record.owner,
+                nft_mint: record.nft_mint,
+                active: record.is_active(),
+            });
+        }
+
+        for (key, acc) in accounts.favourite_accounts {
+            let Ok(favourite) = FavouriteDomain::parse(&acc.data) else {
+                snapshot.skip(key, SkipReason::Malformed);
+                continue;
+            };
+            let owner = owners
+                .get(&favourite.name_account)
+                .filter(|o| derive_favourite_domain_key(o) == key)
+                .copied();
+            snapshot.favourites.push(FavouriteEntry {
+                key,
+                owner,
+                name_account: favourite.name_account,
+            });
+        }
+
+        snapshot
+    }
+
+    fn skip(&mut self, key: Pubkey, reason: SkipReason) {
+        self.skipped.push(SkippedAccount { key, reason });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::derivation::{derive, get_domain_key, REVERSE_LOOKUP_CLASS};
+    use solana_program::program_pack::Pack;
+    use solana_sdk::{signature::Keypair, signer::Signer};
+    use spl_name_service::state::NameRecordHeader;
+
+    fn name_account(parent: Pubkey, owner: Pubkey, class: Pubkey, data: &[u8]) -> Account {
+        let mut buffer = vec![0; NameRecordHeader::LEN];
+        NameRecordHeader {
+            parent_name: parent,
+            owner,
+            class,
+        }
+        .pack_into_slice(&mut buffer);
+        buffer.extend_from_slice(data);
+        Account {
+            data: buffer,
+            ..Default::default()
+        }
+    }
+
+    fn reverse_data(name: &str) -> Vec<u8> {
+        let mut data = (name.len() as u32).to_le_bytes().to_vec();
+        data.extend_from_slice(name.as_bytes());
+        data
+    }
+
+    #[test]
+    fn test_from_accounts() {
+        let owner = Keypair::new().pubkey();
+        let domain = get_domain_key("bonfida").unwrap();
+        let sub = derive("\0dex", &domain, None);
+        let url = get_record_key_from_parent(&domain, Record::Url, RecordVersion::V1);
+        let unknown = Keypair::new().pubkey();
+        let orphan = Keypair::new().pubkey();
+        let malformed = Keypair::new().pubkey();
+
+        let accounts = SnapshotAccounts {
+            name_accounts: vec![
+                (
+                    domain,
+                    name_account(ROOT_DOMAIN_ACCOUNT, owner, Pubkey::default(), &[]),
+                ),
+                (sub, name_account(domain, owner, Pubkey::default(), &[])),
+                (
+                    url,
+                    name_account(domain, owner, Pubkey::default(), b"https://sns.id"),
+                ),
+                (unknown, name_account(domain, owner, Pubkey::default(), &[])),
+                (
+                    orphan,
+                    name_account(Keypair::new().pubkey(), owner, Pubkey::default(), &[]),
+                ),
+            ],
+            reverse_accounts: vec![
+                (
+                    derive_reverse(&domain, None),
+                    name_account(
+                        Pubkey::default(),
+                        Pubkey::default(),
+                        REVERSE_LOOKUP_CLASS,
+                        &reverse_data("bonfida"),
+                    ),
+                ),
+                (
+                    derive_reverse(&sub, Some(&domain)),
+                    name_account(
+                        domain,
+                        Pubkey::default(),
+                        REVERSE_LOOKUP_CLASS,
+                        &reverse_data("\0dex"),
+                    ),
+                ),
+            ],
+            favourite_accounts: vec![(malformed, Account::default())],
+            ..Default::default()
+        };
+        let snapshot = Snapshot::from_accounts(42, accounts);
+        assert_eq!(snapshot.slot, 42);
+        assert_eq!(snapshot.domains.len(), 1);
+        assert_eq!(snapshot.domains[0].name.as_deref(), Some("bonfida"));
+        assert_eq!(snapshot.subdomains.len(), 1);
+        assert_eq!(snapshot.subdomains[0].name, "dex");
+        assert_eq!(snapshot.records.len(), 1);
+        assert_eq!(snapshot.records[0].record, Some(Record::Url));
+        assert_eq!(snapshot.records[0].data, b"https://sns.id");
+        assert_eq!(snapshot.reverses.len(), 2);
+        assert_eq!(
+            snapshot.skipped,
+            vec![
+                SkippedAccount {
+                    key: unknown,
+                    reason: SkipReason::UnknownChild
+                },
+                SkippedAccount {
+                    key: orphan,
+                    reason: SkipReason::UnknownParent
+                },
+                SkippedAccount {
+                    key: malformed,
+                    reason: SkipReason::Malformed
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Builders of SNS accounts for unit tests, enabled by the `testing` feature.
+//!
+//! Each builder returns the key of the account and the account itself, laid out as on chain, owned
+//! by the right program and rent exempt. Fixtures can be served to the SDK by a [`rpc::LocalRpc`],
+//! or loaded into any other in-memory account provider, e.g. `solana-program-test`.
+use {
+    borsh::BorshSerialize,
+    name_tokenizer::state::NftRecord,
+    sns_records::state::{record_header::RecordHeader, validation::Validation},
+    sns_warp_common::constants::EMITTER_KEY,
+    solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey, rent::Rent},
+    solana_sdk::{account::Account, signature::Keypair, signer::Signer},
+    spl_associated_token_account::get_associated_token_address,
+    spl_name_service::state::NameRecordHeader,
+};
+
+use crate::{
+    bridge::XDomain,
+    derivation::{
+        get_domain_key_with_parent, get_domain_mint, get_reverse_key, trim_tld, NAME_TOKENIZER_ID,
+        REVERSE_LOOKUP_CLASS,
+    },
+    error::SnsError,
+    favourite_domain::{derive_favourite_domain_key, Tag},
+    record::{
+        get_record_key, record_v1, record_v2, Record, RecordVersion, CENTRAL_STATE_RECORD_V2,
+    },
+    NAME_OFFERS_PROGRAM_ID,
+};
+
+pub mod rpc;
+
+/// The key and the account of a fixture
+pub type Fixture = (Pubkey, Account);
+
+/// A rent exempt account owned by `program` holding `data`
+pub fn account(program: &Pubkey, data: Vec<u8>) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: *program,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// A name registry account holding `data` after its header
+pub fn name_registry_account(
+    parent: &Pubkey,
+    owner: &Pubkey,
+    class: &Pubkey,
+    data: &[u8],
+) -> Account {
+    let mut buffer = vec![0; NameRecordHeader::LEN];
+    NameRecordHeader {
+        parent_name: *parent,
+        owner: *owner,
+        class: *class,
+    }
+    .pack_into_slice(&mut buffer);
+    buffer.extend_from_slice(data);
+    account(&spl_name_service::ID, buffer)
+}
+
+/// The name registry of a domain or a subdomain owned by `owner`
+pub fn domain(domain: &str, owner: &Pubkey, data: &[u8]) -> Result<Fixture, SnsError> {
+    let keys = get_domain_key_with_parent(domain)?;
+    Ok((
+        keys.key,
+        name_registry_account(&keys.parent, owner, &Pubkey::default(), data),
+    ))
+}
+
+/// The reverse lookup of a domain or a subdomain, which holds its name
+pub fn reverse(domain: &str) -> Result<Fixture, SnsError> {
+    let domain = trim_tld(domain);
+    let (parent, name) = match domain.split_once('.') {
+        Some((sub, parent)) => (get_domain_key_with_parent(parent)?.key, format!("\0{sub}")),
+        None => (Pubkey::default(), domain.to_owned()),
+    };
+    let mut data = (name.len() as u32).to_le_bytes().to_vec();
+    data.extend_from_slice(name.as_bytes());
+    Ok((
+        get_reverse_key(domain)?,
+        name_registry_account(&parent, &REVERSE_LOOKUP_CLASS, &REVERSE_LOOKUP_CLASS, &data),
+    ))
+}
+
+/// The V1 `record` of `domain` holding `content`, serialized as by the SDK
+pub fn record_v1(
+    domain: &str,
+    record: Record,
+    content: &str,
+    owner: &Pubkey,
+) -> Result<Fixture, SnsError> {
+    let data = record_v1::serialize_record(content, record.clone())?;
+    record_v1_with_data(domain, record, &data, owner)
+}
+
+/// The V1 SOL record of `domain` pointing to `content`, signed by `owner`, the domain owner
+pub fn sol_record_v1(domain: &str, content: &Pubkey, owner: &Keypair) -> Result<Fixture, SnsError> {
+    let key = get_record_key(domain, Record::Sol, RecordVersion::V1)?;
+    let data = record_v1::serialize_sol_record(content, &key, owner)?;
+    record_v1_with_data(domain, Record::Sol, &data, &owner.pubkey())
+}
+
+fn record_v1_with_data(
+    domain: &str,
+    record: Record,
+    data: &[u8],
+    owner: &Pubkey,
+) -> Result<Fixture, SnsError> {
+    let parent = get_domain_key_with_parent(domain)?.key;
+    Ok((
+        get_record_key(domain, record, RecordVersion::V1)?,
+        name_registry_account(&parent, owner, &Pubkey::default(), data),
+    ))
+}
+
+/// The V2 `record` of `domain` with arbitrary validations. `content` is the serialized content
+pub fn record_v2(
+    domain: &str,
+    record: Record,
+    staleness_validation: Validation,
+    staleness_id: &[u8],
+    roa_validation: Validation,
+    roa_id: &[u8],
+    content: &[u8],
+) -> Result<Fixture, SnsError> {
+    let header = RecordHeader {
+        staleness_validation: staleness_validation as u16,
+        right_of_association_validation: roa_validation as u16,
+        content_length: content.len() as u32,
+    };
+    let data = [bytemuck::bytes_of(&header), staleness_id, roa_id, content].concat();
+    let parent = get_domain_key_with_parent(domain)?.key;
+    Ok((
+        get_record_key(domain, record, RecordVersion::V2)?,
+        name_registry_account(
+            &parent,
+            &CENTRAL_STATE_RECORD_V2,
+            &CENTRAL_STATE_RECORD_V2,
+            &data,
+        ),
+    ))
+}
+
+/// The V2 `record` of `domain` holding `content`, signed by `owner`, the domain owner, and with
+/// its right of association verified when the record requires one
+pub fn verified_record_v2(
+    domain: &str,
+    record: Record,
+    content: &str,
+    owner: &Pubkey,
+) -> Result<Fixture, SnsError> {
+    let content = record_v2::serialize_record_v2_content(content, record.clone())?;
+    let roa_validation = record.roa_validation();
+    let roa_id = match roa_validation {
+        Validation::None => vec![],
+        _ if matches!(record, Record::CNAME | Record::Url) => {
+            record_v2::GUARDIAN_ID.to_bytes().to_vec()
+        }
+        // The RoA of SOL and EVM records is signed by the address they contain
+        _ => content.clone(),
+    };
+    record_v2(
+        domain,
+        record,
+        Validation::Solana,
+        owner.as_ref(),
+        roa_validation,
+        &roa_id,
+        &content,
+    )
+}
+
+/// The `NftRecord` of a tokenized domain, `owner` being the owner of the domain when it was
+/// tokenized
+pub fn nft_record(domain_key: &Pubkey, owner: &Pubkey) -> Fixture {
+    let (key, nonce) = Pubkey::find_program_address(
+        &[NftRecord::SEED, &domain_key.to_bytes()],
+        &NAME_TOKENIZER_ID,
+    );
+    let record = NftRecord::new(nonce, *owner, *domain_key, get_domain_mint(domain_key));
+    let data = record
+        .try_to_vec()
+        .expect("NftRecord serialization cannot fail");
+    (key, account(&NAME_TOKENIZER_ID, data))
+}
+
+/// The NFT mint of a tokenized domain, with a supply of 1 while the NFT is not burnt
+pub fn domain_mint(domain_key: &Pubkey, supply: u64) -> Fixture {
+    let mint = spl_token::state::Mint {
+        mint_authority: COption::None,
+        supply,
+        decimals: 0,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    };
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    mint.pack_into_slice(&mut data);
+    (get_domain_mint(domain_key), account(&spl_token::ID, data))
+}
+
+/// The associated token account of `owner` holding `amount` of `mint`
+pub fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Fixture {
+    let token_account = spl_token::state::Account {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        state: spl_token::state::AccountState::Initialized,
+        ..Default::default()
+    };
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    token_account.pack_into_slice(&mut data);
+    (
+        get_associated_token_address(owner, mint),
+        account(&spl_token::ID, data),
+    )
+}
+
+/// The `FavouriteDomain` account of `owner`, pointing to `domain_key`
+pub fn favourite_domain(owner: &Pubkey, domain_key: &Pubkey) -> Fixture {
+    let data = [&[Tag::FavouriteDomain as u8][..], domain_key.as_ref()].concat();
+    (
+        derive_favourite_domain_key(owner),
+        account(&NAME_OFFERS_PROGRAM_ID, data),
+    )
+}
+
+/// The `XDomain` account of a domain bridged to `chain`, `address` being in the Wormhole format.
+/// The name registry of a bridged domain is owned by the returned key
+pub fn x_domain(domain_key: &Pubkey, chain: u16, address: [u8; 32]) -> Fixture {
+    let mut x_domain = XDomain {
+        nonce: 0,
+        owner_chain: chain,
+        owner_address: address,
+        name_account: *domain_key,
+    };
+    let key = x_domain.derive_key(&EMITTER_KEY);
+    let data = x_domain
+        .try_to_vec()
+        .expect("XDomain serialization cannot fail");
+    (key, account(&EMITTER_KEY, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "blocking")]
+    use crate::blocking::resolve::{deserialize_name_registry, deserialize_reverse};
+    #[cfg(not(feature = "blocking"))]
+    use crate::non_blocking::resolve::{deserialize_name_registry, deserialize_reverse};
+    use crate::{favourite_domain::FavouriteDomain, record::record_v1::verify_sol_record};
+    use borsh::BorshDeserialize;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_fixtures() {
+        let owner = Keypair::new();
+        let address = Pubkey::new_unique();
+        let accounts = [
+            domain("bonfida", &owner.pubkey(), &[]).unwrap(),
+            domain("dex.bonfida", &owner.pubkey(), &[]).unwrap(),
+            reverse("bonfida").unwrap(),
+            reverse("dex.bonfida").unwrap(),
+            record_v1("bonfida", Record::Url, "https://sns.id", &owner.pubkey()).unwrap(),
+            sol_record_v1("bonfida", &address, &owner).unwrap(),
+            verified_record_v2(
+                "bonfida",
+                Record::Sol,
+                &address.to_string(),
+                &owner.pubkey(),
+            )
+            .unwrap(),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let get = |key: &Pubkey| deserialize_name_registry(&accounts[key].data).unwrap();
+
+        let domain_key = get_domain_key_with_parent("bonfida").unwrap().key;
+        let sub_key = get_domain_key_with_parent("dex.bonfida").unwrap().key;
+        assert_eq!(get(&domain_key).0.owner, owner.pubkey());
+        assert_eq!(get(&sub_key).0.parent_name, domain_key);
+        let (_, data) = get(&get_reverse_key("bonfida").unwrap());
+        assert_eq!(deserialize_reverse(&data).unwrap(), "bonfida");
+        let (header, data) = get(&get_reverse_key("dex.bonfida").unwrap());
+        assert_eq!(header.parent_name, domain_key);
+        assert_eq!(deserialize_reverse(&data).unwrap(), "\0dex");
+
+        let key = get_record_key("bonfida", Record::Url, RecordVersion::V1).unwrap();
+        let (_, data) = get(&key);
+        assert_eq!(
+            record_v1::deserialize_record(&data, Record::Url, &key, &owner.pubkey()).unwrap(),
+            "https://sns.id"
+        );
+        let key = get_record_key("bonfida", Record::Sol, RecordVersion::V1).unwrap();
+        let (_, data) = get(&key);
+        assert_eq!(
+            verify_sol_record(&data, &key, &owner.pubkey()).unwrap(),
+            address
+        );
+
+        let key = get_record_key("bonfida", Record::Sol, RecordVersion::V2).unwrap();
+        let parsed = record_v2::parse_record_v2(Record::Sol, &accounts[&key].data)
+            .await
+            .unwrap();
+        assert_eq!(parsed.content, address.to_string());
+        parsed.verify_roa().unwrap();
+        parsed
+            .verify_staleness(&crate::bridge::Owner::Solana(owner.pubkey()))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_token_fixtures() {
+        let owner = Pubkey::new_unique();
+        let domain_key = get_domain_key_with_parent("bonfida").unwrap().key;
+
+        let (_, acc) = nft_record(&domain_key, &owner);
+        let record = NftRecord::deserialize(&mut acc.data.as_slice()).unwrap();
+        assert_eq!(acc.data.len() as u64, crate::snapshot::NFT_RECORD_LEN);
+        assert_eq!(record.name_account, domain_key);
+        assert_eq!(record.nft_mint, get_domain_mint(&domain_key));
+
+        let (mint, acc) = domain_mint(&domain_key, 1);
+        assert_eq!(spl_token::state::Mint::unpack(&acc.data).unwrap().supply, 1);
+        let (_, acc) = token_account(&mint, &owner, 1);
+        let token = spl_token::state::Account::unpack(&acc.data).unwrap();
+        assert_eq!((token.mint, token.owner, token.amount), (mint, owner, 1));
+
+        let (key, acc) = favourite_domain(&owner, &domain_key);
+        assert_eq!(key, derive_favourite_domain_key(&owner));
+        let favourite = FavouriteDomain::parse(&acc.data).unwrap();
+        assert_eq!(favourite.name_account, domain_key);
+
+        let (key, acc) = x_domain(&domain_key, 4, [1; 32]);
+        assert_eq!(key, crate::bridge::derive_x_domain_key(&domain_key));
+        let x_domain = crate::bridge::deserialize_x_domain(&acc.data).unwrap();
+        assert_eq!(x_domain.name_account, domain_key);
+        assert_eq!((x_domain.owner_chain, x_domain.owner_address), (4, [1; 32]));
+    }
+}
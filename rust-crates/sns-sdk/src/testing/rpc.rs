@@ -0,0 +1,237 @@
+//! An in-memory RPC node serving fixtures, to run the SDK against a local state.
+//!
+//! [`LocalRpc`] answers the account queries of the SDK (`getAccountInfo`, `getMultipleAccounts`
+//! and `getProgramAccounts` with their filters and data slices) from a set of accounts, and the
+//! rent exemption of the default rent, like a validator whose programs never run. Requests that need to execute a program, e.g.
+//! `simulateTransaction` or `sendTransaction`, are rejected.
+// `ClientError` is the error type of `RpcSender`
+#![allow(clippy::result_large_err)]
+use {
+    async_trait::async_trait,
+    serde_json::{json, Value},
+    solana_account_decoder::{UiAccount, UiAccountEncoding},
+    solana_client::{
+        client_error::{ClientError, Result as ClientResult},
+        nonblocking,
+        rpc_client::{RpcClient, RpcClientConfig},
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_request::{RpcError, RpcRequest},
+        rpc_response::{
+            Response, RpcBlockhash, RpcKeyedAccount, RpcResponseContext, RpcVersionInfo,
+        },
+        rpc_sender::{RpcSender, RpcTransportStats},
+    },
+    solana_program::{hash::Hash, pubkey::Pubkey},
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        rent::Rent,
+    },
+    std::{collections::HashMap, str::FromStr},
+};
+
+use super::Fixture;
+
+/// The slot reported by a [`LocalRpc`] unless set with [`LocalRpc::with_slot`]
+pub const LOCAL_SLOT: u64 = 100;
+
+/// An RPC node holding a fixed set of accounts
+#[derive(Debug, Clone, Default)]
+pub struct LocalRpc {
+    accounts: HashMap<Pubkey, Account>,
+    slot: u64,
+}
+
+impl LocalRpc {
+    pub fn new(fixtures: impl IntoIterator<Item = Fixture>) -> Self {
+        Self {
+            accounts: fixtures.into_iter().collect(),
+            slot: LOCAL_SLOT,
+        }
+    }
+
+    pub fn with_slot(mut self, slot: u64) -> Self {
+        self.slot = slot;
+        self
+    }
+
+    /// A non blocking client backed by this node
+    pub fn into_client(self) -> nonblocking::rpc_client::RpcClient {
+        nonblocking::rpc_client::RpcClient::new_sender(self, RpcClientConfig::default())
+    }
+
+    /// A blocking client backed by this node
+    pub fn into_blocking_client(self) -> RpcClient {
+        RpcClient::new_sender(self, RpcClientConfig::default())
+    }
+
+    fn context(&self) -> RpcResponseContext {
+        RpcResponseContext {
+            slot: self.slot,
+            api_version: None,
+        }
+    }
+
+    fn with_context<T: serde::Serialize>(&self, value: T) -> ClientResult<Value> {
+        Ok(serde_json::to_value(Response {
+            context: self.context(),
+            value,
+        })?)
+    }
+
+    fn encode(&self, key: &Pubkey, config: &RpcAccountInfoConfig) -> Option<UiAccount> {
+        self.accounts.get(key).map(|account| {
+            UiAccount::encode(
+                key,
+                account,
+                config.encoding.unwrap_or(UiAccountEncoding::Base64),
+                None,
+                config.data_slice,
+            )
+        })
+    }
+
+    fn get_account_info(&self, params: Value) -> ClientResult<Value> {
+        let (key, config): (String, Option<RpcAccountInfoConfig>) = parse(params)?;
+        let account = self.encode(&parse_pubkey(&key)?, &config.unwrap_or_default());
+        self.with_context(account)
+    }
+
+    fn get_multiple_accounts(&self, params: Value) -> ClientResult<Value> {
+        let (keys, config): (Vec<String>, Option<RpcAccountInfoConfig>) = parse(params)?;
+        let config = config.unwrap_or_default();
+        let accounts = keys
+            .iter()
+            .map(|key| Ok(self.encode(&parse_pubkey(key)?, &config)))
+            .collect::<ClientResult<Vec<_>>>()?;
+        self.with_context(accounts)
+    }
+
+    fn get_program_accounts(&self, params: Value) -> ClientResult<Value> {
+        let (program, config): (String, Option<RpcProgramAccountsConfig>) = parse(params)?;
+        let program = parse_pubkey(&program)?;
+        let config = config.unwrap_or_default();
+        let filters = config.filters.unwrap_or_default();
+        let mut accounts = self
+            .accounts
+            .iter()
+            .filter(|(_, account)| account.owner == program)
+            .filter(|(_, account)| {
+                let account = AccountSharedData::from((*account).clone());
+                filters.iter().all(|filter| filter.allows(&account))
+            })
+            .map(|(key, _)| RpcKeyedAccount {
+                pubkey: key.to_string(),
+                account: self
+                    .encode(key, &config.account_config)
+                    .expect("the account exists"),
+            })
+            .collect::<Vec<_>>();
+        accounts.sort_by(|a, b| a.pubkey.cmp(&b.pubkey));
+        if config.with_context.unwrap_or_default() {
+            self.with_context(accounts)
+        } else {
+            Ok(serde_json::to_value(accounts)?)
+        }
+    }
+}
+
+fn parse<T: serde::de::DeserializeOwned>(params: Value) -> ClientResult<T> {
+    serde_json::from_value(params)
+        .map_err(|e| RpcError::RpcRequestError(format!("Invalid params: {e}")).into())
+}
+
+fn parse_pubkey(key: &str) -> ClientResult<Pubkey> {
+    Pubkey::from_str(key)
+        .map_err(|e| RpcError::RpcRequestError(format!("Invalid pubkey {key}: {e}")).into())
+}
+
+#[async_trait]
+impl RpcSender for LocalRpc {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        match request {
+            RpcRequest::GetVersion => Ok(serde_json::to_value(RpcVersionInfo {
+                solana_core: "1.18.15".to_owned(),
+                feature_set: None,
+            })?),
+            RpcRequest::GetSlot | RpcRequest::GetBlockHeight => Ok(json!(self.slot)),
+            RpcRequest::GetAccountInfo => self.get_account_info(params),
+            RpcRequest::GetMultipleAccounts => self.get_multiple_accounts(params),
+            RpcRequest::GetProgramAccounts => self.get_program_accounts(params),
+            RpcRequest::GetMinimumBalanceForRentExemption => {
+                let (len,): (usize,) = parse(params)?;
+                Ok(json!(Rent::default().minimum_balance(len)))
+            }
+            RpcRequest::GetLatestBlockhash => self.with_context(RpcBlockhash {
+                blockhash: Hash::default().to_string(),
+                last_valid_block_height: self.slot + 150,
+            }),
+            // No transaction has ever been processed by this node
+            RpcRequest::GetSignaturesForAddress => Ok(json!([])),
+            request => Err(ClientError::from(RpcError::RpcRequestError(format!(
+                "{request} is not supported by the local RPC node"
+            )))),
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        "local".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use solana_client::rpc_filter::RpcFilterType;
+
+    #[tokio::test]
+    async fn test_local_rpc() {
+        let program = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let client = LocalRpc::new([
+            (key, testing::account(&program, vec![1, 2, 3])),
+            (Pubkey::new_unique(), testing::account(&program, vec![1])),
+        ])
+        .with_slot(7)
+        .into_client();
+
+        assert_eq!(client.get_slot().await.unwrap(), 7);
+        let account = client.get_account(&key).await.unwrap();
+        assert_eq!((account.owner, account.data), (program, vec![1, 2, 3]));
+        assert!(client.get_account(&Pubkey::new_unique()).await.is_err());
+        assert_eq!(
+            client
+                .get_minimum_balance_for_rent_exemption(3)
+                .await
+                .unwrap(),
+            testing::account(&program, vec![1, 2, 3]).lamports
+        );
+
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::DataSize(3)]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                data_slice: Some(solana_account_decoder::UiDataSliceConfig {
+                    offset: 1,
+                    length: 1,
+                }),
+                ..Default::default()
+            },
+            with_context: Some(true),
+        };
+        let accounts = client
+            .get_program_accounts_with_config(&program, config)
+            .await
+            .unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!((accounts[0].0, &accounts[0].1.data), (key, &vec![2]));
+
+        // Programs are not executed
+        let tx = solana_sdk::transaction::Transaction::default();
+        assert!(client.simulate_transaction(&tx).await.is_err());
+    }
+}
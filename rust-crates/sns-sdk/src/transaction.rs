@@ -0,0 +1,232 @@
+//! Packing of per-domain instructions into transactions.
+//!
+//! The instructions of each domain are kept together in a single transaction, and as many domains
+//! as fit within the transaction size limit are packed in the same transaction. The sending itself
+//! is done by `non_blocking::transaction::send_instruction_groups`.
+use {
+    solana_program::{instruction::Instruction, message::Message, pubkey::Pubkey},
+    solana_sdk::{compute_budget::ComputeBudgetInstruction, packet::PACKET_DATA_SIZE},
+};
+
+use crate::error::SnsError;
+
+/// The maximum compute unit limit of a transaction
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// The instructions for one domain, which always land in the same transaction
+#[derive(Debug, Clone)]
+pub struct InstructionGroup {
+    pub domain: String,
+    pub instructions: Vec<Instruction>,
+}
+
+impl InstructionGroup {
+    pub fn new(domain: impl Into<String>, instructions: Vec<Instruction>) -> Self {
+        Self {
+            domain: domain.into(),
+            instructions,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SendConfig {
+    /// The priority fee in micro-lamports per compute unit, no fee is paid when zero
+    pub compute_unit_price: u64,
+    /// The compute units requested for each domain, summed over the domains of a transaction.
+    /// The default limit of the runtime is used when `None`
+    pub compute_unit_limit: Option<u32>,
+    /// The maximum number of domains packed in one transaction, as many as fit when `None`
+    pub max_groups_per_transaction: Option<usize>,
+    /// The maximum number of transactions in flight
+    pub max_concurrency: usize,
+    /// The number of times a transaction is signed again with a new blockhash after it expired
+    /// without landing, or the latest blockhash is fetched again after a transient RPC error
+    pub max_retries: usize,
+}
+
+impl Default for SendConfig {
+    fn default() -> Self {
+        Self {
+            compute_unit_price: 0,
+            compute_unit_limit: None,
+            max_groups_per_transaction: None,
+            max_concurrency: 8,
+            max_retries: 3,
+        }
+    }
+}
+
+/// The instructions of a transaction and the indices of the groups they come from
+#[derive(Debug, Clone)]
+pub struct PackedTransaction {
+    pub groups: Vec<usize>,
+    pub instructions: Vec<Instruction>,
+}
+
+/// Returns the size in bytes of a signed transaction made of `instructions`
+pub fn transaction_size(payer: &Pubkey, instructions: &[Instruction]) -> usize {
+    let message = Message::new(instructions, Some(payer));
+    let signatures = message.header.num_required_signatures as usize;
+    short_vec_len(signatures) + 64 * signatures + message.serialize().len()
+}
+
+fn short_vec_len(len: usize) -> usize {
+    match len {
+        0..=0x7f => 1,
+        0x80..=0x3fff => 2,
+        _ => 3,
+    }
+}
+
+/// The compute budget instructions of a transaction carrying `groups` instruction groups
+pub fn compute_budget_instructions(config: &SendConfig, groups: usize) -> Vec<Instruction> {
+    let mut res = vec![];
+    if let Some(limit) = config.compute_unit_limit {
+        let limit = limit
+            .saturating_mul(groups as u32)
+            .min(MAX_COMPUTE_UNIT_LIMIT);
+        res.push(ComputeBudgetInstruction::set_compute_unit_limit(limit));
+    }
+    if config.compute_unit_price > 0 {
+        res.push(ComputeBudgetInstruction::set_compute_unit_price(
+            config.compute_unit_price,
+        ));
+    }
+    res
+}
+
+/// Packs the groups in order into as few transactions as possible, with the compute budget
+/// instructions of `config` first
+pub fn pack_instruction_groups(
+    payer: &Pubkey,
+    groups: &[InstructionGroup],
+    config: &SendConfig,
+) -> Result<Vec<PackedTransaction>, SnsError> {
+    let max_groups = config
+        .max_groups_per_transaction
+        .unwrap_or(usize::MAX)
+        .max(1);
+    let mut res: Vec<PackedTransaction> = vec![];
+    let mut current: Vec<usize> = vec![];
+
+    let build = |indices: &[usize]| {
+        let mut instructions = compute_budget_instructions(config, indices.len());
+        for i in indices {
+            instructions.extend(groups[*i].instructions.iter().cloned());
+        }
+        instructions
+    };
+
+    for (i, group) in groups.iter().enumerate() {
+        current.push(i);
+        let fits = current.len() <= max_groups
+            && transaction_size(payer, &build(&current)) <= PACKET_DATA_SIZE;
+        if fits {
+            continue;
+        }
+        current.pop();
+        if current.is_empty() {
+            return Err(SnsError::TransactionTooLarge {
+                domain: group.domain.clone(),
+                size: transaction_size(payer, &build(&[i])),
+            });
+        }
+        res.push(PackedTransaction {
+            instructions: build(&current),
+            groups: std::mem::replace(&mut current, vec![i]),
+        });
+        let size = transaction_size(payer, &build(&current));
+        if size > PACKET_DATA_SIZE {
+            return Err(SnsError::TransactionTooLarge {
+                domain: group.domain.clone(),
+                size,
+            });
+        }
+    }
+    if !current.is_empty() {
+        res.push(PackedTransaction {
+            instructions: build(&current),
+            groups: current,
+        });
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::instruction::AccountMeta;
+    use solana_sdk::{hash::Hash, signature::Keypair, signer::Signer, transaction::Transaction};
+
+    fn group(domain: &str, owner: &Pubkey, data_len: usize) -> InstructionGroup {
+        let ix = Instruction::new_with_bytes(
+            spl_name_service::ID,
+            &vec![1; data_len],
+            vec![
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(*owner, true),
+            ],
+        );
+        InstructionGroup::new(domain, vec![ix])
+    }
+
+    #[test]
+    fn test_transaction_size() {
+        let payer = Keypair::new();
+        let ixs = vec![group("a", &payer.pubkey(), 10).instructions[0].clone()];
+        let tx = Transaction::new_signed_with_payer(
+            &ixs,
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        );
+        let expected = bincode::serialize(&tx).unwrap().len();
+        assert_eq!(transaction_size(&payer.pubkey(), &ixs), expected);
+    }
+
+    #[test]
+    fn test_pack_instruction_groups() {
+        let payer = Pubkey::new_unique();
+        let groups = (0..50)
+            .map(|i| group(&format!("domain{i}"), &payer, 40))
+            .collect::<Vec<_>>();
+        let config = SendConfig {
+            compute_unit_price: 1_000,
+            compute_unit_limit: Some(10_000),
+            ..Default::default()
+        };
+        let packed = pack_instruction_groups(&payer, &groups, &config).unwrap();
+        assert!(packed.len() > 1);
+        assert_eq!(
+            packed
+                .iter()
+                .flat_map(|p| p.groups.clone())
+                .collect::<Vec<_>>(),
+            (0..50).collect::<Vec<_>>()
+        );
+        for p in &packed {
+            assert!(transaction_size(&payer, &p.instructions) <= PACKET_DATA_SIZE);
+            assert_eq!(
+                p.instructions[0],
+                ComputeBudgetInstruction::set_compute_unit_limit(10_000 * p.groups.len() as u32)
+            );
+            assert_eq!(
+                p.instructions[1],
+                ComputeBudgetInstruction::set_compute_unit_price(1_000)
+            );
+        }
+
+        let config = SendConfig {
+            max_groups_per_transaction: Some(3),
+            ..Default::default()
+        };
+        let packed = pack_instruction_groups(&payer, &groups, &config).unwrap();
+        assert_eq!(packed.len(), 17);
+        assert!(packed.iter().all(|p| p.groups.len() <= 3));
+
+        let groups = vec![group("small", &payer, 10), group("large", &payer, 2_000)];
+        let err = pack_instruction_groups(&payer, &groups, &SendConfig::default()).unwrap_err();
+        assert!(matches!(err, SnsError::TransactionTooLarge { domain, .. } if domain == "large"));
+    }
+}
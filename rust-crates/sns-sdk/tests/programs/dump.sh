@@ -0,0 +1,22 @@
+#!/usr/bin/env bash
+# Dumps the mainnet programs used by the SDK next to this script. No test loads them yet, they
+# are meant for a harness executing the programs, such as solana-program-test.
+#
+# Usage: ./dump.sh [rpc url]
+set -euo pipefail
+
+cd "$(dirname "$0")"
+url="${1:-${RPC_URL:-https://api.mainnet-beta.solana.com}}"
+
+programs=(
+    "spl_name_service namesLPneVptA9Z5rqUDD9tMTWEJwofgaYwp8cawRkX"
+    "sns_registrar jCebN34bUfdeUYJT13J1yG16XWQpt5PDx6Mse9GUqhR"
+    "name_tokenizer nftD3vbNkNqfj2Sd3HZwbpw4BxxKWr4AjGb9X38JeZk"
+    "name_offers 85iDfUvr3HJyLM2zcq5BXSiDvUWfw6cSE1FfNBo8Ap29"
+    "sns_records HP3D4D1ZCmohQGFVms2SS4LCANgJyksBf5s1F77FuFjZ"
+)
+
+for program in "${programs[@]}"; do
+    read -r name id <<<"$program"
+    solana program dump --url "$url" "$id" "$name.so"
+done